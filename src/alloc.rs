@@ -0,0 +1,222 @@
+//! # Allocator
+//! Every BSTR, SAFEARRAY and VARIANT this crate allocates or releases goes through an
+//! [`OleAllocator`]. Two backends are provided:
+//!
+//! * [`OleAut32`] (Windows only) calls the OleAut32 functions of the same names, so the
+//!   memory can be handed to and taken from any other COM code.
+//! * [`RustAllocator`] is a pure Rust implementation reproducing the OleAut32 memory
+//!   layouts: the 4 byte length prefix in front of a BSTR and the SAFEARRAY descriptor
+//!   with its `cbElements`, `rgsabound` (stored last dimension first) and the hidden
+//!   vartype in front of it. It is the default everywhere except Windows.
+//!
+//! The allocator in use is returned by [`allocator`] and can be replaced with
+//! [`set_allocator`].
+//!
+//! [`OleAllocator`]: trait.OleAllocator.html
+//! [`OleAut32`]: struct.OleAut32.html
+//! [`RustAllocator`]: struct.RustAllocator.html
+//! [`allocator`]: fn.allocator.html
+//! [`set_allocator`]: fn.set_allocator.html
+use std::sync::RwLock;
+
+#[cfg(not(windows))]
+use super::rust_alloc::RustAllocator;
+use super::sys::{BSTR, HRESULT, LONG, OLECHAR, SAFEARRAY, SAFEARRAYBOUND, UINT, VARIANT, VARTYPE, c_void};
+
+/// The OleAut32 memory management functions used by this crate.
+///
+/// Each method has the signature and the contract of the OleAut32 function it is named
+/// after (`sys_alloc_string_len` is `SysAllocStringLen`, ...), including the HRESULTs
+/// returned on failure.
+///
+/// ## Safety
+/// Implementations must produce the OleAut32 memory layouts: a BSTR points just past a
+/// 4 byte length prefix holding its length in bytes and is followed by a null character,
+/// and a SAFEARRAY descriptor is a `SAFEARRAY` followed by the rest of its `rgsabound`.
+/// Memory must only ever be released by the allocator that created it.
+///
+/// Callers of the methods must uphold the contract of the OleAut32 functions: pointers
+/// are valid and were created by this allocator.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait OleAllocator: Sync {
+    /// `SysAllocStringLen`: allocates a BSTR of `len` characters, copied from `psz` unless it is null
+    unsafe fn sys_alloc_string_len(&self, psz: *const OLECHAR, len: UINT) -> BSTR;
+    /// `SysFreeString`: frees a BSTR, null is ignored
+    unsafe fn sys_free_string(&self, bstr: BSTR);
+    /// `SysStringLen`: length of a BSTR in characters, 0 for null
+    unsafe fn sys_string_len(&self, bstr: BSTR) -> UINT;
+    /// `SysAllocStringByteLen`: allocates a BSTR of `len` bytes, copied from `psz` unless it is null
+    unsafe fn sys_alloc_string_byte_len(&self, psz: *const u8, len: UINT) -> BSTR;
+    /// `SysStringByteLen`: length of a BSTR in bytes, 0 for null
+    unsafe fn sys_string_byte_len(&self, bstr: BSTR) -> UINT;
+
+    /// `SafeArrayCreate`: creates a SAFEARRAY of `c_dims` dimensions with zeroed elements
+    unsafe fn safe_array_create(&self, vt: VARTYPE, c_dims: UINT, rgsabound: *mut SAFEARRAYBOUND) -> *mut SAFEARRAY;
+    /// `SafeArrayDestroy`: releases every element, then the data and the descriptor
+    unsafe fn safe_array_destroy(&self, psa: *mut SAFEARRAY) -> HRESULT;
+    /// `SafeArrayGetDim`: number of dimensions
+    unsafe fn safe_array_get_dim(&self, psa: *mut SAFEARRAY) -> UINT;
+    /// `SafeArrayGetElemsize`: size of an element in bytes
+    unsafe fn safe_array_get_elemsize(&self, psa: *mut SAFEARRAY) -> UINT;
+    /// `SafeArrayGetLBound`: lower bound of the 1-based dimension `n_dim`
+    unsafe fn safe_array_get_lbound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_lbound: *mut LONG) -> HRESULT;
+    /// `SafeArrayGetUBound`: upper bound of the 1-based dimension `n_dim`
+    unsafe fn safe_array_get_ubound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_ubound: *mut LONG) -> HRESULT;
+    /// `SafeArrayGetVartype`: VARTYPE of the elements
+    unsafe fn safe_array_get_vartype(&self, psa: *mut SAFEARRAY, pvt: *mut VARTYPE) -> HRESULT;
+    /// `SafeArrayLock`: increments the lock count
+    unsafe fn safe_array_lock(&self, psa: *mut SAFEARRAY) -> HRESULT;
+    /// `SafeArrayUnlock`: decrements the lock count
+    unsafe fn safe_array_unlock(&self, psa: *mut SAFEARRAY) -> HRESULT;
+    /// `SafeArrayAccessData`: locks the array and writes the address of its data to `ppv_data`
+    unsafe fn safe_array_access_data(&self, psa: *mut SAFEARRAY, ppv_data: *mut *mut c_void) -> HRESULT;
+    /// `SafeArrayUnaccessData`: unlocks an array locked by `safe_array_access_data`
+    unsafe fn safe_array_unaccess_data(&self, psa: *mut SAFEARRAY) -> HRESULT;
+    /// `SafeArrayRedim`: changes the bounds of the last dimension, keeping the elements
+    /// which are still in bounds and releasing the others
+    unsafe fn safe_array_redim(&self, psa: *mut SAFEARRAY, psabound_new: *mut SAFEARRAYBOUND) -> HRESULT;
+    /// `SafeArrayGetElement`: copies the element at `rg_indices` into `pv`
+    unsafe fn safe_array_get_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT;
+    /// `SafeArrayPutElement`: copies `pv` into the element at `rg_indices`
+    unsafe fn safe_array_put_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT;
+
+    /// `VariantClear`: releases the contents of a VARIANT and sets it to VT_EMPTY
+    unsafe fn variant_clear(&self, pvarg: *mut VARIANT) -> HRESULT;
+}
+
+#[cfg(windows)]
+static DEFAULT: OleAut32 = OleAut32;
+#[cfg(not(windows))]
+static DEFAULT: RustAllocator = RustAllocator;
+
+static ALLOCATOR: RwLock<Option<&'static dyn OleAllocator>> = RwLock::new(None);
+
+/// The allocator used by every conversion in this crate.
+///
+/// This is [`OleAut32`] on Windows and [`RustAllocator`] everywhere else, unless it has
+/// been replaced with [`set_allocator`].
+///
+/// [`OleAut32`]: struct.OleAut32.html
+/// [`RustAllocator`]: struct.RustAllocator.html
+/// [`set_allocator`]: fn.set_allocator.html
+pub fn allocator() -> &'static dyn OleAllocator {
+    match *ALLOCATOR.read().unwrap() {
+        Some(alloc) => alloc,
+        None => &DEFAULT,
+    }
+}
+
+/// Replaces the allocator used by every conversion in this crate, returning the previous one.
+///
+/// ## Safety
+/// Memory is never handed back to the allocator that did not create it, so nothing
+/// allocated by the previous allocator may be released after the switch (and vice versa).
+/// Switch before any BSTR, SAFEARRAY or VARIANT is created, or to an allocator wrapping
+/// the previous one.
+pub unsafe fn set_allocator(alloc: &'static dyn OleAllocator) -> &'static dyn OleAllocator {
+    let mut current = ALLOCATOR.write().unwrap();
+    let previous = current.unwrap_or(&DEFAULT);
+    *current = Some(alloc);
+    previous
+}
+
+/// Backend calling the OleAut32 functions.
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OleAut32;
+
+#[cfg(windows)]
+unsafe impl OleAllocator for OleAut32 {
+    unsafe fn sys_alloc_string_len(&self, psz: *const OLECHAR, len: UINT) -> BSTR {
+        oleaut32::SysAllocStringLen(psz, len)
+    }
+    unsafe fn sys_free_string(&self, bstr: BSTR) {
+        oleaut32::SysFreeString(bstr)
+    }
+    unsafe fn sys_string_len(&self, bstr: BSTR) -> UINT {
+        oleaut32::SysStringLen(bstr)
+    }
+    unsafe fn sys_alloc_string_byte_len(&self, psz: *const u8, len: UINT) -> BSTR {
+        oleaut32::SysAllocStringByteLen(psz, len)
+    }
+    unsafe fn sys_string_byte_len(&self, bstr: BSTR) -> UINT {
+        oleaut32::SysStringByteLen(bstr)
+    }
+    unsafe fn safe_array_create(&self, vt: VARTYPE, c_dims: UINT, rgsabound: *mut SAFEARRAYBOUND) -> *mut SAFEARRAY {
+        oleaut32::SafeArrayCreate(vt, c_dims, rgsabound)
+    }
+    unsafe fn safe_array_destroy(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        oleaut32::SafeArrayDestroy(psa)
+    }
+    unsafe fn safe_array_get_dim(&self, psa: *mut SAFEARRAY) -> UINT {
+        oleaut32::SafeArrayGetDim(psa)
+    }
+    unsafe fn safe_array_get_elemsize(&self, psa: *mut SAFEARRAY) -> UINT {
+        oleaut32::SafeArrayGetElemsize(psa)
+    }
+    unsafe fn safe_array_get_lbound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_lbound: *mut LONG) -> HRESULT {
+        oleaut32::SafeArrayGetLBound(psa, n_dim, pl_lbound)
+    }
+    unsafe fn safe_array_get_ubound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_ubound: *mut LONG) -> HRESULT {
+        oleaut32::SafeArrayGetUBound(psa, n_dim, pl_ubound)
+    }
+    unsafe fn safe_array_get_vartype(&self, psa: *mut SAFEARRAY, pvt: *mut VARTYPE) -> HRESULT {
+        oleaut32::SafeArrayGetVartype(psa, pvt)
+    }
+    unsafe fn safe_array_lock(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        oleaut32::SafeArrayLock(psa)
+    }
+    unsafe fn safe_array_unlock(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        oleaut32::SafeArrayUnlock(psa)
+    }
+    unsafe fn safe_array_access_data(&self, psa: *mut SAFEARRAY, ppv_data: *mut *mut c_void) -> HRESULT {
+        oleaut32::SafeArrayAccessData(psa, ppv_data)
+    }
+    unsafe fn safe_array_unaccess_data(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        oleaut32::SafeArrayUnaccessData(psa)
+    }
+    unsafe fn safe_array_redim(&self, psa: *mut SAFEARRAY, psabound_new: *mut SAFEARRAYBOUND) -> HRESULT {
+        oleaut32::SafeArrayRedim(psa, psabound_new)
+    }
+    unsafe fn safe_array_get_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT {
+        oleaut32::SafeArrayGetElement(psa, rg_indices, pv)
+    }
+    unsafe fn safe_array_put_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT {
+        oleaut32::SafeArrayPutElement(psa, rg_indices, pv)
+    }
+    unsafe fn variant_clear(&self, pvarg: *mut VARIANT) -> HRESULT {
+        oleaut32::VariantClear(pvarg)
+    }
+}
+
+#[cfg(windows)]
+#[allow(non_snake_case)]
+mod oleaut32 {
+    use super::super::sys::{BSTR, HRESULT, LONG, OLECHAR, SAFEARRAY, SAFEARRAYBOUND, UINT, VARIANT, VARTYPE, c_void};
+
+    #[link(name="OleAut32")]
+    extern "system" {
+        pub(super) fn SysAllocStringLen(strIn: *const OLECHAR, ui: UINT) -> BSTR;
+        pub(super) fn SysFreeString(bstrString: BSTR);
+        pub(super) fn SysStringLen(pbstr: BSTR) -> UINT;
+        pub(super) fn SysAllocStringByteLen(psz: *const u8, len: UINT) -> BSTR;
+        pub(super) fn SysStringByteLen(bstr: BSTR) -> UINT;
+
+        pub(super) fn SafeArrayCreate(vt: VARTYPE, cDims: UINT, rgsabound: *mut SAFEARRAYBOUND) -> *mut SAFEARRAY;
+        pub(super) fn SafeArrayDestroy(psa: *mut SAFEARRAY) -> HRESULT;
+        pub(super) fn SafeArrayGetDim(psa: *mut SAFEARRAY) -> UINT;
+        pub(super) fn SafeArrayGetElemsize(psa: *mut SAFEARRAY) -> UINT;
+        pub(super) fn SafeArrayGetLBound(psa: *mut SAFEARRAY, nDim: UINT, plLbound: *mut LONG) -> HRESULT;
+        pub(super) fn SafeArrayGetUBound(psa: *mut SAFEARRAY, nDim: UINT, plUbound: *mut LONG) -> HRESULT;
+        pub(super) fn SafeArrayGetVartype(psa: *mut SAFEARRAY, pvt: *mut VARTYPE) -> HRESULT;
+        pub(super) fn SafeArrayLock(psa: *mut SAFEARRAY) -> HRESULT;
+        pub(super) fn SafeArrayUnlock(psa: *mut SAFEARRAY) -> HRESULT;
+        pub(super) fn SafeArrayAccessData(psa: *mut SAFEARRAY, ppvData: *mut *mut c_void) -> HRESULT;
+        pub(super) fn SafeArrayUnaccessData(psa: *mut SAFEARRAY) -> HRESULT;
+        pub(super) fn SafeArrayRedim(psa: *mut SAFEARRAY, psaboundNew: *mut SAFEARRAYBOUND) -> HRESULT;
+        pub(super) fn SafeArrayGetElement(psa: *mut SAFEARRAY, rgIndices: *const LONG, pv: *mut c_void) -> HRESULT;
+        pub(super) fn SafeArrayPutElement(psa: *mut SAFEARRAY, rgIndices: *const LONG, pv: *mut c_void) -> HRESULT;
+
+        pub(super) fn VariantClear(pvarg: *mut VARIANT) -> HRESULT;
+    }
+}
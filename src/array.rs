@@ -1,50 +1,59 @@
 use std::marker::PhantomData;
 use std::mem;
-use std::ptr::null_mut;
+use std::ptr::{self, null_mut};
 
 use rust_decimal::Decimal;
 
-use winapi::ctypes::{c_long, c_void};
-use winapi::shared::minwindef::{UINT, ULONG,};
-use winapi::shared::ntdef::HRESULT;
-use winapi::shared::wtypes::{
-    CY, 
-    DATE, 
-    DECIMAL,  
-    VARTYPE,
+use super::alloc::allocator;
+use super::bstr::{BStr, BString};
+use super::errors::{
+    FromSafeArrayError, 
+    FromSafeArrElemError, 
+    IntoSafeArrayError, 
+    IntoSafeArrElemError,
+    FromVariantError,
+};
+use super::ptr::Ptr;
+use super::sys::{
+    BSTR,
+    CY,
+    DATE,
+    DECIMAL,
+    IDispatch,
+    IUnknown,
+    LONG,
+    SAFEARRAY,
+    SAFEARRAYBOUND,
+    ULONG,
+    VARIANT,
     VARIANT_BOOL,
+    VARTYPE,
     VT_BOOL,
+    VT_BSTR,
     VT_CY,
     VT_DATE,
-    VT_DECIMAL, 
+    VT_DECIMAL,
     VT_DISPATCH,
     VT_ERROR,
-    VT_I1, 
-    VT_I2, 
+    VT_I1,
+    VT_I2,
     VT_I4,
     VT_INT,
-    VT_R4, 
-    VT_R8, 
+    VT_R4,
+    VT_R8,
     VT_UI1,
     VT_UI2,
     VT_UI4,
     VT_UINT,
-    VT_UNKNOWN, 
-    VT_VARIANT,   
+    VT_UNKNOWN,
+    VT_VARIANT,
+    c_void,
 };
-
-use winapi::um::oaidl::{IDispatch, LPSAFEARRAY, LPSAFEARRAYBOUND, SAFEARRAY, SAFEARRAYBOUND, VARIANT};
-use winapi::um::unknwnbase::IUnknown;
-
-use super::errors::{
-    FromSafeArrayError, 
-    FromSafeArrElemError, 
-    IntoSafeArrayError, 
-    IntoSafeArrElemError,
-};
-use super::ptr::Ptr;
+use super::tracking;
 use super::types::{Currency, Date, DecWrapper, Int, SCode, UInt, VariantBool};
+use super::value::VariantValue;
 use super::variant::{Variant, VariantExt};
+use super::vartype::VarType;
 
 /// Helper trait implemented for types that can be converted into a safe array. 
 /// 
@@ -52,10 +61,11 @@ use super::variant::{Variant, VariantExt};
 /// 
 /// * `i8`, `u8`, `i16`, `u16`, `i32`, `u32`
 /// * `bool`, `f32`, `f64`
-/// * `String`, [`Variant<T>`], 
+/// * `String`, [`Variant<T>`], [`VariantValue`]
 /// * [`Ptr<IUnknown>`], [`Ptr<IDispatch>`]
 ///  
 /// [`Variant<T>`]: struct.Variant.html
+/// [`VariantValue`]: enum.VariantValue.html
 /// [`Ptr<IUnknown>`]: struct.Ptr.html
 /// [`Ptr<IDispatch>`]: struct.Ptr.html
 /// 
@@ -66,11 +76,8 @@ use super::variant::{Variant, VariantExt};
 /// 
 /// ```
 /// extern crate oaidl;
-/// extern crate winapi;
-/// 
-/// use winapi::shared::wtypes::VT_I4;
-/// use winapi::um::oaidl::SAFEARRAY;
 /// 
+/// use oaidl::sys::{SAFEARRAY, VT_I4};
 /// use oaidl::{SafeArrayElement, IntoSafeArrElemError, FromSafeArrElemError};
 /// 
 /// 
@@ -97,13 +104,159 @@ pub trait SafeArrayElement: Sized {
     
     /// gets a type from the safearray at the specified index (default impls use SafeArrayGetElement)
     fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError>;
+
+    /// Puts a type into a safearray of any number of dimensions, with one index per
+    /// dimension, first dimension first. The default only handles one dimension and
+    /// calls [`into_safearray`].
+    ///
+    /// [`into_safearray`]: #tymethod.into_safearray
+    fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+        match *ixs {
+            [ix] => self.into_safearray(psa, ix), 
+            _ => Err(IntoSafeArrElemError::IndicesInvalid{count: ixs.len()})
+        }
+    }
+
+    /// Gets a type from a safearray of any number of dimensions, with one index per
+    /// dimension, first dimension first. The default only handles one dimension and
+    /// calls [`from_safearray`].
+    ///
+    /// [`from_safearray`]: #tymethod.from_safearray
+    fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+        match *ixs {
+            [ix] => Self::from_safearray(psa, ix), 
+            _ => Err(FromSafeArrElemError::IndicesInvalid{count: ixs.len()})
+        }
+    }
+
+    /// Whether a SAFEARRAY of vartype `vt` can be read as elements of this type. Only
+    /// `SFTYPE` by default.
+    fn accepts_vartype(vt: u32) -> bool {
+        vt == Self::SFTYPE
+    }
+
+    /// `Some` for [`SafeArrayPod`] types, whose arrays are then converted by copying
+    /// through the data pointer instead of element by element. Only this crate can
+    /// provide it.
+    ///
+    /// [`SafeArrayPod`]: trait.SafeArrayPod.html
+    #[doc(hidden)]
+    const POD: Option<PodElement<Self>> = None;
+}
+
+/// Marker for element types stored in a SAFEARRAY exactly as they are in Rust, so their
+/// data can be used in place as a `[T]`. [`SafeArrayExt`] converts arrays of these types
+/// by copying through the data pointer instead of element by element.
+///
+/// Implemented for `i8`, `u8`, `i16`, `u16`, `i32`, `u32`, `f32`, `f64` and the wrappers
+/// [`Currency`], [`Date`], [`Int`], [`UInt`], [`SCode`] and [`VariantBool`]. `bool` is
+/// not, since its arrays hold 2 byte `VARIANT_BOOL`s: use `VariantBool` to convert them in
+/// bulk.
+///
+/// [`Currency`]: struct.Currency.html
+/// [`Date`]: struct.Date.html
+/// [`Int`]: struct.Int.html
+/// [`UInt`]: struct.UInt.html
+/// [`SCode`]: struct.SCode.html
+/// [`VariantBool`]: struct.VariantBool.html
+/// [`SafeArrayExt`]: trait.SafeArrayExt.html
+///
+/// ## Safety
+///
+/// The type must have the size and layout of the SAFEARRAY elements of vartype `SFTYPE`,
+/// own nothing, and be valid for every bit pattern.
+pub unsafe trait SafeArrayPod: SafeArrayElement + Copy {}
+
+macro_rules! safe_arr_pod_impl {
+    ($($t:ty),+) => {
+        $(unsafe impl SafeArrayPod for $t {})+
+    };
+}
+safe_arr_pod_impl!{i8, u8, i16, u16, i32, u32, f32, f64, Currency, Date, Int, UInt, SCode, VariantBool}
+
+/// Proof that `T` is a [`SafeArrayPod`], held by `SafeArrayElement::POD` so generic code
+/// can take the bulk path
+///
+/// [`SafeArrayPod`]: trait.SafeArrayPod.html
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct PodElement<T>(PhantomData<T>);
+
+impl<T> Clone for PodElement<T> {
+    fn clone(&self) -> PodElement<T> {
+        *self
+    }
+}
+
+impl<T> Copy for PodElement<T> {}
+
+impl<T: SafeArrayPod> PodElement<T> {
+    #[doc(hidden)]
+    pub const fn new() -> PodElement<T> {
+        PodElement(PhantomData)
+    }
+}
+
+/// The `cElements` of a dimension holding `len` elements from `lbound`, failing with
+/// `BoundsOverflow` unless every index up to `lbound + len - 1` fits an `i32`.
+#[allow(clippy::legacy_numeric_constants, clippy::redundant_field_names)]
+pub(crate) fn check_bounds(lbound: i32, len: usize) -> Result<ULONG, IntoSafeArrayError> {
+    if len as u64 > u64::from(u32::max_value()) || i64::from(lbound) + len as i64 - 1 > i64::from(i32::max_value()) {
+        return Err(IntoSafeArrayError::BoundsOverflow{lbound: lbound, len: len});
+    }
+    Ok(len as ULONG)
+}
+
+impl<T> PodElement<T> {
+    /// Writes up to `len` items straight into the data of `psa`, created with `len`
+    /// zeroed elements of vartype `T::SFTYPE`
+    #[allow(clippy::redundant_field_names)]
+    unsafe fn write<I: Iterator<Item=T>>(self, psa: *mut SAFEARRAY, items: I, len: usize) -> Result<(), IntoSafeArrayError> {
+        let mut data: *mut c_void = null_mut();
+        let hr = allocator().safe_array_access_data(psa, &mut data);
+        if hr != 0 {
+            return Err(IntoSafeArrayError::SafeArrayAccessDataFailed{hr: hr});
+        }
+        let data = data as *mut T;
+        for (ix, item) in items.take(len).enumerate() {
+            ptr::write(data.add(ix), item);
+        }
+        allocator().safe_array_unaccess_data(psa);
+        Ok(())
+    }
+
+    /// Copies the `len` elements of `psa`, whose vartype is `T::SFTYPE`, in one go
+    #[allow(clippy::redundant_field_names)]
+    unsafe fn read(self, psa: *mut SAFEARRAY, len: usize) -> Result<Vec<T>, FromSafeArrayError> {
+        let cb = allocator().safe_array_get_elemsize(psa);
+        if cb as usize != mem::size_of::<T>() {
+            return Err(FromSafeArrayError::ElementSizeDoesNotMatch{expected: mem::size_of::<T>(), found: cb});
+        }
+        let mut data: *mut c_void = null_mut();
+        let hr = allocator().safe_array_access_data(psa, &mut data);
+        if hr != 0 {
+            return Err(FromSafeArrayError::SafeArrayAccessDataFailed{hr: hr});
+        }
+        let mut vc: Vec<T> = Vec::with_capacity(len);
+        if len > 0 {
+            ptr::copy_nonoverlapping(data as *const T, vc.as_mut_ptr(), len);
+            vc.set_len(len);
+        }
+        allocator().safe_array_unaccess_data(psa);
+        Ok(vc)
+    }
 }
 
 /// Workhorse trait and main interface for converting to/from SAFEARRAY. 
 /// Default impl is on `ExactSizeIterator<Item=SafeArrayElement>` 
 pub trait SafeArrayExt<T: SafeArrayElement> {
     /// Use `t.into_safearray()` to convert a type into a SAFEARRAY
+    #[allow(clippy::wrong_self_convention)]
     fn into_safearray(&mut self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
+
+    /// Like `into_safearray`, with `lbound` as the lower bound instead of 0
+    #[allow(clippy::wrong_self_convention)]
+    fn into_safearray_with_lbound(&mut self, lbound: i32) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError>;
     
     /// Use `T::from_safearray(psa)` to convert a safearray pointer into the relevant T
     fn from_safearray(psa: *mut SAFEARRAY) -> Result<Vec<T>, FromSafeArrayError>;
@@ -118,13 +271,35 @@ macro_rules! check_and_throw {
     };
 }
 
-struct SafeArrayDestructor {
-    inner: *mut SAFEARRAY, 
+/// Whether `ixs` has one index per dimension of `psa`, as Safe[Get|Put]Element reads
+fn indices_match(psa: *mut SAFEARRAY, ixs: &[i32]) -> bool {
+    !psa.is_null() && unsafe { allocator().safe_array_get_dim(psa) } as usize == ixs.len()
+}
+
+/// Whether the elements of `psa` are VARIANTs
+fn holds_variants(psa: *mut SAFEARRAY) -> bool {
+    let mut vt: VARTYPE = 0;
+    let hr = unsafe { allocator().safe_array_get_vartype(psa, &mut vt) };
+    hr == 0 && u32::from(vt) == VT_VARIANT
+}
+
+/// Decodes a BSTR element, failing on invalid UTF-16
+#[allow(clippy::redundant_field_names)]
+fn bstr_to_string(bstr: BSTR) -> Result<String, FromSafeArrElemError> {
+    match unsafe { BStr::from_ptr(bstr) }.to_string_strict() {
+        Ok(s) => Ok(s), 
+        Err(FromVariantError::InvalidUtf16{index}) => Err(FromSafeArrElemError::InvalidUtf16{index: index}), 
+        Err(_) => Err(FromSafeArrElemError::FromVariantFailed)
+    }
+}
+
+pub(crate) struct SafeArrayDestructor {
+    pub(crate) inner: *mut SAFEARRAY, 
     _marker: PhantomData<SAFEARRAY>
 }
 
 impl SafeArrayDestructor {
-    fn new(p: *mut SAFEARRAY) -> SafeArrayDestructor {
+    pub(crate) fn new(p: *mut SAFEARRAY) -> SafeArrayDestructor {
         assert!(!p.is_null(), "SafeArrayDestructor initialized with null *mut SAFEARRAY pointer.");
         SafeArrayDestructor{
             inner: p, 
@@ -139,7 +314,7 @@ impl Drop for SafeArrayDestructor {
             return;
         }
         unsafe {
-            SafeArrayDestroy(self.inner)
+            allocator().safe_array_destroy(self.inner)
         };
         self.inner = null_mut();
     }
@@ -150,15 +325,26 @@ where I: ExactSizeIterator + ?Sized,
       I::Item: SafeArrayElement
 {
     fn into_safearray(&mut self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError > {
-        let c_elements: ULONG = self.len() as u32;
+        self.into_safearray_with_lbound(0)
+    }
+
+    fn into_safearray_with_lbound(&mut self, lbound: i32) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError > {
+        let _tag = tracking::tag::<Self>("into_safearray");
+        let c_elements: ULONG = check_bounds(lbound, self.len())?;
         let vartype = I::Item::SFTYPE;
-        let mut sab = SAFEARRAYBOUND { cElements: c_elements, lLbound: 0i32};
-        let psa = unsafe { SafeArrayCreate(vartype as u16, 1, &mut sab)};
+        let mut sab = SAFEARRAYBOUND { cElements: c_elements, lLbound: lbound};
+        let psa = unsafe { allocator().safe_array_create(vartype as u16, 1, &mut sab)};
         assert!(!psa.is_null());
         let mut sad = SafeArrayDestructor::new(psa);
 
-        for (ix, mut elem) in self.enumerate() {
-            match elem.into_safearray(psa, ix as i32) {
+        if let Some(pod) = I::Item::POD {
+            unsafe { pod.write(psa, self, c_elements as usize) }?;
+            sad.inner = null_mut();
+            return Ok(Ptr::with_checked(psa).unwrap());
+        }
+
+        for (ix, elem) in self.enumerate() {
+            match elem.into_safearray(psa, (i64::from(lbound) + ix as i64) as i32) {
                 Ok(()) => continue, 
                 Err(e) => return Err(IntoSafeArrayError::from_element_err(e, ix))
             }
@@ -169,45 +355,59 @@ where I: ExactSizeIterator + ?Sized,
     }
 
     fn from_safearray(psa: *mut SAFEARRAY) -> Result<Vec<I::Item>, FromSafeArrayError> {
+        let _tag = tracking::tag::<Vec<I::Item>>("from_safearray");
         //Stack sentinel to ensure safearray is released even if there is a panic or early return.
         let _sad = SafeArrayDestructor::new(psa);
-        let sa_dims = unsafe { SafeArrayGetDim(psa) };
-        assert!(sa_dims > 0); //Assert its not a dimensionless safe array
-        let vt = unsafe {
-            let mut vt: VARTYPE = 0;
-            let hr = SafeArrayGetVartype(psa, &mut vt);
-            check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr})});
-            vt
+        let (_, vc) = read_vector(psa)?;
+        Ok(vc)
+    }
+} 
+
+/// Reads every element of a one dimensional SAFEARRAY, returning its lower bound with
+/// them. Does not destroy the array.
+#[allow(clippy::redundant_field_names)]
+pub(crate) fn read_vector<T: SafeArrayElement>(psa: *mut SAFEARRAY) -> Result<(i32, Vec<T>), FromSafeArrayError> {
+    let sa_dims = unsafe { allocator().safe_array_get_dim(psa) };
+    assert!(sa_dims > 0); //Assert its not a dimensionless safe array
+    let vt = unsafe {
+        let mut vt: VARTYPE = 0;
+        let hr = allocator().safe_array_get_vartype(psa, &mut vt);
+        check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr})});
+        vt
+    };
+
+    if !T::accepts_vartype(u32::from(vt)) {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: VarType::from(T::SFTYPE), found: VarType::from(vt as u32)});
+    }
+
+    if sa_dims == 1 {
+        let (l_bound, r_bound) = unsafe {
+            let mut l_bound: LONG = 0;
+            let mut r_bound: LONG = 0;
+            let hr = allocator().safe_array_get_lbound(psa, 1, &mut l_bound);
+            check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayLBoundFailed{hr: hr})});
+            let hr = allocator().safe_array_get_ubound(psa, 1, &mut r_bound);
+            check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayRBoundFailed{hr: hr})});
+            (l_bound, r_bound)
         };
 
-        if vt as u32 != I::Item::SFTYPE {
-            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: I::Item::SFTYPE, found: vt as u32});
+        if let Some(pod) = T::POD {
+            let len = (i64::from(r_bound) - i64::from(l_bound) + 1) as usize;
+            return Ok((l_bound, unsafe { pod.read(psa, len) }?));
         }
 
-        if sa_dims == 1 {
-            let (l_bound, r_bound) = unsafe {
-                let mut l_bound: c_long = 0;
-                let mut r_bound: c_long = 0;
-                let hr = SafeArrayGetLBound(psa, 1, &mut l_bound);
-                check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayLBoundFailed{hr: hr})});
-                let hr = SafeArrayGetUBound(psa, 1, &mut r_bound);
-                check_and_throw!(hr, {}, {return Err(FromSafeArrayError::SafeArrayRBoundFailed{hr: hr})});
-                (l_bound, r_bound)
-            };
-
-            let mut vc: Vec<I::Item> = Vec::new();
-            for ix in l_bound..=r_bound {
-                match I::Item::from_safearray(psa, ix) {
-                    Ok(val) => vc.push(val), 
-                    Err(e) => return Err(FromSafeArrayError::from_element_err(e, ix as usize))
-                }
+        let mut vc: Vec<T> = Vec::new();
+        for ix in l_bound..=r_bound {
+            match T::from_safearray(psa, ix) {
+                Ok(val) => vc.push(val), 
+                Err(e) => return Err(FromSafeArrayError::from_element_err(e, ix as usize))
             }
-            Ok(vc)
-        } else {
-            Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims})
         }
+        Ok((l_bound, vc))
+    } else {
+        Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims})
     }
-} 
+}
 
 macro_rules! safe_arr_impl {
     (
@@ -221,15 +421,31 @@ macro_rules! safe_arr_impl {
     ) => {
         impl $(<$tn:$tc>)* SafeArrayElement for $t {
             const SFTYPE: u32 = $vt;
-             fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+            fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+                Self::from_safearray_at(psa, &[ix])
+            }
+
+            fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+                self.into_safearray_at(psa, &[ix])
+            }
+
+            #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+            fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+                if !indices_match(psa, ixs) {
+                    return Err(FromSafeArrElemError::IndicesInvalid{count: ixs.len()});
+                }
                 let val = $def;
-                let hr = unsafe {SafeArrayGetElement(psa, &ix, val as *mut _ as *mut c_void)};
+                let hr = unsafe {allocator().safe_array_get_element(psa, ixs.as_ptr(), val as *mut _ as *mut c_void)};
                 check_and_throw!(hr, $from(val), {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})})
             }
             
-            fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+            #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+            fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+                if !indices_match(psa, ixs) {
+                    return Err(IntoSafeArrElemError::IndicesInvalid{count: ixs.len()});
+                }
                 let slf = $into(self)?;
-                let hr = unsafe {SafeArrayPutElement(psa, &ix, slf as *mut _ as *mut c_void)};
+                let hr = unsafe {allocator().safe_array_put_element(psa, ixs.as_ptr(), slf as *mut _ as *mut c_void)};
                 check_and_throw!(hr, {return Ok(())}, {Err(IntoSafeArrElemError::PutElementFailed{hr: hr})})
             }
         }
@@ -237,6 +453,7 @@ macro_rules! safe_arr_impl {
     (
         impl $(< $tn:ident : $tc:ident >)* SafeArrayElement for $t:ty {
             SFTYPE = $vt:expr;
+            $(POD = $pod:expr;)*
             def  => {$def:expr}
             from => {$from:expr}
             into => {$into:expr}
@@ -244,15 +461,32 @@ macro_rules! safe_arr_impl {
     ) => {
         impl $(<$tn:$tc>)* SafeArrayElement for $t {
             const SFTYPE: u32 = $vt;
-             fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+            $(const POD: Option<PodElement<Self>> = $pod;)*
+            fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+                Self::from_safearray_at(psa, &[ix])
+            }
+
+            fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+                self.into_safearray_at(psa, &[ix])
+            }
+
+            #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_closure, clippy::redundant_field_names)]
+            fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+                if !indices_match(psa, ixs) {
+                    return Err(FromSafeArrElemError::IndicesInvalid{count: ixs.len()});
+                }
                 let mut val = $def;
-                let hr = unsafe {SafeArrayGetElement(psa, &ix, &mut val as *mut _ as *mut c_void)};
+                let hr = unsafe {allocator().safe_array_get_element(psa, ixs.as_ptr(), &mut val as *mut _ as *mut c_void)};
                 check_and_throw!(hr, $from(val), {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})})
             }
             
-            fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+            #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+            fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+                if !indices_match(psa, ixs) {
+                    return Err(IntoSafeArrElemError::IndicesInvalid{count: ixs.len()});
+                }
                 let mut slf = $into(self)?;
-                let hr = unsafe {SafeArrayPutElement(psa, &ix, &mut slf as *mut _ as *mut c_void)};
+                let hr = unsafe {allocator().safe_array_put_element(psa, ixs.as_ptr(), &mut slf as *mut _ as *mut c_void)};
                 check_and_throw!(hr, {return Ok(())}, {Err(IntoSafeArrElemError::PutElementFailed{hr: hr})})
             }
         }
@@ -261,69 +495,106 @@ macro_rules! safe_arr_impl {
 
 safe_arr_impl!{impl SafeArrayElement for i16 {
     SFTYPE = VT_I2;
+    POD = Some(PodElement::new());
     def => { 0i16 }
     from => {|i| Ok(i)}
     into => { |slf: i16| -> Result<_, IntoSafeArrElemError> {Ok(slf)} }
 }}
 safe_arr_impl!{impl SafeArrayElement for i32 {
     SFTYPE = VT_I4;
+    POD = Some(PodElement::new());
     def => { 0i32 }
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for f32 {
     SFTYPE = VT_R4;
+    POD = Some(PodElement::new());
     def => { 0.0f32 }
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for f64 { 
     SFTYPE = VT_R8; 
+    POD = Some(PodElement::new());
     def => { 0.0f64 }
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for Currency{
     SFTYPE = VT_CY; 
+    POD = Some(PodElement::new());
     def => { CY{int64: 0} }
     from => { |cy| Ok(Currency::from(cy)) }
     into => {|slf: Currency| -> Result<_, IntoSafeArrElemError> {Ok(CY::from(slf))}}
 }}
 safe_arr_impl!{impl SafeArrayElement for Date{
     SFTYPE = VT_DATE; 
+    POD = Some(PodElement::new());
     def =>  { 0f64 }
     from => { |dt| Ok(Date::from(dt)) } 
     into => { |slf: Date| -> Result<_, IntoSafeArrElemError> {Ok(DATE::from(slf)) }}
 }}
-// Need to wrap the string in a variant because its not working otherwise. 
-safe_arr_impl!{impl SafeArrayElement for String {
-    SFTYPE = VT_VARIANT;
-    ptr
-    def => {{
-        let mut var: VARIANT = unsafe {mem::zeroed()};
-        &mut var as *mut VARIANT
-    }}
-    from => {|pvar| {
-        let pnn = match Ptr::with_checked(pvar) {
-            Some(nn) => nn, 
-            None => return Err(FromSafeArrElemError::VariantPtrNull)
-        };
-        match Variant::<String>::from_variant(pnn) {
-            Ok(var) => Ok(var.unwrap()), 
-            Err(_) => return Err(FromSafeArrElemError::FromVariantFailed)
+// Strings go into arrays of BSTRs owned by the array, and are also read from arrays of
+// VARIANTs holding BSTRs, the layout used before.
+impl SafeArrayElement for String {
+    const SFTYPE: u32 = VT_BSTR;
+
+    fn accepts_vartype(vt: u32) -> bool {
+        vt == VT_BSTR || vt == VT_VARIANT
+    }
+
+    fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+        Self::from_safearray_at(psa, &[ix])
+    }
+
+    fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+        self.into_safearray_at(psa, &[ix])
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+    fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+        if !indices_match(psa, ixs) {
+            return Err(FromSafeArrElemError::IndicesInvalid{count: ixs.len()});
         }
-    }}
-    into => {|slf: String|{
-        let slf = Variant::new(slf);
-        match slf.into_variant() {
-            Ok(slf) => {
-                let mut s = slf.as_ptr();
-                Ok(s)
-            }, 
-            Err(ive) => Err(IntoSafeArrElemError::from(ive))
+        if holds_variants(psa) {
+            let mut var: VARIANT = unsafe {mem::zeroed()};
+            let hr = unsafe {allocator().safe_array_get_element(psa, ixs.as_ptr(), &mut var as *mut _ as *mut c_void)};
+            check_and_throw!(hr, {}, {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})});
+            let n2 = unsafe {var.n1.n2()};
+            let ret = if u32::from(n2.vt) == VT_BSTR {
+                bstr_to_string(unsafe {*n2.n3.bstrVal()})
+            } else {
+                Err(FromSafeArrElemError::FromVariantFailed)
+            };
+            unsafe {allocator().variant_clear(&mut var)};
+            return ret;
         }
-    }}
-}}
+        let mut bstr: BSTR = null_mut();
+        let hr = unsafe {allocator().safe_array_get_element(psa, ixs.as_ptr(), &mut bstr as *mut _ as *mut c_void)};
+        check_and_throw!(hr, {}, {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})});
+        let bstr = unsafe {BString::from_raw(bstr)};
+        bstr_to_string(bstr.as_ptr())
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+    fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+        if !indices_match(psa, ixs) {
+            return Err(IntoSafeArrElemError::IndicesInvalid{count: ixs.len()});
+        }
+        // SafeArrayPutElement copies the BSTR or VARIANT, so ours is freed afterwards
+        let hr = if holds_variants(psa) {
+            let mut var = self.into_raw_variant()?;
+            let hr = unsafe {allocator().safe_array_put_element(psa, ixs.as_ptr(), &mut var as *mut _ as *mut c_void)};
+            unsafe {allocator().variant_clear(&mut var)};
+            hr
+        } else {
+            let bstr = BString::from_str(&self)?;
+            unsafe {allocator().safe_array_put_element(psa, ixs.as_ptr(), bstr.as_ptr() as *mut c_void)}
+        };
+        check_and_throw!(hr, {Ok(())}, {Err(IntoSafeArrElemError::PutElementFailed{hr: hr})})
+    }
+}
 safe_arr_impl!{impl SafeArrayElement for Ptr<IDispatch>{
     SFTYPE = VT_DISPATCH; 
     ptr
@@ -341,6 +612,7 @@ safe_arr_impl!{impl SafeArrayElement for Ptr<IDispatch>{
 }}
 safe_arr_impl!{impl SafeArrayElement for SCode {
     SFTYPE = VT_ERROR;
+    POD = Some(PodElement::new());
     def => {0}
     from => {|sc| Ok(SCode::from(sc))}
     into => { |slf: SCode| -> Result<_, IntoSafeArrElemError> {Ok(i32::from(slf)) }}
@@ -353,6 +625,13 @@ safe_arr_impl!{impl SafeArrayElement for bool {
         |slf: bool| -> Result<_, IntoSafeArrElemError> { Ok(VARIANT_BOOL::from(VariantBool::from(slf)))}
     }
 }}
+safe_arr_impl!{impl SafeArrayElement for VariantBool {
+    SFTYPE = VT_BOOL;
+    POD = Some(PodElement::new());
+    def => {0}
+    from => {|vb| Ok(VariantBool::from(vb))}
+    into => { |slf: VariantBool| -> Result<_, IntoSafeArrElemError> {Ok(VARIANT_BOOL::from(slf)) }}
+}}
 safe_arr_impl!{impl <T: VariantExt> SafeArrayElement for Variant<T> {
     SFTYPE = VT_VARIANT;
     ptr
@@ -373,13 +652,61 @@ safe_arr_impl!{impl <T: VariantExt> SafeArrayElement for Variant<T> {
     into => {|slf: Variant<T>| -> Result<*mut VARIANT, IntoSafeArrElemError>{
         match slf.into_variant() {
             Ok(slf) => {
-                let mut s = slf.as_ptr();
+                let s = slf.as_ptr();
                 Ok(s)
             }, 
             Err(ive) => Err(IntoSafeArrElemError::from(ive))
         }
     }}
 }}
+// Each element keeps its own vartype, as in the `object[]` of C# or a row of Excel cells
+impl SafeArrayElement for VariantValue {
+    const SFTYPE: u32 = VT_VARIANT;
+
+    fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+        Self::from_safearray_at(psa, &[ix])
+    }
+
+    fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+        self.into_safearray_at(psa, &[ix])
+    }
+
+    /// Reads a copy of the element, which the caller owns. A `Bstr` is copied, `Dispatch`
+    /// and `Unknown` hold a reference added for the caller, who must release it, and
+    /// `Array` and `Record` values are copies the caller must free.
+    #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+    fn from_safearray_at(psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<Self, FromSafeArrElemError> {
+        if !indices_match(psa, ixs) {
+            return Err(FromSafeArrElemError::IndicesInvalid{count: ixs.len()});
+        }
+        let mut var: VARIANT = unsafe {mem::zeroed()};
+        let hr = unsafe {allocator().safe_array_get_element(psa, ixs.as_ptr(), &mut var as *mut _ as *mut c_void)};
+        check_and_throw!(hr, {}, {return Err(FromSafeArrElemError::GetElementFailed{hr: hr})});
+        let val = unsafe {VariantValue::from_raw(&var)};
+        match val {
+            // The copied string is all the value keeps, anything else it holds is handed over
+            Ok(VariantValue::Bstr(_)) | Err(_) => {
+                unsafe {allocator().variant_clear(&mut var)};
+            },
+            Ok(_) => {}
+        }
+        val.map_err(|_| FromSafeArrElemError::FromVariantFailed)
+    }
+
+    /// Copies the value into the element, taking ownership of it: the array adds its own
+    /// reference to interfaces and its own copy of arrays and records, and the ones the
+    /// value held are released.
+    #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+    fn into_safearray_at(self, psa: *mut SAFEARRAY, ixs: &[i32]) -> Result<(), IntoSafeArrElemError> {
+        if !indices_match(psa, ixs) {
+            return Err(IntoSafeArrElemError::IndicesInvalid{count: ixs.len()});
+        }
+        let mut var = self.into_raw_variant()?;
+        let hr = unsafe {allocator().safe_array_put_element(psa, ixs.as_ptr(), &mut var as *mut _ as *mut c_void)};
+        unsafe {allocator().variant_clear(&mut var)};
+        check_and_throw!(hr, {Ok(())}, {Err(IntoSafeArrElemError::PutElementFailed{hr: hr})})
+    }
+}
 safe_arr_impl!{impl SafeArrayElement for Ptr<IUnknown> {
     SFTYPE = VT_UNKNOWN; 
     ptr
@@ -418,73 +745,60 @@ safe_arr_impl!{impl SafeArrayElement for DecWrapper {
 //VT_RECORD
 safe_arr_impl!{impl SafeArrayElement for i8 {
     SFTYPE = VT_I1;
+    POD = Some(PodElement::new());
     def => { 0i8 }
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for u8 {
     SFTYPE = VT_UI1;
+    POD = Some(PodElement::new());
     def => { 0u8}
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for u16 {
     SFTYPE = VT_UI2;
+    POD = Some(PodElement::new());
     def => { 0u16 }
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for u32 {
     SFTYPE = VT_UI4;
+    POD = Some(PodElement::new());
     def => { 0u32 }
     from => {|i| Ok(i)}
     into => { |slf: _| -> Result<_, IntoSafeArrElemError> { Ok(slf) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for Int {
     SFTYPE = VT_INT;
+    POD = Some(PodElement::new());
     def => { 0i32 }
     from => {|i| Ok(Int::from(i))}
     into => { |slf: Int| -> Result<_, IntoSafeArrElemError> {Ok(i32::from(slf)) }}
 }}
 safe_arr_impl!{impl SafeArrayElement for UInt {
     SFTYPE = VT_UINT;
+    POD = Some(PodElement::new());
     def => { 0u32 }
     from => {|i| Ok(UInt::from(i))}
     into => { |slf: UInt| -> Result<_, IntoSafeArrElemError> {Ok(u32::from(slf)) }}
 }}
 
-#[allow(dead_code)]
-#[link(name="OleAut32")]
-extern "system" {
-     fn SafeArrayCreate(vt: VARTYPE, cDims: UINT, rgsabound: LPSAFEARRAYBOUND) -> LPSAFEARRAY;
-	 fn SafeArrayDestroy(safe: LPSAFEARRAY)->HRESULT;
-    
-     fn SafeArrayGetDim(psa: LPSAFEARRAY) -> UINT;
-	
-     fn SafeArrayGetElement(psa: LPSAFEARRAY, rgIndices: *const c_long, pv: *mut c_void) -> HRESULT;
-     fn SafeArrayGetElemSize(psa: LPSAFEARRAY) -> UINT;
-    
-     fn SafeArrayGetLBound(psa: LPSAFEARRAY, nDim: UINT, plLbound: *mut c_long)->HRESULT;
-     fn SafeArrayGetUBound(psa: LPSAFEARRAY, nDim: UINT, plUbound: *mut c_long)->HRESULT;
-    
-     fn SafeArrayGetVartype(psa: LPSAFEARRAY, pvt: *mut VARTYPE) -> HRESULT;
-
-     fn SafeArrayLock(psa: LPSAFEARRAY) -> HRESULT;
-	 fn SafeArrayUnlock(psa: LPSAFEARRAY) -> HRESULT;
-    
-     fn SafeArrayPutElement(psa: LPSAFEARRAY, rgIndices: *const c_long, pv: *mut c_void) -> HRESULT;
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
+    use super::super::rust_alloc::test::Counted;
+    use super::super::sys::{VARIANT_TRUE, VT_ARRAY};
+    use super::super::variant::OwnedVariant;
     macro_rules! validate_safe_arr {
         ($t:ident, $vals:expr, $vt:expr) => {
             let v: Vec<$t> = $vals;
 
             let p = v.into_iter().into_safearray().unwrap();
             
-            let r = ExactSizeIterator::<Item=$t>::from_safearray(p.as_ptr());
+            let r = <dyn ExactSizeIterator<Item=$t>>::from_safearray(p.as_ptr());
             let r = r.unwrap();
             assert_eq!(r, $vals);
         };
@@ -520,12 +834,48 @@ mod test {
 
         let p = v.into_iter().into_safearray().unwrap();
 
-        let r = ExactSizeIterator::<Item=String>::from_safearray(p.as_ptr());
+        let r = <dyn ExactSizeIterator<Item=String>>::from_safearray(p.as_ptr());
 
         let r = r.unwrap();
         assert_eq!(r, vec![String::from("validate"), String::from("test string")]);
     }
 
+    #[test]
+    fn test_str_layouts() {
+        let tracker = tracking::AllocTracker::start();
+        {
+            let p = vec![String::from("a"), String::from("bc")].into_iter().into_safearray().unwrap();
+            let mut vt: VARTYPE = 0;
+            unsafe { allocator().safe_array_get_vartype(p.as_ptr(), &mut vt) };
+            assert_eq!(u32::from(vt), VT_BSTR);
+            assert_eq!(String::from_safearray(p.as_ptr(), 1).unwrap(), "bc");
+            assert_eq!(<dyn ExactSizeIterator<Item=String>>::from_safearray(p.as_ptr()).unwrap(), vec!["a", "bc"]);
+
+            let mut sab = SAFEARRAYBOUND { cElements: 2, lLbound: 0 };
+            let psa = unsafe { allocator().safe_array_create(VT_VARIANT as u16, 1, &mut sab) };
+            String::from("old").into_safearray(psa, 0).unwrap();
+            let mut var = 5i32.into_raw_variant().unwrap();
+            unsafe { allocator().safe_array_put_element(psa, &1, &mut var as *mut VARIANT as *mut c_void) };
+            assert_eq!(String::from_safearray(psa, 0).unwrap(), "old");
+            match String::from_safearray(psa, 1) {
+                Err(FromSafeArrElemError::FromVariantFailed) => {},
+                r => panic!("unexpected result: {:?}", r)
+            }
+            unsafe { allocator().safe_array_destroy(psa) };
+        }
+        tracker.assert_no_leaks();
+
+        let mut sab = SAFEARRAYBOUND { cElements: 1, lLbound: 0 };
+        let psa = unsafe { allocator().safe_array_create(VT_BSTR as u16, 1, &mut sab) };
+        let bad = BString::from_wide(&[0x61, 0xD800]).unwrap();
+        unsafe { allocator().safe_array_put_element(psa, &0, bad.as_ptr() as *mut c_void) };
+        match String::from_safearray(psa, 0) {
+            Err(FromSafeArrElemError::InvalidUtf16{index: 1}) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+        unsafe { allocator().safe_array_destroy(psa) };
+    }
+
     #[test]
     fn test_scode() {
         validate_safe_arr!(SCode, vec![SCode::from(100), SCode::from(10000)], VT_ERROR );
@@ -535,17 +885,98 @@ mod test {
         validate_safe_arr!(bool, vec![true, false, true, true, false, false, true], VT_BOOL );
     }
 
+    #[test]
+    fn test_variant_bool() {
+        let v: Vec<VariantBool> = vec![true, false, true].into_iter().map(VariantBool::from).collect();
+        validate_safe_arr!(VariantBool, v.clone(), VT_BOOL );
+        assert!(VariantBool::POD.is_some());
+
+        let p = v.into_iter().into_safearray().unwrap();
+        let mut vb: VARIANT_BOOL = 0;
+        unsafe { allocator().safe_array_get_element(p.as_ptr(), &0, &mut vb as *mut VARIANT_BOOL as *mut c_void) };
+        assert_eq!(vb, VARIANT_TRUE);
+        assert_eq!(<dyn ExactSizeIterator<Item=bool>>::from_safearray(p.as_ptr()).unwrap(), vec![true, false, true]);
+
+        let mut sab = SAFEARRAYBOUND { cElements: 3, lLbound: 0 };
+        let psa = unsafe { allocator().safe_array_create(VT_BOOL as u16, 1, &mut sab) };
+        for (ix, vb) in [1i16, -2, 0].iter().enumerate() {
+            unsafe { allocator().safe_array_put_element(psa, &(ix as i32), vb as *const VARIANT_BOOL as *mut c_void) };
+        }
+        let r = <dyn ExactSizeIterator<Item=VariantBool>>::from_safearray(psa).unwrap();
+        assert_eq!(r, vec![VariantBool::from(false), VariantBool::from(true), VariantBool::from(false)]);
+        assert_eq!(VARIANT_BOOL::from(r[1]), VARIANT_TRUE);
+    }
+
     #[test]
     fn test_variant() {
         let v: Vec<Variant<u64>> = vec![Variant::new(100u64), Variant::new(100u64), Variant::new(103u64)];
 
         let p = v.into_iter().into_safearray().unwrap();
         
-        let r = ExactSizeIterator::<Item=Variant<u64>>::from_safearray(p.as_ptr());
+        let r = <dyn ExactSizeIterator<Item=Variant<u64>>>::from_safearray(p.as_ptr());
         let r = r.unwrap();
         assert_eq!(r,  vec![Variant::new(100u64), Variant::new(100u64), Variant::new(103u64)]);
     }
 
+    #[test]
+    fn test_variant_value() {
+        let tracker = tracking::AllocTracker::start();
+        {
+            let v = vec![
+                VariantValue::I4(42),
+                VariantValue::from("cell"),
+                VariantValue::R8(2.5),
+                VariantValue::Bool(true),
+                VariantValue::Empty,
+                VariantValue::Null,
+                VariantValue::Unknown(None),
+                VariantValue::from(Decimal::new(-1337, 2)),
+            ];
+            let p = v.clone().into_iter().into_safearray().unwrap();
+            assert_eq!(VariantValue::from_safearray(p.as_ptr(), 1).unwrap(), VariantValue::from("cell"));
+            assert_eq!(<dyn ExactSizeIterator<Item=VariantValue>>::from_safearray(p.as_ptr()).unwrap(), v);
+
+            // The array copies `inner` and destroys it, and reading the element copies it again
+            let inner = vec![1u8, 2].into_iter().into_safearray().unwrap();
+            let p = vec![VariantValue::Array{vartype: VarType::from(VT_UI1), array: inner}].into_iter().into_safearray().unwrap();
+            let copy = match VariantValue::from_safearray(p.as_ptr(), 0).unwrap() {
+                VariantValue::Array{vartype, array} if vartype == VT_UI1 => array,
+                r => panic!("unexpected result: {:?}", r)
+            };
+            assert_eq!(<dyn ExactSizeIterator<Item=u8>>::from_safearray(copy.as_ptr()).unwrap(), vec![1, 2]);
+            unsafe { allocator().safe_array_destroy(p.as_ptr()) };
+        }
+        tracker.assert_no_leaks();
+
+        let mut obj = Counted::new();
+        let punk = Ptr::with_checked(&mut obj.iface as *mut IUnknown).unwrap();
+        let refs = || unsafe { (*punk.as_ptr().cast::<Counted>()).refs };
+        unsafe { punk.as_ref().AddRef() };
+        let p = vec![VariantValue::Unknown(Some(punk))].into_iter().into_safearray().unwrap();
+        assert_eq!(refs(), 2);
+        let val = VariantValue::from_safearray(p.as_ptr(), 0).unwrap();
+        assert_eq!(val, VariantValue::Unknown(Some(punk)));
+        assert_eq!(refs(), 3);
+        drop(OwnedVariant::from_value(val).unwrap());
+        assert_eq!(refs(), 2);
+        unsafe { allocator().safe_array_destroy(p.as_ptr()) };
+        assert_eq!(refs(), 1);
+    }
+
+    #[test]
+    fn test_variant_value_vec() {
+        let tracker = tracking::AllocTracker::start();
+        {
+            let v = vec![VariantValue::I4(1), VariantValue::from("two"), VariantValue::Null];
+            let var = OwnedVariant::new(v.clone()).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_VARIANT);
+            let pvar = var.into_raw();
+            assert_eq!(Vec::<VariantValue>::from_variant(pvar).unwrap(), v);
+            drop(unsafe { OwnedVariant::from_raw(pvar) });
+        }
+        tracker.assert_no_leaks();
+    }
+
     #[test]
     fn test_decimal() {
         validate_safe_arr!(Decimal, vec![Decimal::new(2, 2), Decimal::new(3, 3)], VE_DECIMAL );
@@ -567,6 +998,28 @@ mod test {
         validate_safe_arr!(u32, vec![0,1,2,3,4], VT_UI4 );
     }
 
+    #[test]
+    fn test_pod_bulk() {
+        assert!(i32::POD.is_some() && Currency::POD.is_some());
+        assert!(VariantBool::POD.is_some());
+        assert!(bool::POD.is_none() && String::POD.is_none());
+
+        let v: Vec<u16> = (0..10_000u16).collect();
+        let p = v.clone().into_iter().into_safearray_with_lbound(-5).unwrap();
+        assert_eq!(u16::from_safearray(p.as_ptr(), 9_994).unwrap(), 9_999);
+        let (lbound, r) = read_vector::<u16>(p.as_ptr()).unwrap();
+        assert_eq!((lbound, &r), (-5, &v));
+        assert_eq!(unsafe { (*p.as_ptr()).cLocks }, 0);
+        match read_vector::<i16>(p.as_ptr()) {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+        assert_eq!(<dyn ExactSizeIterator<Item=u16>>::from_safearray(p.as_ptr()).unwrap(), v);
+
+        let p = Vec::<Int>::new().into_iter().into_safearray().unwrap();
+        assert!(<dyn ExactSizeIterator<Item=Int>>::from_safearray(p.as_ptr()).unwrap().is_empty());
+    }
+
     #[test]
     fn test_send() {
         fn assert_send<T: Send>() {}
@@ -0,0 +1,269 @@
+use std::ops::{Index, IndexMut};
+use std::slice;
+
+use super::array::{read_vector, SafeArrayDestructor, SafeArrayElement, SafeArrayExt};
+use super::errors::{FromSafeArrayError, FromVariantError, IntoSafeArrayError, IntoVariantError};
+use super::ptr::Ptr;
+use super::sys::{SAFEARRAY, VARIANT, VT_ARRAY};
+use super::tracking;
+use super::variant::{array_accepts, heap_variant, safearray_variant, take_array, VariantExt};
+
+/// One dimensional SAFEARRAY contents together with the lower bound of the array.
+///
+/// Converting a `Vec<T>` always creates an array starting at 0 and drops the lower bound
+/// of arrays it reads. A `BoundedVec` keeps it both ways, so an array created with a
+/// lower bound of 1 works with VBA code like `For i = 1 To UBound(arr)`. Indexing and
+/// [`get`] take SAFEARRAY indices, from `lbound` to [`ubound`].
+///
+/// [`get`]: #method.get
+/// [`ubound`]: #method.ubound
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::BoundedVec;
+///
+/// fn main() {
+///     let arr = BoundedVec::new(1, vec![10, 20, 30]);
+///     assert_eq!(arr[1], 10);
+///     assert_eq!(arr.ubound(), Some(3));
+///     assert_eq!(arr.get(0), None);
+///
+///     let psa = arr.clone().into_safearray().unwrap();
+///     assert_eq!(BoundedVec::<i32>::from_safearray(psa.as_ptr()).unwrap(), arr);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BoundedVec<T> {
+    /// Lower bound of the array, the SAFEARRAY index of the first item
+    pub lbound: i32,
+    /// The elements, in index order
+    pub items: Vec<T>,
+}
+
+impl<T> BoundedVec<T> {
+    /// Creates an array whose first item has the SAFEARRAY index `lbound`
+    #[allow(clippy::redundant_field_names)]
+    pub fn new(lbound: i32, items: Vec<T>) -> BoundedVec<T> {
+        BoundedVec { lbound: lbound, items: items }
+    }
+
+    /// Upper bound of the array, as returned by `SafeArrayGetUBound`. It is `lbound - 1`
+    /// when the array is empty. None when it does not fit an `i32`, in which case the
+    /// array cannot be converted into a SAFEARRAY either.
+    #[allow(clippy::legacy_numeric_constants)]
+    pub fn ubound(&self) -> Option<i32> {
+        let ubound = self.last_index();
+        if ubound < i64::from(i32::min_value()) || ubound > i64::from(i32::max_value()) {
+            None
+        } else {
+            Some(ubound as i32)
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The element at the SAFEARRAY index `ix`, None if out of bounds
+    pub fn get(&self, ix: i32) -> Option<&T> {
+        self.offset(ix).map(|ix| &self.items[ix])
+    }
+
+    /// The element at the SAFEARRAY index `ix`, None if out of bounds
+    pub fn get_mut(&mut self, ix: i32) -> Option<&mut T> {
+        match self.offset(ix) {
+            Some(ix) => Some(&mut self.items[ix]),
+            None => None
+        }
+    }
+
+    /// Iterates over the elements in index order
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Index of the last element, which may lie outside the `i32` range
+    fn last_index(&self) -> i64 {
+        i64::from(self.lbound) + self.items.len() as i64 - 1
+    }
+
+    /// Position of the element at `ix` in `items`
+    fn offset(&self, ix: i32) -> Option<usize> {
+        let pos = i64::from(ix) - i64::from(self.lbound);
+        if pos < 0 || pos >= self.items.len() as i64 {
+            None
+        } else {
+            Some(pos as usize)
+        }
+    }
+}
+
+impl<T: SafeArrayElement> BoundedVec<T> {
+    /// Creates a one dimensional SAFEARRAY starting at `lbound` and puts every element
+    /// into it. Fails with `IntoSafeArrayError::BoundsOverflow` if the upper bound does
+    /// not fit an `i32`.
+    pub fn into_safearray(self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        let lbound = self.lbound;
+        self.items.into_iter().into_safearray_with_lbound(lbound)
+    }
+
+    /// Reads every element of a one dimensional SAFEARRAY along with its lower bound, then
+    /// destroys it like [`SafeArrayExt::from_safearray`].
+    ///
+    /// [`SafeArrayExt::from_safearray`]: trait.SafeArrayExt.html#tymethod.from_safearray
+    #[allow(clippy::redundant_field_names)]
+    pub fn from_safearray(psa: *mut SAFEARRAY) -> Result<BoundedVec<T>, FromSafeArrayError> {
+        let _tag = tracking::tag::<Self>("from_safearray");
+        let _sad = SafeArrayDestructor::new(psa);
+        let (lbound, items) = read_vector(psa)?;
+        Ok(BoundedVec { lbound: lbound, items: items })
+    }
+}
+
+impl<T> From<Vec<T>> for BoundedVec<T> {
+    /// Starts the array at 0
+    fn from(items: Vec<T>) -> BoundedVec<T> {
+        BoundedVec::new(0, items)
+    }
+}
+
+impl<T> From<BoundedVec<T>> for Vec<T> {
+    fn from(bv: BoundedVec<T>) -> Vec<T> {
+        bv.items
+    }
+}
+
+impl<T> Index<i32> for BoundedVec<T> {
+    type Output = T;
+    /// ## Panics
+    /// If `ix` is outside `lbound..=ubound`.
+    fn index(&self, ix: i32) -> &T {
+        match self.offset(ix) {
+            Some(pos) => &self.items[pos],
+            None => panic!("index {} out of bounds {}..={}", ix, self.lbound, self.last_index())
+        }
+    }
+}
+
+impl<T> IndexMut<i32> for BoundedVec<T> {
+    fn index_mut(&mut self, ix: i32) -> &mut T {
+        match self.offset(ix) {
+            Some(pos) => &mut self.items[pos],
+            None => panic!("index {} out of bounds {}..={}", ix, self.lbound, self.last_index())
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BoundedVec<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.items.iter()
+    }
+}
+
+impl<T> IntoIterator for BoundedVec<T> {
+    type Item = T;
+    type IntoIter = ::std::vec::IntoIter<T>;
+    fn into_iter(self) -> ::std::vec::IntoIter<T> {
+        self.items.into_iter()
+    }
+}
+
+impl<T: SafeArrayElement> VariantExt for BoundedVec<T> {
+    const VARTYPE: u32 = VT_ARRAY | T::SFTYPE;
+    fn accepts_vartype(vt: u32) -> bool {
+        array_accepts::<T>(vt)
+    }
+
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let _tag = tracking::tag::<Self>("from_variant");
+        let psa = take_array::<T>(var)?;
+        Ok(BoundedVec::from_safearray(psa)?)
+    }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        Ok(heap_variant(self.into_raw_variant()?))
+    }
+
+    fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        let psa = self.into_safearray()?;
+        Ok(safearray_variant(psa, Self::VARTYPE))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::alloc::allocator;
+    use super::super::sys::VT_BSTR;
+    use super::super::tracking::AllocTracker;
+    use super::super::variant::OwnedVariant;
+
+    #[test]
+    fn test_bounds() {
+        let arr = BoundedVec::new(1, vec![1.5f64, 2.5, 3.5]);
+        let psa = arr.clone().into_safearray().unwrap().as_ptr();
+        let (mut lbound, mut ubound) = (0, 0);
+        unsafe {
+            assert_eq!(allocator().safe_array_get_lbound(psa, 1, &mut lbound), 0);
+            assert_eq!(allocator().safe_array_get_ubound(psa, 1, &mut ubound), 0);
+        }
+        assert_eq!((lbound, ubound), (1, 3));
+        assert_eq!(f64::from_safearray(psa, 3).unwrap(), 3.5);
+        assert!(f64::from_safearray(psa, 0).is_err());
+        assert_eq!(BoundedVec::<f64>::from_safearray(psa).unwrap(), arr);
+
+        let neg = BoundedVec::new(-2, vec![String::from("a"), String::from("b")]);
+        assert_eq!(neg[-1], "b");
+        assert_eq!(neg.ubound(), Some(-1));
+        let var = neg.clone().into_variant().unwrap();
+        assert_eq!(BoundedVec::<String>::from_variant(var).unwrap(), neg);
+        drop(unsafe { OwnedVariant::from_raw(var) });
+
+        let tracker = AllocTracker::start();
+        {
+            let var = OwnedVariant::new(neg).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_BSTR);
+        }
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    fn test_empty() {
+        let empty = BoundedVec::<i32>::new(1, vec![]);
+        assert_eq!(empty.ubound(), Some(0));
+        let psa = empty.clone().into_safearray().unwrap();
+        assert_eq!(BoundedVec::<i32>::from_safearray(psa.as_ptr()).unwrap(), empty);
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_bounds_overflow() {
+        let last = BoundedVec::new(i32::max_value(), vec![String::from("a")]);
+        assert_eq!(last.ubound(), Some(i32::max_value()));
+        assert_eq!(last[i32::max_value()], "a");
+        let psa = last.clone().into_safearray().unwrap();
+        assert_eq!(BoundedVec::<String>::from_safearray(psa.as_ptr()).unwrap(), last);
+
+        let over = BoundedVec::new(i32::max_value(), vec![1, 2]);
+        assert_eq!(over.ubound(), None);
+        assert_eq!(over.get(i32::max_value()), Some(&1));
+        match over.into_safearray() {
+            Err(IntoSafeArrayError::BoundsOverflow{lbound, len}) => assert_eq!((lbound, len), (i32::max_value(), 2)),
+            r => panic!("unexpected result: {:?}", r)
+        }
+        assert_eq!(BoundedVec::<i32>::new(i32::min_value(), vec![]).ubound(), None);
+    }
+}
@@ -1,10 +1,20 @@
-use std::ptr::null_mut;
+use std::borrow::Borrow;
+use std::char;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::Deref;
+use std::ptr::{self, null_mut};
+use std::slice;
 
-use winapi::um::oleauto::{SysAllocStringLen, SysFreeString, SysStringLen};
 use widestring::U16String;
 
-use super::errors::BStringError;
+use super::alloc::allocator;
+use super::errors::{BStringError, FromVariantError};
 use super::ptr::Ptr;
+#[cfg(unix)]
+use super::wtf8;
 
 // pub type wchar_t = u16;
 // pub type WCHAR = wchar_t;
@@ -12,6 +22,7 @@ use super::ptr::Ptr;
 // pub type BSTR = *mut OLECHAR;
 
 //This is how C/Rust look at it, but the memory returned by SysX methods is a bit different
+#[allow(clippy::upper_case_acronyms)]
 type BSTR = *mut u16; 
 
 /// This trait is implemented on `String` to enable the convenient and safe conversion of
@@ -26,15 +37,16 @@ type BSTR = *mut u16;
 /// allocation method will cause UB and crashes. 
 /// 
 /// [`allocate_managed_bstr`]: #tymethod.allocate_managed_bstr
-/// [`DroppableBString`]: struct.DroppableBString.html
+/// [`BString`]: struct.BString.html
 pub trait BStringExt {
     /// Allocates a [`Ptr<u16>`] (aka a `*mut u16` aka a BSTR)
     fn allocate_bstr(&mut self) -> Result<Ptr<u16>, BStringError>;
-    /// Allocates a [`DroppableBString`] container - automatically frees the memory properly if dropped.
-    fn allocate_managed_bstr(&mut self) -> Result<DroppableBString, BStringError>;
+    /// Allocates a [`BString`] container - automatically frees the memory properly if dropped.
+    fn allocate_managed_bstr(&mut self) -> Result<BString, BStringError>;
     /// Manually and correct free the memory allocated via Sys* methods
     fn deallocate_bstr(bstr: Ptr<u16>);
-    /// Convenience method for conversion to a good intermediary type
+    /// Convenience method for conversion to a good intermediary type. A null BSTR is
+    /// converted to the empty string.
     fn from_bstr(bstr: *mut u16) -> U16String;
     /// Convenience method for conversion to a good intermediary type
     fn from_pbstr(bstr: Ptr<u16>) -> U16String;
@@ -44,29 +56,21 @@ pub trait BStringExt {
 
 impl BStringExt for U16String {
     fn allocate_bstr(&mut self) -> Result<Ptr<u16>, BStringError> {
-        let sz = self.len();
-        let cln = self.clone();
-        let rw = cln.as_ptr();
-        let bstr: BSTR = unsafe {SysAllocStringLen(rw, sz as u32)};
-        match Ptr::with_checked(bstr) {
-            Some(pbstr) => Ok(pbstr), 
-            None => Err(BStringError::AllocateFailed{len: sz})
-        }
+        let bstr = self.allocate_managed_bstr()?;
+        Ok(Ptr::with_checked(bstr.into_raw()).unwrap())
     }
 
-    fn allocate_managed_bstr(&mut self) -> Result<DroppableBString, BStringError> {
-        Ok(DroppableBString{ inner: Some(self.allocate_bstr()?) })
+    fn allocate_managed_bstr(&mut self) -> Result<BString, BStringError> {
+        BString::from_wide(self.as_slice())
     }
 
     fn deallocate_bstr(bstr: Ptr<u16>) {
-        let bstr: BSTR = bstr.as_ptr();
-        unsafe { SysFreeString(bstr) }
+        drop(unsafe { BString::from_raw(bstr.as_ptr()) })
     }
 
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn from_bstr(bstr: *mut u16) -> U16String {
-        assert!(!bstr.is_null());
-        let sz = unsafe {SysStringLen(bstr)};
-        unsafe {U16String::from_ptr(bstr, sz as usize)}
+        U16String::from_vec(unsafe { BStr::from_ptr(bstr) }.as_slice())
     }
 
     fn from_pbstr(bstr: Ptr<u16>) -> U16String {
@@ -78,37 +82,753 @@ impl BStringExt for U16String {
     }
 }
 
-/// Struct that holds pointer to Sys* allocated memory. 
-/// It will automatically free the memory via the Sys* 
-/// functions unless it has been consumed. 
-pub struct DroppableBString {
+/// Owned BSTR, which is to a BSTR what `String` is to a `str`.
+///
+/// The BSTR is allocated with the Sys* functions and freed with `SysFreeString` when
+/// dropped. It dereferences to its UTF-16 characters, without the length prefix and the
+/// terminating null.
+///
+/// A `BString` can be null, which COM treats as the empty string: it is the
+/// [`Default`], has a length of 0 and compares equal to `""`.
+///
+/// Use [`into_raw`] to hand the BSTR across FFI, and [`from_raw`] to take ownership of
+/// one again.
+///
+/// [`Default`]: #impl-Default
+/// [`into_raw`]: #method.into_raw
+/// [`from_raw`]: #method.from_raw
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::BString;
+///
+/// fn main() {
+///     let bstr = BString::from("Turing completeness.");
+///     assert_eq!(bstr.len(), 20);
+///     assert_eq!(&bstr, "Turing completeness.");
+///     assert_eq!(bstr.to_string(), "Turing completeness.");
+///     assert!(BString::default().is_null());
+/// }
+/// ```
+pub struct BString {
     inner: Option<Ptr<u16>>
 }
 
-impl DroppableBString {
-    /// `consume()` -> `*mut u16` returns the contained data
-    /// while also setting a flag that the data has been
-    /// consumed. It is your responsibility to manage the 
-    /// memory yourself. Most uses of BSTR in FFI will
-    /// free the memory for you. 
-    #[allow(dead_code)]
-    pub fn consume(&mut self) -> *mut u16 {
-        let ret = match self.inner {
+/// The length as passed to the Sys* allocators, which take a `u32`. Longer strings fail
+/// with `AllocateFailed` instead of being truncated.
+#[allow(clippy::legacy_numeric_constants, clippy::redundant_field_names)]
+fn alloc_len(len: usize) -> Result<u32, BStringError> {
+    if len > u32::max_value() as usize {
+        return Err(BStringError::AllocateFailed{len: len});
+    }
+    Ok(len as u32)
+}
+
+impl BString {
+    /// The null BSTR
+    pub fn new() -> BString {
+        BString { inner: None }
+    }
+
+    /// Allocates a BSTR holding a copy of `s`
+    pub fn from_wide(s: &[u16]) -> Result<BString, BStringError> {
+        let bstr: BSTR = unsafe {allocator().sys_alloc_string_len(s.as_ptr(), alloc_len(s.len())?)};
+        match Ptr::with_checked(bstr) {
+            Some(pbstr) => Ok(BString { inner: Some(pbstr) }), 
+            None => Err(BStringError::AllocateFailed{len: s.len()})
+        }
+    }
+
+    /// Allocates a BSTR holding `s` encoded as UTF-16. The BSTR is allocated once at its
+    /// final length and `s` is encoded straight into it.
+    #[allow(clippy::redundant_field_names, clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<BString, BStringError> {
+        let len = static_bstr_len(s);
+        let bstr: BSTR = unsafe {allocator().sys_alloc_string_len(ptr::null(), alloc_len(len)?)};
+        let pbstr = match Ptr::with_checked(bstr) {
+            Some(pbstr) => pbstr, 
+            None => return Err(BStringError::AllocateFailed{len: len})
+        };
+        // The characters may be uninitialized, so they are written without making a slice
+        for (ix, u) in s.encode_utf16().enumerate().take(len) {
+            unsafe { bstr.add(ix).write(u) };
+        }
+        Ok(BString { inner: Some(pbstr) })
+    }
+
+    /// Allocates a BSTR holding `s`, which round-trips through [`BStr::to_os_string`]
+    /// unchanged. On Windows this cannot fail except for the allocation. Elsewhere `s` is
+    /// expected to hold WTF-8, which includes all UTF-8.
+    ///
+    /// [`BStr::to_os_string`]: struct.BStr.html#method.to_os_string
+    pub fn from_os_str(s: &OsStr) -> Result<BString, BStringError> {
+        BString::from_wide(&os_str_to_wide(s)?)
+    }
+
+    /// Allocates a BSTR holding a copy of the bytes in `b`, like `SysAllocStringByteLen`.
+    /// The BSTR is not required to hold UTF-16: odd lengths and embedded nulls are kept
+    /// as they are.
+    pub fn from_bytes(b: &[u8]) -> Result<BString, BStringError> {
+        let bstr: BSTR = unsafe {allocator().sys_alloc_string_byte_len(b.as_ptr(), alloc_len(b.len())?)};
+        match Ptr::with_checked(bstr) {
+            Some(pbstr) => Ok(BString { inner: Some(pbstr) }), 
+            None => Err(BStringError::AllocateFailed{len: b.len()})
+        }
+    }
+
+    /// Takes ownership of a BSTR, which may be null.
+    ///
+    /// ## Safety
+    ///
+    /// `bstr` must have been allocated with the Sys* functions of the current allocator
+    /// and must not be owned by anything else.
+    pub unsafe fn from_raw(bstr: *mut u16) -> BString {
+        BString { inner: Ptr::with_checked(bstr) }
+    }
+
+    /// Releases ownership of the BSTR without freeing it, null for the null BSTR.
+    /// It is your responsibility to free it, eg by passing it to [`from_raw`]
+    /// or to an FFI function that takes ownership.
+    ///
+    /// [`from_raw`]: #method.from_raw
+    pub fn into_raw(self) -> *mut u16 {
+        let slf = mem::ManuallyDrop::new(self);
+        slf.as_ptr()
+    }
+
+    /// The BSTR, for passing as an `[in] BSTR` argument. Null for the null BSTR.
+    pub fn as_ptr(&self) -> *mut u16 {
+        match self.inner {
             Some(ptr) => ptr.as_ptr(), 
             None => null_mut()
-        };
-        self.inner = None;
-        ret
+        }
+    }
+
+    /// Length in UTF-16 code units, as returned by `SysStringLen`
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Whether the BSTR is null or has a length of 0
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Length in bytes, as returned by `SysStringByteLen`
+    pub fn byte_len(&self) -> usize {
+        self.as_bstr().byte_len()
+    }
+
+    /// The exact bytes of the BSTR, for BSTRs carrying binary data
+    pub fn as_bytes(&self) -> &[u8] {
+        self.as_bstr().as_bytes()
+    }
+
+    /// Whether this is the null BSTR
+    pub fn is_null(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    /// The UTF-16 characters
+    pub fn as_slice(&self) -> &[u16] {
+        self.as_bstr().as_slice()
+    }
+
+    /// Decodes the characters, replacing invalid UTF-16 with U+FFFD
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
+
+    /// Borrows the BSTR as a [`BStr`]
+    ///
+    /// [`BStr`]: struct.BStr.html
+    pub fn as_bstr(&self) -> &BStr {
+        unsafe { BStr::from_ptr(self.as_ptr()) }
+    }
+
+    /// Hands the BSTR over to the caller without freeing it, leaving the null BSTR behind.
+    /// The caller must free the returned pointer. Use [`into_raw`] instead.
+    ///
+    /// [`into_raw`]: #method.into_raw
+    #[deprecated(since = "0.1.5", note = "use `into_raw`")]
+    pub fn consume(&mut self) -> *mut u16 {
+        mem::take(self).into_raw()
+    }
+}
+
+/// Former name of [`BString`]
+///
+/// [`BString`]: struct.BString.html
+#[deprecated(since = "0.1.5", note = "renamed to `BString`")]
+pub type DroppableBString = BString;
+
+impl Deref for BString {
+    type Target = [u16];
+    fn deref(&self) -> &[u16] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u16]> for BString {
+    fn as_ref(&self) -> &[u16] {
+        self.as_slice()
+    }
+}
+
+impl Clone for BString {
+    /// Copies the bytes into a new BSTR. Null is cloned as null.
+    ///
+    /// ## Panics
+    /// If the allocation fails.
+    fn clone(&self) -> BString {
+        if self.is_null() {
+            return BString::new();
+        }
+        match BString::from_bytes(self.as_bytes()) {
+            Ok(bstr) => bstr, 
+            Err(bse) => panic!("{}", bse)
+        }
+    }
+}
+
+impl Default for BString {
+    fn default() -> BString {
+        BString::new()
     }
 }
 
-impl Drop for DroppableBString {
+impl Drop for BString {
+    #[allow(clippy::single_match)]
     fn drop(&mut self) {
         match self.inner {
             Some(ptr) => {
-                unsafe { SysFreeString(ptr.as_ptr())}
+                unsafe { allocator().sys_free_string(ptr.as_ptr())}
             }, 
             None => {}
         }
     }
-}
\ No newline at end of file
+}
+
+#[allow(clippy::needless_lifetimes)]
+impl<'s> From<&'s str> for BString {
+    /// ## Panics
+    /// If the allocation fails.
+    fn from(s: &str) -> BString {
+        match BString::from_str(s) {
+            Ok(bstr) => bstr, 
+            Err(bse) => panic!("{}", bse)
+        }
+    }
+}
+
+impl fmt::Display for BString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in char::decode_utf16(self.as_slice().iter().cloned()) {
+            fmt::Write::write_char(f, c.unwrap_or(char::REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl PartialEq for BString {
+    fn eq(&self, other: &BString) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for BString {}
+
+impl Hash for BString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
+}
+
+impl PartialEq<str> for BString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_slice().iter().cloned().eq(other.encode_utf16())
+    }
+}
+
+#[allow(clippy::needless_lifetimes)]
+impl<'s> PartialEq<&'s str> for BString {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<BString> for str {
+    fn eq(&self, other: &BString) -> bool {
+        *other == *self
+    }
+}
+
+/// Length prefix and terminating null of an empty BSTR, which null BSTRs are viewed as
+static EMPTY_BSTR: [u32; 2] = [0, 0];
+
+/// Reads the length prefix of a non-null BSTR
+unsafe fn byte_len(bstr: *const u16) -> u32 {
+    ptr::read_unaligned((bstr as *const u32).offset(-1))
+}
+
+/// Borrowed BSTR, which is to a [`BString`] what `CStr` is to a `CString`.
+///
+/// Use [`from_ptr`] to view an `[in] BSTR` argument without copying it. The length is
+/// read from the prefix in front of the characters, so nothing calls into OleAut32, and
+/// a null BSTR is the empty string, as OLE Automation specifies.
+///
+/// [`BString`]: struct.BString.html
+/// [`from_ptr`]: #method.from_ptr
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{BStr, BString};
+///
+/// fn main() {
+///     let bstr = BString::from("Turing completeness.");
+///     let view = unsafe { BStr::from_ptr(bstr.as_ptr()) };
+///     assert_eq!(view.len(), 20);
+///     assert_eq!(view.to_string(), "Turing completeness.");
+///     assert_eq!(view.to_string_strict().unwrap(), "Turing completeness.");
+///     assert!(unsafe { BStr::from_ptr(std::ptr::null()) }.is_empty());
+/// }
+/// ```
+#[repr(transparent)]
+pub struct BStr {
+    inner: [u16]
+}
+
+impl BStr {
+    /// Wraps a BSTR, reading its length from the 4 byte prefix. Null is the empty string.
+    ///
+    /// ## Safety
+    ///
+    /// `bstr` must be null or a valid BSTR which outlives `'a` and is not modified
+    /// while borrowed.
+    pub unsafe fn from_ptr<'a>(bstr: *const u16) -> &'a BStr {
+        // A null BSTR is viewed as an empty one, so there is always a length prefix
+        let bstr = if bstr.is_null() { EMPTY_BSTR[1..].as_ptr() as *const u16 } else { bstr };
+        let chars = slice::from_raw_parts(bstr, byte_len(bstr) as usize / 2);
+        &*(chars as *const [u16] as *const BStr)
+    }
+
+    /// The BSTR, for passing as an `[in] BSTR` argument. The callee must not modify or
+    /// free it.
+    pub fn as_ptr(&self) -> *mut u16 {
+        self.inner.as_ptr() as *mut u16
+    }
+
+    /// The UTF-16 characters, without the length prefix and the terminating null
+    pub fn as_slice(&self) -> &[u16] {
+        &self.inner
+    }
+
+    /// Length in UTF-16 code units, as returned by `SysStringLen`
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether the BSTR is null or has a length of 0
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Length in bytes, as returned by `SysStringByteLen`. Unlike [`len`] it counts the
+    /// last byte of a BSTR with an odd byte length.
+    ///
+    /// [`len`]: #method.len
+    pub fn byte_len(&self) -> usize {
+        unsafe { byte_len(self.inner.as_ptr()) as usize }
+    }
+
+    /// The exact bytes of the BSTR, for BSTRs carrying binary data
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.inner.as_ptr() as *const u8, self.byte_len()) }
+    }
+
+    /// Decodes the characters, replacing invalid UTF-16 with U+FFFD
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.inner)
+    }
+
+    /// Decodes the characters, failing with the index of the first unpaired surrogate
+    /// if they are not valid UTF-16
+    #[allow(clippy::redundant_field_names)]
+    pub fn to_string_strict(&self) -> Result<String, FromVariantError> {
+        let mut s = String::with_capacity(utf8_len(&self.inner));
+        let mut index = 0;
+        for c in char::decode_utf16(self.inner.iter().cloned()) {
+            match c {
+                Ok(c) => {
+                    s.push(c);
+                    index += c.len_utf16();
+                },
+                Err(_) => return Err(FromVariantError::InvalidUtf16{index: index}),
+            }
+        }
+        Ok(s)
+    }
+
+    /// Converts the characters into an `OsString` without losing anything: on Windows the
+    /// `OsString` holds the UTF-16 as it is, elsewhere it holds WTF-8.
+    pub fn to_os_string(&self) -> OsString {
+        os_string_from_wide(&self.inner)
+    }
+}
+
+/// Length in bytes of valid UTF-16 once encoded as UTF-8. A surrogate pair takes 4 bytes,
+/// 2 for each half.
+fn utf8_len(wide: &[u16]) -> usize {
+    wide.iter().map(|&u| match u {
+        0x0000..=0x007F => 1,
+        0x0080..=0x07FF | 0xD800..=0xDFFF => 2,
+        _ => 3,
+    }).sum()
+}
+
+#[cfg(windows)]
+fn os_string_from_wide(wide: &[u16]) -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+    OsString::from_wide(wide)
+}
+
+#[cfg(unix)]
+fn os_string_from_wide(wide: &[u16]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(wtf8::from_wide(wide))
+}
+
+// No lossless representation is known elsewhere
+#[cfg(not(any(windows, unix)))]
+fn os_string_from_wide(wide: &[u16]) -> OsString {
+    OsString::from(String::from_utf16_lossy(wide))
+}
+
+#[cfg(windows)]
+fn os_str_to_wide(s: &OsStr) -> Result<Vec<u16>, BStringError> {
+    use std::os::windows::ffi::OsStrExt;
+    Ok(s.encode_wide().collect())
+}
+
+#[cfg(unix)]
+#[allow(clippy::redundant_field_names)]
+fn os_str_to_wide(s: &OsStr) -> Result<Vec<u16>, BStringError> {
+    use std::os::unix::ffi::OsStrExt;
+    wtf8::to_wide(s.as_bytes()).map_err(|index| BStringError::InvalidWtf8{index: index})
+}
+
+#[cfg(not(any(windows, unix)))]
+fn os_str_to_wide(s: &OsStr) -> Result<Vec<u16>, BStringError> {
+    Ok(s.to_string_lossy().encode_utf16().collect())
+}
+
+/// Creates a `&'static BStr` from a string literal, without allocating.
+///
+/// The UTF-16 characters, their length prefix and the terminating null are built at
+/// compile time in static memory, laid out like a BSTR from `SysAllocString`. The BSTR can
+/// be passed as an `[in] BSTR` argument with [`BStr::as_ptr`], but must never be freed,
+/// so it cannot become a [`BString`].
+///
+/// [`BStr::as_ptr`]: struct.BStr.html#method.as_ptr
+/// [`BString`]: struct.BString.html
+///
+/// ## Example usage
+///
+/// ```
+/// #[macro_use]
+/// extern crate oaidl;
+///
+/// use oaidl::BStr;
+///
+/// fn main() {
+///     let name: &'static BStr = bstr!("Name");
+///     assert_eq!(name.len(), 4);
+///     assert_eq!(name, "Name");
+///     assert_eq!(unsafe { BStr::from_ptr(name.as_ptr()) }, name);
+/// }
+/// ```
+#[macro_export]
+macro_rules! bstr {
+    ($s:expr) => {{
+        const S: &'static str = $s;
+        static BSTR: $crate::StaticBStr<{ $crate::static_bstr_len(S) + 1 }> = $crate::StaticBStr::new(S);
+        BSTR.as_bstr()
+    }};
+}
+
+/// Number of UTF-16 code units in `s`, used by [`bstr!`](macro.bstr.html) and to size
+/// the BSTR allocated by `BString::from_str`
+#[doc(hidden)]
+pub const fn static_bstr_len(s: &str) -> usize {
+    let b = s.as_bytes();
+    let mut ix = 0;
+    let mut len = 0;
+    while ix < b.len() {
+        let (n, units) = match b[ix] {
+            0x00..=0x7F => (1, 1),
+            0xC0..=0xDF => (2, 1),
+            0xE0..=0xEF => (3, 1),
+            _ => (4, 2),
+        };
+        ix += n;
+        len += units;
+    }
+    len
+}
+
+/// Static storage of a [`bstr!`](macro.bstr.html) literal: the length prefix followed by
+/// `N - 1` characters and the terminating null
+#[doc(hidden)]
+#[repr(C)]
+pub struct StaticBStr<const N: usize> {
+    len: u32,
+    chars: [u16; N]
+}
+
+#[allow(clippy::redundant_field_names)]
+impl<const N: usize> StaticBStr<N> {
+    /// Encodes `s`, which must have `N - 1` UTF-16 code units
+    pub const fn new(s: &str) -> StaticBStr<N> {
+        let b = s.as_bytes();
+        let mut chars = [0u16; N];
+        let mut ix = 0;
+        let mut jx = 0;
+        while ix < b.len() {
+            let (mut cp, n) = match b[ix] {
+                0x00..=0x7F => (b[ix] as u32, 1),
+                0xC0..=0xDF => ((b[ix] & 0x1F) as u32, 2),
+                0xE0..=0xEF => ((b[ix] & 0x0F) as u32, 3),
+                _ => ((b[ix] & 0x07) as u32, 4),
+            };
+            let mut kx = 1;
+            while kx < n {
+                cp = (cp << 6) | (b[ix + kx] & 0x3F) as u32;
+                kx += 1;
+            }
+            if cp >= 0x10000 {
+                chars[jx] = 0xD800 | ((cp - 0x10000) >> 10) as u16;
+                chars[jx + 1] = 0xDC00 | ((cp - 0x10000) & 0x3FF) as u16;
+                jx += 2;
+            } else {
+                chars[jx] = cp as u16;
+                jx += 1;
+            }
+            ix += n;
+        }
+        assert!(jx + 1 == N, "length of the static BSTR does not match its string");
+        StaticBStr { len: (jx * 2) as u32, chars: chars }
+    }
+
+    /// The characters as a `BStr`, which the length prefix is in front of
+    pub fn as_bstr(&'static self) -> &'static BStr {
+        // The pointer is derived from the whole struct so that the prefix can be read
+        unsafe {
+            let chars = (self as *const StaticBStr<N> as *const u16).offset(2);
+            BStr::from_ptr(chars)
+        }
+    }
+}
+
+impl AsRef<[u16]> for BStr {
+    fn as_ref(&self) -> &[u16] {
+        &self.inner
+    }
+}
+
+impl fmt::Display for BStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in char::decode_utf16(self.inner.iter().cloned()) {
+            fmt::Write::write_char(f, c.unwrap_or(char::REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl PartialEq for BStr {
+    fn eq(&self, other: &BStr) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for BStr {}
+
+impl Hash for BStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state)
+    }
+}
+
+impl PartialEq<str> for BStr {
+    fn eq(&self, other: &str) -> bool {
+        self.inner.iter().cloned().eq(other.encode_utf16())
+    }
+}
+
+impl ToOwned for BStr {
+    type Owned = BString;
+    /// Copies the bytes into a new BSTR.
+    ///
+    /// ## Panics
+    /// If the allocation fails.
+    fn to_owned(&self) -> BString {
+        match BString::from_bytes(self.as_bytes()) {
+            Ok(bstr) => bstr, 
+            Err(bse) => panic!("{}", bse)
+        }
+    }
+}
+
+impl Borrow<BStr> for BString {
+    fn borrow(&self) -> &BStr {
+        self.as_bstr()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        for s in &["", "Turing completeness.", "\u{e9}\u{20ac}\u{1F600}\0x"] {
+            let bstr = BString::from_str(s).unwrap();
+            let wide: Vec<u16> = s.encode_utf16().collect();
+            assert_eq!(bstr.as_slice(), &wide[..]);
+            assert_eq!(bstr.byte_len(), wide.len() * 2);
+            assert_eq!(unsafe { *bstr.as_ptr().add(wide.len()) }, 0);
+            assert_eq!(utf8_len(&wide), s.len());
+            assert_eq!(bstr.as_bstr().to_string_strict().unwrap(), *s);
+        }
+    }
+
+    #[test]
+    fn test_static() {
+        let name: &'static BStr = bstr!("Name");
+        assert_eq!(name, "Name");
+        assert_eq!(name.byte_len(), 8);
+        assert_eq!(unsafe { *name.as_ptr().offset(4) }, 0);
+
+        let wide = bstr!("\u{e9}\u{20ac}\u{1F600}");
+        assert_eq!(wide.as_slice(), &[0xE9, 0x20AC, 0xD83D, 0xDE00]);
+        assert_eq!(wide.to_string_strict().unwrap(), "\u{e9}\u{20ac}\u{1F600}");
+        assert!(bstr!("").is_empty());
+    }
+
+    #[test]
+    fn test_bstring() {
+        let bstr = BString::from("Turing completeness.");
+        assert_eq!(bstr.len(), 20);
+        assert!(!bstr.is_null());
+        assert_eq!(bstr, "Turing completeness.");
+        assert_eq!(bstr.to_string(), "Turing completeness.");
+        assert_eq!(format!("{:?}", bstr), "\"Turing completeness.\"");
+        assert_eq!(bstr[0], u16::from(b'T'));
+
+        let cln = bstr.clone();
+        assert_ne!(cln.as_ptr(), bstr.as_ptr());
+        assert_eq!(cln, bstr);
+
+        let raw = cln.into_raw();
+        let cln = unsafe { BString::from_raw(raw) };
+        assert_eq!(cln.as_ptr(), raw);
+        assert_ne!(cln, BString::from("Turing"));
+    }
+
+    #[test]
+    fn test_null() {
+        let null = BString::default();
+        assert!(null.is_null());
+        assert!(null.is_empty());
+        assert!(null.as_ptr().is_null());
+        assert!(null.clone().is_null());
+        assert_eq!(null, "");
+        assert_eq!(null, BString::from(""));
+        assert!(!BString::from("").is_null());
+        assert_eq!(null.to_string(), "");
+        assert!(null.into_raw().is_null());
+    }
+
+    #[test]
+    fn test_bstr() {
+        let bstr = BString::from("Turing completeness.");
+        let view = unsafe { BStr::from_ptr(bstr.as_ptr()) };
+        assert_eq!(view.as_slice().as_ptr(), bstr.as_ptr() as *const u16);
+        assert_eq!(view.len(), 20);
+        assert_eq!(view, bstr.as_bstr());
+        assert!(*view == *"Turing completeness.");
+        assert_eq!(view.to_string(), "Turing completeness.");
+        assert_eq!(format!("[{}]", view), "[Turing completeness.]");
+        assert_eq!(view.to_string_strict().unwrap(), "Turing completeness.");
+        assert_eq!(view.to_owned(), bstr);
+
+        let null = unsafe { BStr::from_ptr(ptr::null()) };
+        assert!(null.is_empty());
+        assert_eq!(null.to_string_strict().unwrap(), "");
+        assert_eq!(null, BString::new().as_bstr());
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = b"\x00odd\x00";
+        let bstr = BString::from_bytes(bytes).unwrap();
+        assert_eq!(bstr.byte_len(), 5);
+        assert_eq!(bstr.len(), 2);
+        assert_eq!(bstr.as_bytes(), bytes);
+        assert_eq!(bstr.clone().as_bytes(), bytes);
+
+        let raw = bstr.into_raw();
+        let view = unsafe { BStr::from_ptr(raw) };
+        assert_eq!(view.byte_len(), 5);
+        assert_eq!(view.as_bytes(), bytes);
+        assert_eq!(view.to_owned().as_bytes(), bytes);
+        let bstr = unsafe { BString::from_raw(raw) };
+        assert_eq!(bstr.as_bytes(), bytes);
+
+        let empty = BString::from_bytes(&[]).unwrap();
+        assert!(!empty.is_null());
+        assert_eq!(empty.as_bytes(), b"");
+        assert_eq!(BString::new().as_bytes(), b"");
+        assert_eq!(BString::new().byte_len(), 0);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_alloc_len() {
+        assert_eq!(alloc_len(5).unwrap(), 5);
+        assert_eq!(alloc_len(u32::max_value() as usize).unwrap(), u32::max_value());
+        match alloc_len(u32::max_value() as usize + 1) {
+            Err(BStringError::AllocateFailed{len}) => assert_eq!(len, 1 << 32),
+            r => panic!("unexpected result: {:?}", r)
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf16() {
+        let bstr = BString::from_wide(&[0x48, 0xD800, 0x69]).unwrap();
+        assert_eq!(bstr.to_string(), "H\u{FFFD}i");
+        assert_eq!(bstr.to_string_lossy(), "H\u{FFFD}i");
+        let view = unsafe { BStr::from_ptr(bstr.as_ptr()) };
+        assert!(view.to_string_strict().is_err());
+        assert_eq!(view.to_string(), "H\u{FFFD}i");
+        assert_eq!(view.to_string_lossy(), "H\u{FFFD}i");
+    }
+}
@@ -0,0 +1,285 @@
+//! # ByRef
+//! VT_BYREF VARIANTs that alias memory owned by the caller, as `[in, out] VARIANT*`
+//! parameters (VB6 `ByRef` arguments) pass them.
+//!
+//! Unlike the `Box<T>` implementations of [`VariantExt`], [`ByRef`] never allocates:
+//! the VARIANT points at the caller's `T`, and writes through the `&mut T` read back out
+//! of a VARIANT land in the caller's memory. For that reason it does not implement
+//! `VariantExt`, whose VARIANTs outlive any borrow.
+//!
+//! [`VariantExt`]: trait.VariantExt.html
+//! [`ByRef`]: struct.ByRef.html
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+use super::alloc::allocator;
+use super::errors::FromVariantError;
+use super::sys::{
+    BSTR,
+    DECIMAL,
+    IDispatch,
+    IUnknown,
+    VARIANT,
+    VT_BSTR,
+    VT_BYREF,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_DISPATCH,
+    VT_ERROR,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_R4,
+    VT_R8,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+    VT_UNKNOWN,
+    VT_VARIANT,
+    c_void,
+};
+use super::tracking;
+use super::types::{Currency, Date, Int, SCode, UInt};
+use super::vartype::VarType;
+
+/// Types a VT_BYREF VARIANT can point at.
+///
+/// ## Safety
+/// The type must have the exact memory layout of the C type `VARTYPE` describes,
+/// since the VARIANT's pointer is read and written as a `*mut Self`.
+pub unsafe trait ByRefTarget {
+    /// VARTYPE of the pointee, without VT_BYREF
+    const VARTYPE: u32;
+}
+
+macro_rules! byref_target_impl {
+    ($($t:ty => $vt:expr),* $(,)*) => {
+        $(
+            unsafe impl ByRefTarget for $t {
+                const VARTYPE: u32 = $vt;
+            }
+        )*
+    };
+}
+
+byref_target_impl!{
+    i8 => VT_I1,
+    i16 => VT_I2,
+    i32 => VT_I4,
+    i64 => VT_I8,
+    u8 => VT_UI1,
+    u16 => VT_UI2,
+    u32 => VT_UI4,
+    u64 => VT_UI8,
+    f32 => VT_R4,
+    f64 => VT_R8,
+    Int => VT_INT,
+    UInt => VT_UINT,
+    Currency => VT_CY,
+    Date => VT_DATE,
+    SCode => VT_ERROR,
+    DECIMAL => VT_DECIMAL,
+    BSTR => VT_BSTR,
+    VARIANT => VT_VARIANT,
+    *mut IUnknown => VT_UNKNOWN,
+    *mut IDispatch => VT_DISPATCH,
+}
+
+/// A mutable reference wrapped as a VT_BYREF VARIANT.
+///
+/// `ByRef::new(&mut x).into_variant()` builds a VARIANT of `VT_BYREF | T::VARTYPE` pointing
+/// at `x`, inside a [`ByRefVariant`] which keeps `x` borrowed for as long as the VARIANT
+/// lives. `ByRef::<T>::from_variant_ref` reads such a VARIANT back as a `&mut T` into the
+/// memory it points at, so assignments are seen by whoever owns that memory.
+///
+/// VT_BOOL is not supported, since `bool` does not have the layout of `VARIANT_BOOL`.
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::ByRef;
+///
+/// fn main() {
+///     let mut count = 1i32;
+///     {
+///         let var = ByRef::new(&mut count).into_variant();
+///         let mut r = unsafe { ByRef::<i32>::from_variant_ref(var.as_variant()) }.unwrap();
+///         *r += 41;
+///     }
+///     assert_eq!(count, 42);
+/// }
+/// ```
+///
+/// [`ByRefVariant`]: struct.ByRefVariant.html
+pub struct ByRef<'a, T: 'a + ByRefTarget> {
+    inner: &'a mut T,
+}
+
+impl<'a, T: 'a + ByRefTarget> ByRef<'a, T> {
+    /// Wraps a reference to caller owned memory
+    #[allow(clippy::redundant_field_names)]
+    pub fn new(inner: &'a mut T) -> ByRef<'a, T> {
+        ByRef { inner: inner }
+    }
+
+    /// Get access to the inner reference and the ByRef is consumed
+    pub fn into_inner(self) -> &'a mut T {
+        self.inner
+    }
+
+    /// Builds a VARIANT of `VT_BYREF | T::VARTYPE` pointing at the wrapped memory, which
+    /// stays borrowed until the returned [`ByRefVariant`] is dropped.
+    ///
+    /// [`ByRefVariant`]: struct.ByRefVariant.html
+    pub fn into_variant(self) -> ByRefVariant<'a> {
+        let mut var: VARIANT = unsafe { mem::zeroed() };
+        unsafe {
+            let n2 = var.n1.n2_mut();
+            n2.vt = (VT_BYREF | T::VARTYPE) as u16;
+            *n2.n3.byref_mut() = self.inner as *mut T as *mut c_void;
+        }
+        ByRefVariant { inner: var, _borrow: PhantomData }
+    }
+
+    /// Reads a VARIANT of `VT_BYREF | T::VARTYPE` as a `&mut T` to the memory it points at.
+    /// The VARIANT is left untouched.
+    ///
+    /// ## Safety
+    ///
+    /// The pointee must be a valid `T` which outlives `'a`, and nothing else may access
+    /// it while the returned `ByRef` is alive.
+    #[allow(clippy::redundant_field_names)]
+    pub unsafe fn from_variant_ref(var: &'a VARIANT) -> Result<ByRef<'a, T>, FromVariantError> {
+        let n2 = var.n1.n2();
+        if n2.vt as u32 != VT_BYREF | T::VARTYPE {
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: VarType::from(VT_BYREF | T::VARTYPE), found: VarType::from(n2.vt)});
+        }
+        match (*n2.n3.byref() as *mut T).as_mut() {
+            Some(inner) => Ok(ByRef { inner: inner }),
+            None => Err(FromVariantError::CVoidPtrNull),
+        }
+    }
+}
+
+impl<'a, T: 'a + ByRefTarget> Deref for ByRef<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.inner
+    }
+}
+
+impl<'a, T: 'a + ByRefTarget> DerefMut for ByRef<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.inner
+    }
+}
+
+impl<'a, T: 'a + ByRefTarget + fmt::Debug> fmt::Debug for ByRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ByRef").field(&self.inner).finish()
+    }
+}
+
+/// A VT_BYREF VARIANT built by [`ByRef::into_variant`], borrowing the memory it points at.
+///
+/// Pass it on as an `[in, out] VARIANT*` with `as_mut_ptr`. Whatever the callee leaves
+/// in the VARIANT is cleared on drop; a VT_BYREF value owns nothing, so this is a no-op
+/// unless the callee replaced it.
+///
+/// [`ByRef::into_variant`]: struct.ByRef.html#method.into_variant
+pub struct ByRefVariant<'a> {
+    inner: VARIANT,
+    _borrow: PhantomData<&'a mut ()>,
+}
+
+impl<'a> ByRefVariant<'a> {
+    /// The VARIANT, for reading it back with `ByRef::from_variant_ref` or `VariantRef::new`
+    pub fn as_variant(&self) -> &VARIANT {
+        &self.inner
+    }
+
+    /// Pointer to the VARIANT, for passing as an `[in] VARIANT*` argument
+    pub fn as_ptr(&self) -> *const VARIANT {
+        &self.inner
+    }
+
+    /// Mutable pointer to the VARIANT, for passing as an `[in, out] VARIANT*` argument
+    pub fn as_mut_ptr(&mut self) -> *mut VARIANT {
+        &mut self.inner
+    }
+
+    /// The vartype of the VARIANT
+    pub fn vartype(&self) -> VarType {
+        VarType::from(unsafe { self.inner.n1.n2().vt })
+    }
+}
+
+impl<'a> fmt::Debug for ByRefVariant<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ByRefVariant").field("vt", &self.vartype()).finish()
+    }
+}
+
+impl<'a> Drop for ByRefVariant<'a> {
+    fn drop(&mut self) {
+        let _tag = tracking::tag::<Self>("drop");
+        unsafe { allocator().variant_clear(&mut self.inner) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::tracking::AllocTracker;
+    use super::super::value::VariantValue;
+    use super::super::variant::VariantRef;
+
+    #[test]
+    fn test_write_back() {
+        let tracker = AllocTracker::start();
+        let mut x = 5i32;
+        {
+            let var = ByRef::new(&mut x).into_variant();
+            assert_eq!(var.vartype(), VT_BYREF | VT_I4);
+            let mut r = unsafe { ByRef::<i32>::from_variant_ref(var.as_variant()) }.unwrap();
+            assert_eq!(*r, 5);
+            *r = 7;
+        }
+        assert_eq!(x, 7);
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    fn test_aliasing() {
+        let mut cy = Currency::from(10000);
+        let pcy = &mut cy as *mut Currency as *mut c_void;
+        {
+            let var = ByRef::new(&mut cy).into_variant();
+            match VariantRef::new(var.as_variant()).value().unwrap() {
+                VariantValue::ByRef{vartype, pointer} => {
+                    assert_eq!(vartype, VT_CY);
+                    assert_eq!(pointer.as_ptr(), pcy);
+                },
+                v => panic!("unexpected value: {:?}", v)
+            }
+            match unsafe { ByRef::<Date>::from_variant_ref(var.as_variant()) } {
+                Err(FromVariantError::VarTypeDoesNotMatch{expected, found}) => {
+                    assert_eq!(expected, VT_BYREF | VT_DATE);
+                    assert_eq!(found, VT_BYREF | VT_CY);
+                },
+                r => panic!("unexpected result: {:?}", r)
+            }
+            *unsafe { ByRef::<Currency>::from_variant_ref(var.as_variant()) }.unwrap().into_inner() = Currency::from(20000);
+        }
+        assert_eq!(cy, Currency::from(20000));
+    }
+}
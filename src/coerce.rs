@@ -0,0 +1,847 @@
+//! # Coercion
+//! A pure Rust implementation of the OLE Automation conversion rules used by
+//! `VariantChangeType`, so that it works without OleAut32.
+//!
+//! Conversions follow the invariant locale:
+//!   * numbers are widened and narrowed with a range check - `DISP_E_OVERFLOW` on failure
+//!   * reals are rounded to integers with bankers rounding, like OleAut32 does
+//!   * strings are parsed as numbers (`1,000.5`, `1e3`, `&HFF`), booleans (`True`) or dates
+//!     (`2018-10-08`, `10/08/2018 14:30:00`)
+//!   * `true` becomes -1 and `false` becomes 0
+//!   * VT_EMPTY becomes zero, `false` or the empty string
+//!
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use super::errors::ChangeTypeError;
+use super::sys::{
+    VT_BOOL,
+    VT_BSTR,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_EMPTY,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_NULL,
+    VT_R4,
+    VT_R8,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+};
+use super::types::{Currency, Date, DecWrapper, Int, UInt};
+use super::value::VariantValue;
+use super::vartype::VarType;
+
+/// Smallest DATE value OleAut32 accepts - 1 January 100
+const DATE_MIN: f64 = -657434.0;
+/// One past the largest DATE value OleAut32 accepts - 1 January 10000
+const DATE_MAX: f64 = 2958466.0;
+/// `days_from_civil(1899, 12, 30)`, the day DATE counts from
+const DATE_EPOCH: i64 = -25569;
+
+/// Intermediate representation of a numeric value during coercion
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Number {
+    Int(i128),
+    Real(f64),
+    Dec(Decimal),
+}
+
+impl VariantValue {
+    /// Converts the value to another vartype, following the rules `VariantChangeType`
+    /// applies in the invariant locale.
+    ///
+    /// Converting to the value's own vartype returns a copy. Pointer types (arrays,
+    /// interfaces, records and VT_BYREF) can only be converted to themselves.
+    ///
+    /// ## Example usage
+    ///
+    /// ```
+    /// extern crate oaidl;
+    ///
+    /// use oaidl::sys::{VT_I2, VT_I4};
+    /// use oaidl::{VariantValue, VarType};
+    ///
+    /// fn main() {
+    ///     let val = VariantValue::from(" 42 ").change_type(VarType::from(VT_I4)).unwrap();
+    ///     assert_eq!(val, VariantValue::I4(42));
+    ///     assert!(VariantValue::I4(40000).change_type(VarType::from(VT_I2)).is_err());
+    /// }
+    /// ```
+    #[allow(clippy::legacy_numeric_constants, clippy::manual_range_contains, clippy::redundant_field_names, clippy::wildcard_in_or_patterns)]
+    pub fn change_type(&self, to: VarType) -> Result<VariantValue, ChangeTypeError> {
+        let from = self.vartype();
+        if from == to {
+            return Ok(self.clone());
+        }
+        let overflow = ChangeTypeError::Overflow{from: from, to: to};
+        let mismatch = ChangeTypeError::TypeMismatch{from: from, to: to};
+        let vartype = u32::from(to);
+
+        match *self {
+            VariantValue::Null => return Err(mismatch),
+            VariantValue::Error(_) | VariantValue::Dispatch(_) | VariantValue::Unknown(_) |
+            VariantValue::Array{..} | VariantValue::ByRef{..} | VariantValue::Record{..} => {
+                return match vartype {
+                    VT_EMPTY => Ok(VariantValue::Empty),
+                    _ => Err(mismatch)
+                };
+            },
+            _ => {}
+        }
+
+        if vartype == VT_EMPTY {
+            return Ok(VariantValue::Empty);
+        }
+        if vartype == VT_BSTR {
+            return to_bstr(self).map(VariantValue::Bstr).ok_or(overflow);
+        }
+        if vartype == VT_BOOL {
+            if let VariantValue::Bstr(ref s) = *self {
+                if let Some(b) = parse_bool(s) {
+                    return Ok(VariantValue::Bool(b));
+                }
+            }
+        }
+        if vartype == VT_DATE {
+            if let VariantValue::Bstr(ref s) = *self {
+                return match parse_date(s) {
+                    Some(dt) => Ok(VariantValue::Date(Date::from(dt))),
+                    None => Err(mismatch)
+                };
+            }
+        }
+
+        let num = match *self {
+            // OleAut32 casts VARIANT_TRUE to unsigned types, setting every bit
+            VariantValue::Bool(true) => match vartype {
+                VT_UI1 => Number::Int(u8::max_value() as i128),
+                VT_UI2 => Number::Int(u16::max_value() as i128),
+                VT_UI4 | VT_UINT => Number::Int(u32::max_value() as i128),
+                VT_UI8 => Number::Int(u64::max_value() as i128),
+                _ => Number::Int(-1)
+            },
+            VariantValue::Bstr(ref s) => match parse_radix_literal(s) {
+                Some((negative, i)) => {
+                    let i = wrap_radix_literal(i, vartype);
+                    Number::Int(if negative {-i} else {i})
+                },
+                None => match parse_number(s) {
+                    Some(num) => num,
+                    None => return Err(mismatch)
+                }
+            },
+            _ => match to_number(self) {
+                Some(num) => num,
+                None => return Err(mismatch)
+            }
+        };
+
+        let val = match vartype {
+            VT_I1 => VariantValue::I1(to_integer(num, i8::min_value() as i128, i8::max_value() as i128).ok_or(overflow)? as i8),
+            VT_I2 => VariantValue::I2(to_integer(num, i16::min_value() as i128, i16::max_value() as i128).ok_or(overflow)? as i16),
+            VT_I4 => VariantValue::I4(to_integer(num, i32::min_value() as i128, i32::max_value() as i128).ok_or(overflow)? as i32),
+            VT_I8 => VariantValue::I8(to_integer(num, i64::min_value() as i128, i64::max_value() as i128).ok_or(overflow)? as i64),
+            VT_UI1 => VariantValue::UI1(to_integer(num, 0, u8::max_value() as i128).ok_or(overflow)? as u8),
+            VT_UI2 => VariantValue::UI2(to_integer(num, 0, u16::max_value() as i128).ok_or(overflow)? as u16),
+            VT_UI4 => VariantValue::UI4(to_integer(num, 0, u32::max_value() as i128).ok_or(overflow)? as u32),
+            VT_UI8 => VariantValue::UI8(to_integer(num, 0, u64::max_value() as i128).ok_or(overflow)? as u64),
+            VT_INT => {
+                let i = to_integer(num, i32::min_value() as i128, i32::max_value() as i128).ok_or(overflow)?;
+                VariantValue::Int(Int::from(i as i32))
+            },
+            VT_UINT => {
+                let i = to_integer(num, 0, u32::max_value() as i128).ok_or(overflow)?;
+                VariantValue::UInt(UInt::from(i as u32))
+            },
+            VT_R4 => {
+                let r = to_real(num);
+                if r.is_finite() && r.abs() > f32::MAX as f64 {
+                    return Err(overflow);
+                }
+                VariantValue::R4(r as f32)
+            },
+            VT_R8 => VariantValue::R8(to_real(num)),
+            VT_DATE => {
+                let r = to_real(num);
+                if !(r >= DATE_MIN && r < DATE_MAX) {
+                    return Err(overflow);
+                }
+                VariantValue::Date(Date::from(r))
+            },
+            VT_CY => VariantValue::Cy(to_currency(num).ok_or(overflow)?),
+            VT_DECIMAL => VariantValue::Decimal(DecWrapper::from(to_decimal(num).ok_or(overflow)?)),
+            VT_BOOL => VariantValue::Bool(match num {
+                Number::Int(i) => i != 0,
+                Number::Real(r) => r != 0.0,
+                Number::Dec(d) => d != Decimal::new(0, 0)
+            }),
+            VT_NULL | _ => return Err(mismatch)
+        };
+        Ok(val)
+    }
+}
+
+pub(crate) fn to_number(val: &VariantValue) -> Option<Number> {
+    let num = match *val {
+        VariantValue::Empty => Number::Int(0),
+        VariantValue::I1(v) => Number::Int(v as i128),
+        VariantValue::I2(v) => Number::Int(v as i128),
+        VariantValue::I4(v) => Number::Int(v as i128),
+        VariantValue::I8(v) => Number::Int(v as i128),
+        VariantValue::UI1(v) => Number::Int(v as i128),
+        VariantValue::UI2(v) => Number::Int(v as i128),
+        VariantValue::UI4(v) => Number::Int(v as i128),
+        VariantValue::UI8(v) => Number::Int(v as i128),
+        VariantValue::Int(v) => Number::Int(i32::from(v) as i128),
+        VariantValue::UInt(v) => Number::Int(u32::from(v) as i128),
+        VariantValue::Bool(v) => Number::Int(if v {-1} else {0}),
+        VariantValue::R4(v) => Number::Real(v as f64),
+        VariantValue::R8(v) => Number::Real(v),
+        VariantValue::Date(v) => Number::Real(f64::from(v)),
+        VariantValue::Cy(v) => Number::Dec(currency_to_decimal(v)),
+        VariantValue::Decimal(v) => Number::Dec(Decimal::from(v)),
+        VariantValue::Bstr(ref s) => return parse_number(s),
+        _ => return None
+    };
+    Some(num)
+}
+
+/// Rounds half way cases to the nearest even integer
+fn round_half_even(r: f64) -> f64 {
+    if (r - r.trunc()).abs() == 0.5 {
+        2.0 * (r / 2.0).round()
+    } else {
+        r.round()
+    }
+}
+
+fn to_integer(num: Number, min: i128, max: i128) -> Option<i128> {
+    let i = match num {
+        Number::Int(i) => i,
+        Number::Real(r) => {
+            let r = round_half_even(r);
+            if !(r >= min as f64 && r <= max as f64) {
+                return None;
+            }
+            r as i128
+        },
+        Number::Dec(d) => i128::from_str(&d.round().to_string()).ok()?
+    };
+    if i < min || i > max {
+        return None;
+    }
+    Some(i)
+}
+
+pub(crate) fn to_real(num: Number) -> f64 {
+    match num {
+        Number::Int(i) => i as f64,
+        Number::Real(r) => r,
+        Number::Dec(d) => f64::from_str(&d.to_string()).unwrap_or(0.0)
+    }
+}
+
+#[allow(clippy::legacy_numeric_constants)]
+fn to_currency(num: Number) -> Option<Currency> {
+    let cy = match num {
+        Number::Int(i) => {
+            let i = i.checked_mul(10000)?;
+            if i < i64::min_value() as i128 || i > i64::max_value() as i128 {
+                return None;
+            }
+            i as i64
+        },
+        Number::Real(r) => {
+            let r = round_half_even(r * 10000.0);
+            if !(r >= i64::min_value() as f64 && r < i64::max_value() as f64) {
+                return None;
+            }
+            r as i64
+        },
+        Number::Dec(d) => {
+            if d < currency_to_decimal(Currency::from(i64::min_value())) || d > currency_to_decimal(Currency::from(i64::max_value())) {
+                return None;
+            }
+            let scaled = (d * Decimal::new(10000, 0)).round();
+            i64::from_str(&scaled.to_string()).ok()?
+        }
+    };
+    Some(Currency::from(cy))
+}
+
+/// `Decimal::new` cannot take `i64::min_value()`, so build the parts by hand
+#[allow(clippy::cast_abs_to_unsigned)]
+fn currency_to_decimal(cy: Currency) -> Decimal {
+    let cy = i64::from(cy);
+    let abs = (cy as i128).abs() as u64;
+    Decimal::from_parts(abs as u32, (abs >> 32) as u32, 0, cy < 0, 4)
+}
+
+fn to_decimal(num: Number) -> Option<Decimal> {
+    match num {
+        Number::Int(i) => Decimal::from_str(&i.to_string()).ok(),
+        Number::Real(r) => real_to_decimal(r),
+        Number::Dec(d) => Some(d)
+    }
+}
+
+/// Converts through the 15 significant digits a double is displayed with
+fn real_to_decimal(r: f64) -> Option<Decimal> {
+    // Largest magnitude a DECIMAL can hold, 2^96 - 1
+    if !r.is_finite() || r.abs() >= 79228162514264337593543950335.0 {
+        return None;
+    }
+    let (negative, digits, exp) = real_digits(r, 15);
+    if digits == "0" {
+        return Some(Decimal::new(0, 0));
+    }
+    let mut plain = String::new();
+    if exp >= 0 {
+        let exp = exp as usize;
+        plain.push_str(&digits[..digits.len().min(exp + 1)]);
+        for _ in digits.len()..(exp + 1) {
+            plain.push('0');
+        }
+        if digits.len() > exp + 1 {
+            plain.push('.');
+            plain.push_str(&digits[exp + 1..]);
+        }
+    } else {
+        plain.push_str("0.");
+        for _ in 0..(-exp - 1) {
+            plain.push('0');
+        }
+        plain.push_str(&digits);
+        // DECIMAL keeps at most 28 digits after the point
+        plain.truncate(30);
+    }
+    let mut dec = Decimal::from_str(&plain).ok()?;
+    if negative {
+        dec = -dec;
+    }
+    Some(dec)
+}
+
+/// Splits a finite real into its sign, significant digits (without trailing zeros)
+/// and decimal exponent, rounded to `precision` significant digits.
+#[allow(clippy::is_digit_ascii_radix)]
+fn real_digits(r: f64, precision: usize) -> (bool, String, i32) {
+    if r == 0.0 {
+        return (false, String::from("0"), 0);
+    }
+    let sci = format!("{:.*e}", precision - 1, r);
+    let epos = sci.find('e').unwrap();
+    let exp = i32::from_str(&sci[epos + 1..]).unwrap();
+    let mut digits: String = sci[..epos].chars().filter(|c| c.is_digit(10)).collect();
+    while digits.len() > 1 && digits.ends_with('0') {
+        digits.pop();
+    }
+    (r < 0.0, digits, exp)
+}
+
+/// Formats a real the way `VarBstrFromR8` does in the invariant locale
+fn format_real(r: f64, precision: usize) -> Option<String> {
+    if !r.is_finite() {
+        return None;
+    }
+    let (negative, digits, exp) = real_digits(r, precision);
+    let mut s = String::new();
+    if negative {
+        s.push('-');
+    }
+    if exp < -5 || exp >= precision as i32 {
+        s.push_str(&digits[..1]);
+        if digits.len() > 1 {
+            s.push('.');
+            s.push_str(&digits[1..]);
+        }
+        s.push_str(&format!("E{}{:02}", if exp < 0 {'-'} else {'+'}, exp.abs()));
+    } else if exp < 0 {
+        s.push_str("0.");
+        for _ in 0..(-exp - 1) {
+            s.push('0');
+        }
+        s.push_str(&digits);
+    } else {
+        let exp = exp as usize;
+        if digits.len() > exp + 1 {
+            s.push_str(&digits[..exp + 1]);
+            s.push('.');
+            s.push_str(&digits[exp + 1..]);
+        } else {
+            s.push_str(&digits);
+            for _ in digits.len()..(exp + 1) {
+                s.push('0');
+            }
+        }
+    }
+    Some(s)
+}
+
+fn to_bstr(val: &VariantValue) -> Option<String> {
+    let s = match *val {
+        VariantValue::Empty => String::new(),
+        VariantValue::I1(v) => v.to_string(),
+        VariantValue::I2(v) => v.to_string(),
+        VariantValue::I4(v) => v.to_string(),
+        VariantValue::I8(v) => v.to_string(),
+        VariantValue::UI1(v) => v.to_string(),
+        VariantValue::UI2(v) => v.to_string(),
+        VariantValue::UI4(v) => v.to_string(),
+        VariantValue::UI8(v) => v.to_string(),
+        VariantValue::Int(v) => i32::from(v).to_string(),
+        VariantValue::UInt(v) => u32::from(v).to_string(),
+        VariantValue::R4(v) => format_real(v as f64, 7)?,
+        VariantValue::R8(v) => format_real(v, 15)?,
+        VariantValue::Cy(v) => currency_to_decimal(v).normalize().to_string(),
+        VariantValue::Decimal(v) => Decimal::from(v).normalize().to_string(),
+        VariantValue::Date(v) => format_date(f64::from(v))?,
+        VariantValue::Bool(v) => String::from(if v {"True"} else {"False"}),
+        VariantValue::Bstr(ref s) => s.clone(),
+        _ => return None
+    };
+    Some(s)
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if s.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Splits the sign off a trimmed number
+#[allow(clippy::manual_strip)]
+fn split_sign(s: &str) -> (bool, &str) {
+    if s.starts_with('-') {
+        (true, &s[1..])
+    } else if s.starts_with('+') {
+        (false, &s[1..])
+    } else {
+        (false, s)
+    }
+}
+
+/// Parses a `&H`/`&O` hex/octal integer with an optional sign, returning the sign and
+/// the unsigned value of the literal
+#[allow(clippy::is_digit_ascii_radix)]
+fn parse_radix_literal(s: &str) -> Option<(bool, i128)> {
+    let (negative, body) = split_sign(s.trim());
+    if body.len() <= 2 || !body.is_char_boundary(2) {
+        return None;
+    }
+    let radix = match &body[..2] {
+        "&H" | "&h" => 16,
+        "&O" | "&o" => 8,
+        _ => return None
+    };
+    let digits = &body[2..];
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let i = i128::from_str_radix(digits, radix).ok()?;
+    Some((negative, i))
+}
+
+/// Wraps the value of a hex/octal literal to a signed target the way `VarI2FromStr` and
+/// friends do, so that `&HFFFF` is -1 as a VT_I2. Larger values are left to overflow.
+fn wrap_radix_literal(i: i128, vartype: u32) -> i128 {
+    let bits = match vartype {
+        VT_I1 => 8,
+        VT_I2 => 16,
+        VT_I4 | VT_INT => 32,
+        VT_I8 => 64,
+        _ => return i
+    };
+    if i >= 1 << (bits - 1) && i < 1 << bits {
+        i - (1 << bits)
+    } else {
+        i
+    }
+}
+
+/// Parses a number in the invariant locale: optional sign, `,` thousands separators in
+/// the integer part, `.` decimal point and an exponent, or a `&H`/`&O` hex/octal integer.
+#[allow(clippy::is_digit_ascii_radix, clippy::manual_pattern_char_comparison)]
+fn parse_number(s: &str) -> Option<Number> {
+    if let Some((negative, i)) = parse_radix_literal(s) {
+        return Some(Number::Int(if negative {-i} else {i}));
+    }
+    let (negative, body) = split_sign(s.trim());
+
+    let (mantissa, exponent) = match body.find(|c| c == 'e' || c == 'E') {
+        Some(ix) => (&body[..ix], Some(&body[ix + 1..])),
+        None => (body, None),
+    };
+    let (int, frac) = match mantissa.find('.') {
+        Some(ix) => (&mantissa[..ix], &mantissa[ix + 1..]),
+        None => (mantissa, ""),
+    };
+    // Separators only go between the digits of the integer part
+    if int.starts_with(',') || !int.chars().all(|c| c.is_digit(10) || c == ',') ||
+       !frac.chars().all(|c| c.is_digit(10)) ||
+       !int.chars().chain(frac.chars()).any(|c| c.is_digit(10)) {
+        return None;
+    }
+    let mut cleaned: String = int.chars().filter(|&c| c != ',').collect();
+    if !frac.is_empty() {
+        cleaned.push('.');
+        cleaned.push_str(frac);
+    }
+    if let Some(exponent) = exponent {
+        let (exp_negative, exp_digits) = split_sign(exponent);
+        if exp_digits.is_empty() || !exp_digits.chars().all(|c| c.is_digit(10)) {
+            return None;
+        }
+        let r = f64::from_str(&format!("{}e{}{}", cleaned, if exp_negative {"-"} else {""}, exp_digits)).ok()?;
+        return Some(Number::Real(if negative {-r} else {r}));
+    }
+    if let Ok(i) = i128::from_str(&cleaned) {
+        return Some(Number::Int(if negative {-i} else {i}));
+    }
+    if let Ok(d) = Decimal::from_str(&cleaned) {
+        return Some(Number::Dec(if negative {-d} else {d}));
+    }
+    let r = f64::from_str(&cleaned).ok()?;
+    Some(Number::Real(if negative {-r} else {r}))
+}
+
+/// Days since 1 March 0000 style civil calendar conversion, after Howard Hinnant
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 {y - 1} else {y};
+    let era = if y >= 0 {y} else {y - 399} / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 {m - 3} else {m + 9}) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = if z >= 0 {z} else {z - 146096} / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 {mp + 3} else {mp - 9};
+    (if m <= 2 {y + 1} else {y}, m, d)
+}
+
+fn days_in_month(y: i64, m: i64) -> i64 {
+    match m {
+        2 if (y % 4 == 0 && y % 100 != 0) || y % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// Builds a DATE from its parts. Times are added away from zero, which is how DATE
+/// encodes times on days before 30 December 1899.
+fn date_from_parts(y: i64, m: i64, d: i64, secs: i64) -> f64 {
+    let days = days_from_civil(y, m, d) - DATE_EPOCH;
+    let frac = secs as f64 / 86400.0;
+    if days < 0 {
+        days as f64 - frac
+    } else {
+        days as f64 + frac
+    }
+}
+
+/// Splits a DATE into its calendar day and the seconds since midnight
+#[allow(clippy::manual_range_contains)]
+fn date_to_parts(dt: f64) -> Option<((i64, i64, i64), i64)> {
+    if !(dt >= DATE_MIN && dt < DATE_MAX) {
+        return None;
+    }
+    let days = dt.trunc();
+    let secs = round_half_even((dt - days).abs() * 86400.0).min(86399.0);
+    Some((civil_from_days(days as i64 + DATE_EPOCH), secs as i64))
+}
+
+/// Formats a DATE as `MM/DD/YYYY HH:MM:SS`, leaving out a midnight time and the
+/// date of a pure time value.
+fn format_date(dt: f64) -> Option<String> {
+    let ((y, m, d), secs) = date_to_parts(dt)?;
+    let date = format!("{:02}/{:02}/{:04}", m, d, y);
+    let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    if dt.trunc() == 0.0 {
+        Some(time)
+    } else if secs == 0 {
+        Some(date)
+    } else {
+        Some(format!("{} {}", date, time))
+    }
+}
+
+#[allow(clippy::is_digit_ascii_radix, clippy::manual_pattern_char_comparison, clippy::manual_range_contains)]
+fn parse_date_part(s: &str) -> Option<(i64, i64, i64)> {
+    let parts: Vec<&str> = s.split(|c| c == '-' || c == '/').collect();
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_digit(10))) {
+        return None;
+    }
+    let nums: Vec<i64> = parts.iter().map(|p| i64::from_str(p)).collect::<Result<_, _>>().ok()?;
+    let (y, m, d) = if parts[0].len() == 4 {
+        (nums[0], nums[1], nums[2])
+    } else {
+        let y = match (parts[2].len(), nums[2]) {
+            (1..=2, y) if y < 30 => 2000 + y,
+            (1..=2, y) => 1900 + y,
+            (_, y) => y
+        };
+        (y, nums[0], nums[1])
+    };
+    if m < 1 || m > 12 || d < 1 || d > days_in_month(y, m) || y < 100 || y > 9999 {
+        return None;
+    }
+    Some((y, m, d))
+}
+
+#[allow(clippy::is_digit_ascii_radix)]
+fn parse_time_part(s: &str) -> Option<(i64, i64, i64)> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_digit(10))) {
+        return None;
+    }
+    let nums: Vec<i64> = parts.iter().map(|p| i64::from_str(p)).collect::<Result<_, _>>().ok()?;
+    let (h, m, s) = (nums[0], nums[1], if nums.len() == 3 {nums[2]} else {0});
+    if h > 23 || m > 59 || s > 59 {
+        return None;
+    }
+    Some((h, m, s))
+}
+
+/// Parses a date and/or time in the invariant locale into a DATE
+#[allow(clippy::manual_range_contains)]
+fn parse_date(s: &str) -> Option<f64> {
+    let mut date = None;
+    let mut time = None;
+    let mut meridiem = None;
+    for token in s.split(|c: char| c.is_whitespace() || c == 'T').filter(|t| !t.is_empty()) {
+        if token.eq_ignore_ascii_case("AM") || token.eq_ignore_ascii_case("PM") {
+            if meridiem.is_some() {
+                return None;
+            }
+            meridiem = Some(token.eq_ignore_ascii_case("PM"));
+        } else if token.contains(':') {
+            if time.is_some() {
+                return None;
+            }
+            time = Some(parse_time_part(token)?);
+        } else {
+            if date.is_some() {
+                return None;
+            }
+            date = Some(parse_date_part(token)?);
+        }
+    }
+    if date.is_none() && time.is_none() {
+        return None;
+    }
+    let (mut h, m, s) = time.unwrap_or((0, 0, 0));
+    if let Some(pm) = meridiem {
+        if time.is_none() || h < 1 || h > 12 {
+            return None;
+        }
+        h = match (pm, h) {
+            (false, 12) => 0,
+            (true, 12) => 12,
+            (true, h) => h + 12,
+            (false, h) => h
+        };
+    }
+    let secs = h * 3600 + m * 60 + s;
+    Some(match date {
+        Some((y, mo, d)) => date_from_parts(y, mo, d, secs),
+        None => secs as f64 / 86400.0
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::sys::{VT_ARRAY, VT_BYREF, VT_ERROR, VT_VARIANT};
+    use super::super::errors::FromVariantError;
+    use super::super::ptr::Ptr;
+    use super::super::types::SCode;
+    use super::super::variant::{OwnedVariant, VariantExt};
+
+    macro_rules! validate_coerce {
+        ($val:expr, $vt:expr, $expected:expr) => {
+            assert_eq!(VariantValue::from($val).change_type(VarType::from($vt)).unwrap(), $expected);
+        };
+    }
+    macro_rules! validate_overflow {
+        ($val:expr, $vt:expr) => {
+            match VariantValue::from($val).change_type(VarType::from($vt)) {
+                Err(ChangeTypeError::Overflow{..}) => {},
+                r => panic!("expected overflow, got {:?}", r)
+            }
+        };
+    }
+    #[test]
+    fn test_numeric() {
+        validate_coerce!(300i16, VT_I4, VariantValue::I4(300));
+        validate_coerce!(-3i32, VT_I8, VariantValue::I8(-3));
+        validate_coerce!(2.5f64, VT_I4, VariantValue::I4(2));
+        validate_coerce!(3.5f64, VT_I4, VariantValue::I4(4));
+        validate_coerce!(-2.5f64, VT_I2, VariantValue::I2(-2));
+        validate_coerce!(7u8, VT_R8, VariantValue::R8(7.0));
+        validate_overflow!(300i32, VT_UI1);
+        validate_overflow!(-1i32, VT_UI4);
+        validate_overflow!(40000i32, VT_I2);
+        validate_overflow!(1e300f64, VT_R4);
+        validate_overflow!(1e20f64, VT_I8);
+    }
+    #[test]
+    fn test_bool() {
+        validate_coerce!(true, VT_I2, VariantValue::I2(-1));
+        validate_coerce!(false, VT_I4, VariantValue::I4(0));
+        validate_coerce!(true, VT_UI1, VariantValue::UI1(255));
+        validate_coerce!(true, VT_R8, VariantValue::R8(-1.0));
+        validate_coerce!(5i32, VT_BOOL, VariantValue::Bool(true));
+        validate_coerce!(0.0f64, VT_BOOL, VariantValue::Bool(false));
+        validate_coerce!(" true ", VT_BOOL, VariantValue::Bool(true));
+        validate_coerce!("-1", VT_BOOL, VariantValue::Bool(true));
+        validate_coerce!(true, VT_BSTR, VariantValue::from("True"));
+    }
+    #[test]
+    fn test_empty() {
+        assert_eq!(VariantValue::Empty.change_type(VarType::from(VT_I4)).unwrap(), VariantValue::I4(0));
+        assert_eq!(VariantValue::Empty.change_type(VarType::from(VT_R8)).unwrap(), VariantValue::R8(0.0));
+        assert_eq!(VariantValue::Empty.change_type(VarType::from(VT_BSTR)).unwrap(), VariantValue::from(""));
+        assert_eq!(VariantValue::Empty.change_type(VarType::from(VT_BOOL)).unwrap(), VariantValue::Bool(false));
+        assert!(VariantValue::Null.change_type(VarType::from(VT_I4)).is_err());
+        assert!(VariantValue::Error(SCode::from(10)).change_type(VarType::from(VT_I4)).is_err());
+        assert_eq!(VariantValue::Error(SCode::from(10)).change_type(VarType::from(VT_ERROR)).unwrap(), VariantValue::Error(SCode::from(10)));
+    }
+    #[test]
+    fn test_string_to_number() {
+        validate_coerce!("42", VT_I4, VariantValue::I4(42));
+        validate_coerce!(" -1,000 ", VT_I4, VariantValue::I4(-1000));
+        validate_coerce!("&HFF", VT_I2, VariantValue::I2(255));
+        validate_coerce!("1.5", VT_R8, VariantValue::R8(1.5));
+        validate_coerce!("1e3", VT_R4, VariantValue::R4(1000.0));
+        validate_coerce!("2.5", VT_I4, VariantValue::I4(2));
+        validate_coerce!("12.3456", VT_CY, VariantValue::Cy(Currency::from(123456)));
+        validate_coerce!("12.345", VT_DECIMAL, VariantValue::from(Decimal::new(12345, 3)));
+        validate_overflow!("70000", VT_I2);
+        validate_coerce!("&HFFFF", VT_I2, VariantValue::I2(-1));
+        validate_coerce!("&HFFFF", VT_I4, VariantValue::I4(65535));
+        validate_coerce!("&O177777", VT_I2, VariantValue::I2(-1));
+        validate_coerce!("&HFFFFFFFF", VT_I4, VariantValue::I4(-1));
+        validate_coerce!("&H80", VT_I1, VariantValue::I1(-128));
+        validate_coerce!("&HFF", VT_UI1, VariantValue::UI1(255));
+        validate_overflow!("&H10000", VT_I2);
+        validate_overflow!("&H100", VT_UI1);
+        validate_coerce!("1,234,5.5", VT_R8, VariantValue::R8(12345.5));
+        for s in &["--1", "+-1", "-+1", "&H-1", "&H+1", "-&H-1", "&HG", "1.2,3", ",1", "1e,3", "1e", "1e--3", "1.2.3", "."] {
+            match VariantValue::from(*s).change_type(VarType::from(VT_I4)) {
+                Err(ChangeTypeError::TypeMismatch{..}) => {},
+                r => panic!("{:?}: expected a type mismatch, got {:?}", s, r)
+            }
+        }
+        assert!(VariantValue::from("abc").change_type(VarType::from(VT_I4)).is_err());
+        assert!(VariantValue::from("inf").change_type(VarType::from(VT_R8)).is_err());
+        assert!(VariantValue::from("").change_type(VarType::from(VT_I4)).is_err());
+    }
+    #[test]
+    fn test_number_to_string() {
+        validate_coerce!(42i32, VT_BSTR, VariantValue::from("42"));
+        validate_coerce!(1.5f64, VT_BSTR, VariantValue::from("1.5"));
+        validate_coerce!(0.1f64, VT_BSTR, VariantValue::from("0.1"));
+        validate_coerce!(1e20f64, VT_BSTR, VariantValue::from("1E+20"));
+        validate_coerce!(-1.5e-7f64, VT_BSTR, VariantValue::from("-1.5E-07"));
+        validate_coerce!(1.1f32, VT_BSTR, VariantValue::from("1.1"));
+        validate_coerce!(Currency::from(15000), VT_BSTR, VariantValue::from("1.5"));
+        validate_coerce!(Decimal::new(1500, 3), VT_BSTR, VariantValue::from("1.5"));
+    }
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_currency() {
+        validate_coerce!(1.23456f64, VT_CY, VariantValue::Cy(Currency::from(12346)));
+        validate_coerce!(7i32, VT_CY, VariantValue::Cy(Currency::from(70000)));
+        validate_coerce!(Currency::from(25000), VT_I4, VariantValue::I4(2));
+        validate_coerce!(Currency::from(15000), VT_R8, VariantValue::R8(1.5));
+        validate_overflow!(1e300f64, VT_CY);
+        validate_overflow!(i64::max_value(), VT_CY);
+        validate_coerce!(Currency::from(i64::min_value()), VT_BSTR, VariantValue::from("-922337203685477.5808"));
+    }
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_decimal() {
+        validate_coerce!(Decimal::new(25, 1), VT_I4, VariantValue::I4(2));
+        validate_coerce!(1.25f64, VT_DECIMAL, VariantValue::from(Decimal::new(125, 2)));
+        validate_coerce!(-0.001f64, VT_DECIMAL, VariantValue::from(Decimal::new(-1, 3)));
+        validate_coerce!(u64::max_value(), VT_DECIMAL, VariantValue::from(Decimal::from_str("18446744073709551615").unwrap()));
+        validate_overflow!(1e30f64, VT_DECIMAL);
+    }
+    #[test]
+    fn test_dates() {
+        validate_coerce!("1899-12-30", VT_DATE, VariantValue::Date(Date::from(0.0)));
+        validate_coerce!("2018-10-08", VT_DATE, VariantValue::Date(Date::from(43381.0)));
+        validate_coerce!("10/08/2018 18:00", VT_DATE, VariantValue::Date(Date::from(43381.75)));
+        validate_coerce!("10/8/18 6:00:00 PM", VT_DATE, VariantValue::Date(Date::from(43381.75)));
+        validate_coerce!("12:00 AM", VT_DATE, VariantValue::Date(Date::from(0.0)));
+        validate_coerce!("1899-12-29 06:00", VT_DATE, VariantValue::Date(Date::from(-1.25)));
+        validate_coerce!(Date::from(43381.75), VT_BSTR, VariantValue::from("10/08/2018 18:00:00"));
+        validate_coerce!(Date::from(43381.0), VT_BSTR, VariantValue::from("10/08/2018"));
+        validate_coerce!(Date::from(-1.25), VT_BSTR, VariantValue::from("12/29/1899 06:00:00"));
+        validate_coerce!(Date::from(0.5), VT_BSTR, VariantValue::from("12:00:00"));
+        validate_coerce!(Date::from(43381.75), VT_R8, VariantValue::R8(43381.75));
+        validate_overflow!(1e10f64, VT_DATE);
+        assert!(VariantValue::from("2018-02-30").change_type(VarType::from(VT_DATE)).is_err());
+        assert!(VariantValue::from("25:00").change_type(VarType::from(VT_DATE)).is_err());
+    }
+    #[test]
+    fn test_from_variant_coerced() {
+        let mut var = OwnedVariant::new(String::from("1,234")).unwrap();
+        let ptr = Ptr::with_checked(var.as_mut_ptr()).unwrap();
+        assert_eq!(i32::from_variant_coerced(ptr).unwrap(), 1234);
+        assert_eq!(var.vartype(), VT_BSTR);
+        assert_eq!(String::from_variant_coerced(ptr).unwrap(), "1,234");
+        assert_eq!(var.vartype(), VT_BSTR);
+        match i8::from_variant_coerced(ptr) {
+            Err(FromVariantError::ChangeTypeFailed(ChangeTypeError::Overflow{from, to})) => {
+                assert_eq!(from, VT_BSTR);
+                assert_eq!(to, VT_I1);
+            },
+            r => panic!("expected overflow, got {:?}", r)
+        }
+        // a failed conversion clears the VARIANT, coerced or not, like `from_variant`
+        assert_eq!(var.vartype(), VT_EMPTY);
+        let mut var = OwnedVariant::new(String::from("x")).unwrap();
+        let ptr = Ptr::with_checked(var.as_mut_ptr()).unwrap();
+        assert!(Vec::<i32>::from_variant_coerced(ptr).is_err());
+        assert_eq!(var.vartype(), VT_EMPTY);
+
+        let mut var = OwnedVariant::new(true).unwrap();
+        let ptr = Ptr::with_checked(var.as_mut_ptr()).unwrap();
+        assert_eq!(i16::from_variant_coerced(ptr).unwrap(), -1);
+        assert_eq!(String::from_variant_coerced(ptr).unwrap(), "True");
+
+        // arrays of VARIANTs holding strings are read as they are, without coercing
+        let mut var = OwnedVariant::new(vec![VariantValue::from("a"), VariantValue::from("b")]).unwrap();
+        assert_eq!(var.vartype(), VT_ARRAY | VT_VARIANT);
+        assert!(Vec::<String>::accepts_vartype(VT_ARRAY | VT_VARIANT));
+        assert!(!Vec::<String>::accepts_vartype(VT_ARRAY | VT_BYREF | VT_VARIANT));
+        let ptr = Ptr::with_checked(var.as_mut_ptr()).unwrap();
+        assert_eq!(Vec::<String>::from_variant_coerced(ptr).unwrap(), vec!["a", "b"]);
+        assert_eq!(var.vartype(), VT_EMPTY);
+    }
+}
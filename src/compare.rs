@@ -0,0 +1,512 @@
+//! # Comparison
+//! `VarCmp` compatible comparison of [`VariantValue`]s, and the [`VariantKey`] and
+//! [`VariantKeyIgnoreCase`] wrappers which give them `Eq`, `Ord` and `Hash` so they
+//! can be sorted, deduplicated and used as `HashMap` keys.
+//!
+//! [`VariantValue`]: enum.VariantValue.html
+//! [`VariantKey`]: struct.VariantKey.html
+//! [`VariantKeyIgnoreCase`]: struct.VariantKeyIgnoreCase.html
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use super::coerce::{to_number, to_real, Number};
+use super::errors::ChangeTypeError;
+use super::value::VariantValue;
+
+/// Result of comparing two variant values. Mirrors the `VARCMP_*` values
+/// returned by `VarCmp`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum VarCmp {
+    /// `VARCMP_LT` - the left value is less than the right value
+    Lt,
+    /// `VARCMP_EQ` - the values are equal
+    Eq,
+    /// `VARCMP_GT` - the left value is greater than the right value
+    Gt,
+    /// `VARCMP_NULL` - at least one of the values is VT_NULL
+    Null,
+}
+
+impl VarCmp {
+    /// The `VARCMP_*` constant `VarCmp` would return
+    pub fn value(self) -> u32 {
+        match self {
+            VarCmp::Lt => 0,
+            VarCmp::Eq => 1,
+            VarCmp::Gt => 2,
+            VarCmp::Null => 3,
+        }
+    }
+
+    /// Converts to an `Ordering`, `None` for `VarCmp::Null`
+    pub fn ordering(self) -> Option<Ordering> {
+        match self {
+            VarCmp::Lt => Some(Ordering::Less),
+            VarCmp::Eq => Some(Ordering::Equal),
+            VarCmp::Gt => Some(Ordering::Greater),
+            VarCmp::Null => None,
+        }
+    }
+}
+
+impl From<Ordering> for VarCmp {
+    fn from(o: Ordering) -> VarCmp {
+        match o {
+            Ordering::Less => VarCmp::Lt,
+            Ordering::Equal => VarCmp::Eq,
+            Ordering::Greater => VarCmp::Gt,
+        }
+    }
+}
+
+impl VariantValue {
+    /// Compares two values following the rules of `VarCmp`:
+    ///
+    ///   * VT_NULL on either side gives `VarCmp::Null`
+    ///   * numbers, booleans, dates, currency and decimals are compared by value across types
+    ///   * strings are compared to each other, case insensitively if `ignore_case` is set
+    ///   * a number is always less than a string
+    ///   * VT_EMPTY compares as zero or as the empty string
+    ///
+    /// Errors, interfaces, arrays, records and VT_BYREF values cannot be compared and give
+    /// `ChangeTypeError::TypeMismatch`.
+    ///
+    /// ## Example usage
+    ///
+    /// ```
+    /// extern crate oaidl;
+    ///
+    /// use oaidl::{VarCmp, VariantValue};
+    ///
+    /// fn main() {
+    ///     let cmp = VariantValue::I2(2).var_cmp(&VariantValue::R8(2.5), false).unwrap();
+    ///     assert_eq!(cmp, VarCmp::Lt);
+    ///     let cmp = VariantValue::from("ABC").var_cmp(&VariantValue::from("abc"), true).unwrap();
+    ///     assert_eq!(cmp, VarCmp::Eq);
+    ///     let cmp = VariantValue::Null.var_cmp(&VariantValue::I4(1), false).unwrap();
+    ///     assert_eq!(cmp, VarCmp::Null);
+    /// }
+    /// ```
+    #[allow(clippy::needless_borrowed_reference)]
+    pub fn var_cmp(&self, other: &VariantValue, ignore_case: bool) -> Result<VarCmp, ChangeTypeError> {
+        if rank(self) > NUMERIC_RANK + 1 || rank(other) > NUMERIC_RANK + 1 {
+            return Err(ChangeTypeError::TypeMismatch{from: self.vartype(), to: other.vartype()});
+        }
+        let ord = match (self, other) {
+            (&VariantValue::Null, _) | (_, &VariantValue::Null) => return Ok(VarCmp::Null),
+            (&VariantValue::Bstr(ref a), &VariantValue::Bstr(ref b)) => cmp_str(a, b, ignore_case),
+            (&VariantValue::Bstr(ref a), &VariantValue::Empty) => cmp_str(a, "", ignore_case),
+            (&VariantValue::Empty, &VariantValue::Bstr(ref b)) => cmp_str("", b, ignore_case),
+            (&VariantValue::Bstr(_), _) => Ordering::Greater,
+            (_, &VariantValue::Bstr(_)) => Ordering::Less,
+            _ => cmp_numbers(to_number(self).unwrap(), to_number(other).unwrap()),
+        };
+        Ok(VarCmp::from(ord))
+    }
+}
+
+const NUMERIC_RANK: u8 = 1;
+
+/// Groups vartypes in the order keys sort in
+fn rank(val: &VariantValue) -> u8 {
+    match *val {
+        VariantValue::Null => 0,
+        VariantValue::Bstr(_) => NUMERIC_RANK + 1,
+        VariantValue::Error(_) => NUMERIC_RANK + 2,
+        VariantValue::Dispatch(_) | VariantValue::Unknown(_) | VariantValue::Array{..} |
+        VariantValue::ByRef{..} | VariantValue::Record{..} => NUMERIC_RANK + 3,
+        _ => NUMERIC_RANK,
+    }
+}
+
+fn cmp_str(a: &str, b: &str, ignore_case: bool) -> Ordering {
+    if ignore_case {
+        a.chars().flat_map(char::to_lowercase).cmp(b.chars().flat_map(char::to_lowercase))
+    } else {
+        a.cmp(b)
+    }
+}
+
+/// Orders reals with NaN after every number and equal to itself
+fn cmp_reals(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+/// The real as an integer, if it holds one exactly
+fn real_as_int(r: f64) -> Option<i128> {
+    if r.fract() == 0.0 && r.abs() < 1e38 {
+        Some(r as i128)
+    } else {
+        None
+    }
+}
+
+fn int_as_decimal(i: i128) -> Option<Decimal> {
+    Decimal::from_str(&i.to_string()).ok()
+}
+
+fn decimal_as_int(d: Decimal) -> Option<i128> {
+    if d.fract() == Decimal::new(0, 0) {
+        i128::from_str(&d.trunc().to_string()).ok()
+    } else {
+        None
+    }
+}
+
+/// Number of decimal digits after the point in the exact expansion of a finite real
+fn real_frac_digits(r: f64) -> usize {
+    let bits = r.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i64;
+    let mut mantissa = bits & ((1 << 52) - 1);
+    let mut exp = if biased == 0 { -1074 } else { mantissa |= 1 << 52; biased - 1075 };
+    if mantissa == 0 {
+        return 0;
+    }
+    exp += i64::from(mantissa.trailing_zeros());
+    if exp < 0 { -exp as usize } else { 0 }
+}
+
+/// A finite number as its exact decimal expansion `0.d1d2..dn * 10^exp`, without
+/// leading or trailing zero digits. Zero has no digits.
+#[derive(Debug, Eq, Hash, PartialEq)]
+struct Exact {
+    neg: bool,
+    digits: Vec<u8>,
+    exp: i64,
+}
+
+impl Exact {
+    #[allow(clippy::redundant_field_names)]
+    fn new(num: Number) -> Exact {
+        let s = match num {
+            Number::Int(i) => i.to_string(),
+            Number::Dec(d) => d.to_string(),
+            Number::Real(r) => format!("{:.*}", real_frac_digits(r), r),
+        };
+        let neg = s.starts_with('-');
+        let s = s.trim_start_matches('-');
+        let (int, frac) = match s.find('.') {
+            Some(ix) => (&s[..ix], &s[ix + 1..]),
+            None => (s, ""),
+        };
+        let all: Vec<u8> = int.bytes().chain(frac.bytes()).map(|b| b - b'0').collect();
+        let lead = all.iter().take_while(|&&d| d == 0).count();
+        let mut digits = all[lead..].to_vec();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        if digits.is_empty() {
+            return Exact { neg: false, digits: digits, exp: 0 };
+        }
+        Exact { neg: neg, digits: digits, exp: int.len() as i64 - lead as i64 }
+    }
+}
+
+impl Ord for Exact {
+    fn cmp(&self, other: &Exact) -> Ordering {
+        if self.neg != other.neg {
+            return other.neg.cmp(&self.neg);
+        }
+        let magnitude = match (self.digits.is_empty(), other.digits.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => (self.exp, &self.digits).cmp(&(other.exp, &other.digits)),
+        };
+        if self.neg { magnitude.reverse() } else { magnitude }
+    }
+}
+
+impl PartialOrd for Exact {
+    fn partial_cmp(&self, other: &Exact) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn is_finite(num: Number) -> bool {
+    match num {
+        Number::Real(r) => r.is_finite(),
+        _ => true,
+    }
+}
+
+/// Compares exactly, so that the order is total and equal values also hash equally.
+/// Reals are compared to integers and decimals through their exact decimal expansion.
+fn cmp_numbers(a: Number, b: Number) -> Ordering {
+    let fast = match (a, b) {
+        (Number::Int(a), Number::Int(b)) => Some(a.cmp(&b)),
+        (Number::Dec(a), Number::Dec(b)) => Some(a.cmp(&b)),
+        (Number::Real(a), Number::Real(b)) => Some(cmp_reals(a, b)),
+        (Number::Int(i), Number::Dec(d)) => int_as_decimal(i).map(|i| i.cmp(&d)),
+        (Number::Int(i), Number::Real(r)) => real_as_int(r).map(|r| i.cmp(&r)),
+        (Number::Dec(d), Number::Real(r)) => real_as_int(r).and_then(int_as_decimal).map(|r| d.cmp(&r)),
+        (Number::Dec(_), Number::Int(_)) | (Number::Real(_), Number::Int(_)) | (Number::Real(_), Number::Dec(_)) => {
+            return cmp_numbers(b, a).reverse();
+        },
+    };
+    match fast {
+        Some(ord) => ord,
+        // Only a real can be infinite or NaN, and it then orders the same as in f64
+        None if !is_finite(a) || !is_finite(b) => cmp_reals(to_real(a), to_real(b)),
+        None => Exact::new(a).cmp(&Exact::new(b)),
+    }
+}
+
+/// Hashes integral values as `i128` and the other values a decimal can be equal to as
+/// their exact expansion, so that equal numbers hash equally
+#[allow(clippy::legacy_numeric_constants)]
+fn hash_number<H: Hasher>(num: Number, state: &mut H) {
+    let r = match num {
+        Number::Int(i) => return i.hash(state),
+        Number::Dec(d) => match decimal_as_int(d) {
+            Some(i) => return i.hash(state),
+            None => return Exact::new(num).hash(state),
+        },
+        Number::Real(r) => r,
+    };
+    match real_as_int(r) {
+        Some(i) => i.hash(state),
+        None if r.is_nan() => ::std::f64::NAN.to_bits().hash(state),
+        // A decimal has at most 28 digits after the point
+        None if r.is_finite() && real_frac_digits(r) <= 28 => Exact::new(num).hash(state),
+        None => r.to_bits().hash(state),
+    }
+}
+
+/// Address(es) of a pointer value, used to order and hash values that have no
+/// value semantics
+fn addresses(val: &VariantValue) -> (usize, usize) {
+    match *val {
+        VariantValue::Dispatch(p) => (p.map_or(0, |p| p.as_ptr() as usize), 0),
+        VariantValue::Unknown(p) => (p.map_or(0, |p| p.as_ptr() as usize), 0),
+        VariantValue::Array{array, ..} => (array.as_ptr() as usize, 0),
+        VariantValue::ByRef{pointer, ..} => (pointer.as_ptr() as usize, 0),
+        VariantValue::Record{record, record_info} => (record.as_ptr() as usize, record_info.as_ptr() as usize),
+        _ => (0, 0),
+    }
+}
+
+/// Total order used by the key wrappers. It agrees with `var_cmp` for numbers and
+/// strings, except that VT_EMPTY always orders as zero.
+#[allow(clippy::needless_borrowed_reference)]
+fn key_cmp(a: &VariantValue, b: &VariantValue, ignore_case: bool) -> Ordering {
+    match rank(a).cmp(&rank(b)) {
+        Ordering::Equal => {},
+        ord => return ord,
+    }
+    match (a, b) {
+        (&VariantValue::Null, &VariantValue::Null) => Ordering::Equal,
+        (&VariantValue::Bstr(ref a), &VariantValue::Bstr(ref b)) => cmp_str(a, b, ignore_case),
+        (&VariantValue::Error(a), &VariantValue::Error(b)) => i32::from(a).cmp(&i32::from(b)),
+        _ if rank(a) == NUMERIC_RANK => cmp_numbers(to_number(a).unwrap(), to_number(b).unwrap()),
+        _ => (a.vartype(), addresses(a)).cmp(&(b.vartype(), addresses(b))),
+    }
+}
+
+fn key_hash<H: Hasher>(val: &VariantValue, ignore_case: bool, state: &mut H) {
+    rank(val).hash(state);
+    match *val {
+        VariantValue::Null => {},
+        VariantValue::Bstr(ref s) if ignore_case => {
+            for c in s.chars().flat_map(char::to_lowercase) {
+                c.hash(state);
+            }
+            0xffu8.hash(state);
+        },
+        VariantValue::Bstr(ref s) => s.hash(state),
+        VariantValue::Error(e) => i32::from(e).hash(state),
+        _ if rank(val) == NUMERIC_RANK => hash_number(to_number(val).unwrap(), state),
+        _ => (val.vartype(), addresses(val)).hash(state),
+    }
+}
+
+macro_rules! variant_key_impl {
+    ($(#[$attrs:meta])* $name:ident, $ignore_case:expr) => {
+        $(#[$attrs])*
+        #[derive(Clone, Debug)]
+        pub struct $name(VariantValue);
+
+        impl $name {
+            /// default constructor
+            pub fn new(val: VariantValue) -> $name {
+                $name(val)
+            }
+
+            /// Get access to the inner value and the key is consumed
+            pub fn unwrap(self) -> VariantValue {
+                self.0
+            }
+
+            /// Borrow reference to inner value
+            #[allow(clippy::should_implement_trait)]
+            pub fn borrow(&self) -> &VariantValue {
+                &self.0
+            }
+        }
+
+        impl From<VariantValue> for $name {
+            fn from(val: VariantValue) -> $name {
+                $name(val)
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                key_cmp(&self.0, &other.0, $ignore_case) == Ordering::Equal
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &$name) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &$name) -> Ordering {
+                key_cmp(&self.0, &other.0, $ignore_case)
+            }
+        }
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                key_hash(&self.0, $ignore_case, state)
+            }
+        }
+    };
+}
+
+variant_key_impl!(
+    /// Wraps a `VariantValue` so that it can be sorted or used as a `HashMap` key.
+    ///
+    /// Values are compared as `var_cmp` compares them, so `I2(1)`, `R8(1.0)` and a
+    /// currency of 1 are the same key. To make the order total VT_NULL equals itself and
+    /// sorts first, VT_EMPTY is treated as zero, NaN equals itself and sorts after every
+    /// other number, and errors, interfaces, arrays and records come last, ordered by
+    /// vartype and address.
+    VariantKey, false
+);
+
+variant_key_impl!(
+    /// Like `VariantKey`, but strings are compared case insensitively.
+    VariantKeyIgnoreCase, true
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+    use super::super::types::{Currency, Date, SCode};
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_var_cmp() {
+        let cmp = |a: VariantValue, b: VariantValue| a.var_cmp(&b, false).unwrap();
+        assert_eq!(cmp(VariantValue::I2(1), VariantValue::I4(2)), VarCmp::Lt);
+        assert_eq!(cmp(VariantValue::I4(2), VariantValue::R8(2.0)), VarCmp::Eq);
+        assert_eq!(cmp(VariantValue::Cy(Currency::from(25000)), VariantValue::R4(2.0)), VarCmp::Gt);
+        assert_eq!(cmp(VariantValue::from(Decimal::new(25, 1)), VariantValue::Cy(Currency::from(25000))), VarCmp::Eq);
+        assert_eq!(cmp(VariantValue::Date(Date::from(43381.5)), VariantValue::I4(43381)), VarCmp::Gt);
+        assert_eq!(cmp(VariantValue::Bool(true), VariantValue::I2(0)), VarCmp::Lt);
+        assert_eq!(cmp(VariantValue::UI8(u64::max_value()), VariantValue::I8(-1)), VarCmp::Gt);
+        assert_eq!(cmp(VariantValue::Empty, VariantValue::I4(0)), VarCmp::Eq);
+        assert_eq!(cmp(VariantValue::Empty, VariantValue::from("")), VarCmp::Eq);
+        assert_eq!(cmp(VariantValue::from("a"), VariantValue::Empty), VarCmp::Gt);
+        assert_eq!(cmp(VariantValue::from("1"), VariantValue::I4(2)), VarCmp::Gt);
+        assert_eq!(cmp(VariantValue::from("abc"), VariantValue::from("abd")), VarCmp::Lt);
+        assert_eq!(cmp(VariantValue::from("ABC"), VariantValue::from("abc")), VarCmp::Lt);
+        assert_eq!(VariantValue::from("ABC").var_cmp(&VariantValue::from("abc"), true).unwrap(), VarCmp::Eq);
+        assert_eq!(cmp(VariantValue::Null, VariantValue::Null), VarCmp::Null);
+        assert_eq!(cmp(VariantValue::I4(1), VariantValue::Null), VarCmp::Null);
+        assert_eq!(VarCmp::Null.value(), 3);
+        assert!(VariantValue::Error(SCode::from(1)).var_cmp(&VariantValue::I4(1), false).is_err());
+    }
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_keys() {
+        let mut map = HashMap::new();
+        map.insert(VariantKey::new(VariantValue::I2(1)), "one");
+        map.insert(VariantKey::new(VariantValue::R8(1.0)), "real one");
+        map.insert(VariantKey::new(VariantValue::Cy(Currency::from(10000))), "currency one");
+        map.insert(VariantKey::new(VariantValue::from(Decimal::new(10, 1))), "decimal one");
+        map.insert(VariantKey::new(VariantValue::R8(0.1)), "tenth");
+        map.insert(VariantKey::new(VariantValue::from(Decimal::new(1, 1))), "decimal tenth");
+        map.insert(VariantKey::new(VariantValue::from("1")), "string");
+        map.insert(VariantKey::new(VariantValue::Null), "null");
+        map.insert(VariantKey::new(VariantValue::R8(::std::f64::NAN)), "nan");
+        map.insert(VariantKey::new(VariantValue::R4(::std::f32::NAN)), "nan");
+        map.insert(VariantKey::new(VariantValue::R8(0.5)), "half");
+        map.insert(VariantKey::new(VariantValue::from(Decimal::new(50, 2))), "decimal half");
+        assert_eq!(map.len(), 7);
+        assert_eq!(map[&VariantKey::new(VariantValue::UI8(1))], "decimal one");
+        assert_eq!(map[&VariantKey::new(VariantValue::R8(0.1))], "tenth");
+        assert_eq!(map[&VariantKey::new(VariantValue::from(Decimal::new(1, 1)))], "decimal tenth");
+        assert_eq!(map[&VariantKey::new(VariantValue::R4(0.5))], "decimal half");
+
+        let set: HashSet<_> = vec!["Abc", "aBC", "abc"].into_iter()
+            .map(|s| VariantKeyIgnoreCase::new(VariantValue::from(s)))
+            .collect();
+        assert_eq!(set.len(), 1);
+        let set: HashSet<_> = vec!["Abc", "aBC", "abc"].into_iter()
+            .map(|s| VariantKey::new(VariantValue::from(s)))
+            .collect();
+        assert_eq!(set.len(), 3);
+    }
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_exact() {
+        let tenth = VariantValue::from(Decimal::from_str("0.1").unwrap());
+        let closer = VariantValue::from(Decimal::from_str("0.1000000000000000055511151231").unwrap());
+        let real = VariantValue::R8(0.1);
+        let cmp = |a: &VariantValue, b: &VariantValue| a.var_cmp(b, false).unwrap();
+        assert_eq!(cmp(&tenth, &closer), VarCmp::Lt);
+        assert_eq!(cmp(&closer, &real), VarCmp::Lt);
+        assert_eq!(cmp(&tenth, &real), VarCmp::Lt);
+        assert_eq!(cmp(&real, &tenth), VarCmp::Gt);
+
+        let mut keys: Vec<VariantKey> = vec![real.clone(), closer.clone(), tenth.clone(), real.clone()]
+            .into_iter().map(VariantKey::from).collect();
+        keys.sort();
+        keys.dedup();
+        let sorted: Vec<VariantValue> = keys.into_iter().map(VariantKey::unwrap).collect();
+        assert_eq!(sorted, vec![tenth, closer, real]);
+
+        assert_eq!(cmp(&VariantValue::from(Decimal::new(-25, 1)), &VariantValue::R4(-2.5)), VarCmp::Eq);
+        assert_eq!(cmp(&VariantValue::from(Decimal::new(-1, 1)), &VariantValue::R8(-0.1)), VarCmp::Gt);
+        assert_eq!(cmp(&VariantValue::from(Decimal::new(1, 28)), &VariantValue::R8(1e-300)), VarCmp::Gt);
+        assert_eq!(cmp(&VariantValue::from(Decimal::new(0, 3)), &VariantValue::R8(-1e-300)), VarCmp::Gt);
+        assert_eq!(cmp(&VariantValue::from(Decimal::new(1, 1)), &VariantValue::R8(::std::f64::INFINITY)), VarCmp::Lt);
+        assert_eq!(cmp(&VariantValue::R8(1e30), &VariantValue::from(Decimal::new(15, 1))), VarCmp::Gt);
+    }
+
+    #[test]
+    fn test_key_sort() {
+        let mut keys: Vec<VariantKey> = vec![
+            VariantValue::from("b"),
+            VariantValue::R8(2.5),
+            VariantValue::Null,
+            VariantValue::Error(SCode::from(5)),
+            VariantValue::I4(-3),
+            VariantValue::from("a"),
+            VariantValue::Empty,
+            VariantValue::Bool(true),
+        ].into_iter().map(VariantKey::from).collect();
+        keys.sort();
+        let sorted: Vec<VariantValue> = keys.into_iter().map(VariantKey::unwrap).collect();
+        assert_eq!(sorted, vec![
+            VariantValue::Null,
+            VariantValue::I4(-3),
+            VariantValue::Bool(true),
+            VariantValue::Empty,
+            VariantValue::R8(2.5),
+            VariantValue::from("a"),
+            VariantValue::from("b"),
+            VariantValue::Error(SCode::from(5)),
+        ]);
+    }
+}
@@ -1,3 +1,8 @@
+//`failure`'s derive expands to impls inside a const block
+#![allow(non_local_definitions)]
+
+use super::vartype::VarType;
+
 /// Supererror type SafeArray element conversion errors
 #[derive(Debug, Fail)]
 pub enum ElementError {
@@ -30,6 +35,18 @@ pub enum FromSafeArrElemError {
     /// IDispatch pointer during conversion was null
     #[fail(display = "IDispatch pointer is null")]
     DispatchPtrNull,
+    /// The number of indices does not match the dimensions of the SAFEARRAY
+    #[fail(display = "{} indices do not match the dimensions of the SAFEARRAY", count)]
+    IndicesInvalid {
+        /// The number of indices given
+        count: usize
+    },
+    /// The BSTR element is not valid UTF-16, so it cannot be decoded into a `String`
+    #[fail(display = "BSTR holds an unpaired surrogate at index {}", index)]
+    InvalidUtf16 {
+        /// Index of the unpaired surrogate, in UTF-16 code units
+        index: usize
+    },
 }
 
 /// Errors for converting into C/C++ data structures from Rust types
@@ -45,7 +62,7 @@ pub enum IntoSafeArrElemError {
     #[fail(display = "VARIANT allocation failed for vartype: {}", vartype)]
     VariantAllocFailed{
         /// vartype that failed
-        vartype: u32
+        vartype: VarType
     },
     /// `SafeArrayPutElement` failed with `HRESULT`
     #[fail(display = "SafeArrayPutElement failed with HRESULT = 0x{}", hr)]
@@ -56,6 +73,12 @@ pub enum IntoSafeArrElemError {
     /// Encapsulates a `IntoVariantError`
     #[fail(display = "IntoVariantError: {}", _0)]
     IntoVariantError(Box<IntoVariantError>),
+    /// The number of indices does not match the dimensions of the SAFEARRAY
+    #[fail(display = "{} indices do not match the dimensions of the SAFEARRAY", count)]
+    IndicesInvalid {
+        /// The number of indices given
+        count: usize
+    },
 }
 
 impl From<FromSafeArrElemError> for ElementError {
@@ -85,7 +108,7 @@ pub enum SafeArrayError {
 #[derive(Debug, Fail)]
 pub enum FromSafeArrayError{
     /// Either the safe array dimensions = 0 or > 1
-    /// multi-dimensional arrays are *not* handled.
+    /// multi-dimensional arrays are handled by `NdSafeArray`, not `Vec`.
     #[fail(display = "Safe array dimensions are invalid: {}", sa_dims)]
     SafeArrayDimsInvalid {
         /// safe array dimensions that was wrong
@@ -95,9 +118,9 @@ pub enum FromSafeArrayError{
     #[fail(display = "expected vartype was not found - expected: {} - found: {}", expected, found)]
     VarTypeDoesNotMatch {
         /// The expected vartype
-        expected: u32, 
+        expected: VarType, 
         /// the found vartype
-        found: u32
+        found: VarType
     },
     /// Call to SafeArrayGetLBound failed
     #[fail(display = "SafeArrayGetLBound failed with HRESULT = 0x{}", hr)]
@@ -124,7 +147,24 @@ pub enum FromSafeArrayError{
         index: usize, 
         /// The element error encapsulating the failure
         element: Box<ElementError>
-    }
+    },
+    /// SAFEARRAY pointer is null
+    #[fail(display = "SAFEARRAY pointer is null")]
+    SafeArrayPtrNull,
+    /// The size of the SAFEARRAY elements differs from the Rust type viewing them
+    #[fail(display = "element size does not match - expected: {} - found: {}", expected, found)]
+    ElementSizeDoesNotMatch {
+        /// Size of the Rust type
+        expected: usize, 
+        /// `cbElements` of the SAFEARRAY
+        found: u32
+    },
+    /// Call to SafeArrayAccessData failed
+    #[fail(display = "SafeArrayAccessData failed with HRESULT = 0x{:x}", hr)]
+    SafeArrayAccessDataFailed {
+        /// HRESULT returned
+        hr: i32
+    },
 }
 
 /// Represents the different ways converting into `SAFEARRAY` can fail
@@ -141,6 +181,29 @@ pub enum IntoSafeArrayError {
     /// The called to `SafeArrayCreate` failed
     #[fail(display = "safe array creation failed")]
     SafeArrayCreateFailed,
+    /// The call to `SafeArrayAccessData` failed
+    #[fail(display = "SafeArrayAccessData failed with HRESULT = 0x{:x}", hr)]
+    SafeArrayAccessDataFailed {
+        /// Holds the HRESULT returned by SafeArrayAccessData
+        hr: i32
+    },
+    /// The array has `FADF_FIXEDSIZE` set, so it cannot be resized
+    #[fail(display = "safe array has a fixed size")]
+    SafeArrayFixedSize,
+    /// The upper bound `lbound + len - 1` of the array does not fit an `i32`
+    #[fail(display = "safe array of {} elements from index {} overflows the i32 bounds", len, lbound)]
+    BoundsOverflow {
+        /// The requested lower bound
+        lbound: i32,
+        /// The requested number of elements
+        len: usize
+    },
+    /// The call to `SafeArrayRedim` failed
+    #[fail(display = "SafeArrayRedim failed with HRESULT = 0x{:x}", hr)]
+    SafeArrayRedimFailed {
+        /// Holds the HRESULT returned by SafeArrayRedim
+        hr: i32
+    },
 }
 
 impl From<FromSafeArrayError> for SafeArrayError {
@@ -158,6 +221,7 @@ impl From<IntoSafeArrayError> for SafeArrayError {
 impl FromSafeArrayError {
     /// converts an `ElementError` into a `FromSafeArrayError`
     /// Need the index so a From impl doesn't apply
+    #[allow(clippy::redundant_field_names)]
     pub fn from_element_err<E: Into<ElementError>>(ee: E, index: usize) -> FromSafeArrayError {
         FromSafeArrayError::ElementConversionFailed{index: index, element: Box::new(ee.into())}
     }
@@ -166,12 +230,13 @@ impl FromSafeArrayError {
 impl IntoSafeArrayError {
     /// converts an `ElementError` into a `FromSafeArrayError`
     /// Need the index so a From impl doesn't apply
+    #[allow(clippy::redundant_field_names)]
     pub fn from_element_err<E: Into<ElementError>>(ee: E, index: usize) -> IntoSafeArrayError {
         IntoSafeArrayError::ElementConversionFailed{index: index, element: Box::new(ee.into())}
     }
 }
 
-/// Ways BString can fail. Currently just one way.
+/// Ways BString can fail.
 #[derive(Clone, Copy, Debug, Fail)]
 pub enum BStringError {
     /// SysAllocStringLen failed
@@ -180,12 +245,20 @@ pub enum BStringError {
         /// len which was used for allocation
         len: usize
     },    
+    /// The `OsStr` is not WTF-8, so it has no UTF-16 representation
+    #[fail(display = "OsStr is not valid WTF-8 at byte {}", index)]
+    InvalidWtf8 {
+        /// Index of the first invalid byte
+        index: usize
+    },
 }
 
 impl From<BStringError> for IntoSafeArrElemError {
+    #[allow(clippy::redundant_field_names)]
     fn from(bse: BStringError) -> IntoSafeArrElemError {
         match bse {
-            BStringError::AllocateFailed{len} =>  IntoSafeArrElemError::BStringAllocFailed{len: len}
+            BStringError::AllocateFailed{len} =>  IntoSafeArrElemError::BStringAllocFailed{len: len},
+            BStringError::InvalidWtf8{..} => IntoSafeArrElemError::from(IntoVariantError::from(bse)),
         }
     }
 }
@@ -203,9 +276,9 @@ pub enum FromVariantError {
     #[fail(display = "expected vartype was not found - expected: {} - found: {}", expected, found)]
     VarTypeDoesNotMatch {
         /// The expected vartype
-        expected: u32, 
+        expected: VarType, 
         /// the found vartype
-        found: u32
+        found: VarType
     },
     /// Encapsulates BString errors
     #[fail(display = "{}", _0)]
@@ -225,9 +298,129 @@ pub enum FromVariantError {
     /// `*mut c_void` pointer during conversion was null
     #[fail(display = "void pointer is null")]
     CVoidPtrNull,
+    /// `IRecordInfo` pointer during conversion was null
+    #[fail(display = "IRecordInfo pointer is null")]
+    RecordInfoPtrNull,
+    /// The VARIANT holds a vartype that cannot be represented
+    #[fail(display = "vartype is not supported: {}", found)]
+    VarTypeNotSupported {
+        /// the found vartype
+        found: VarType
+    },
     /// Conversion into `SAFEARRAY` failed.
     #[fail(display = "Safe array conversion failed: {}", _0)]
     SafeArrConvFailed(Box<SafeArrayError>),
+    /// Coercing the `VARIANT` to the expected vartype failed
+    #[fail(display = "{}", _0)]
+    ChangeTypeFailed(ChangeTypeError),
+    /// The BSTR is not valid UTF-16, so it cannot be decoded into a `String`
+    #[fail(display = "BSTR holds an unpaired surrogate at index {}", index)]
+    InvalidUtf16 {
+        /// Index of the unpaired surrogate, in UTF-16 code units
+        index: usize
+    },
+    /// `VariantClear` failed to release a `VARIANT`
+    #[fail(display = "VariantClear failed with HRESULT = 0x{:x}", hr)]
+    VariantClearFailed {
+        /// HRESULT returned
+        hr: i32
+    },
+}
+
+impl From<ChangeTypeError> for FromVariantError {
+    fn from(cte: ChangeTypeError) -> FromVariantError {
+        FromVariantError::ChangeTypeFailed(cte)
+    }
+}
+
+impl From<IntoVariantError> for FromVariantError {
+    #[allow(clippy::redundant_field_names)]
+    fn from(ive: IntoVariantError) -> FromVariantError {
+        match ive {
+            IntoVariantError::AllocBStrFailed(bse) => FromVariantError::AllocBStr(bse),
+            IntoVariantError::SafeArrConvFailed(sae) => FromVariantError::SafeArrConvFailed(sae),
+            IntoVariantError::VariantPtrNull => FromVariantError::VariantPtrNull,
+            IntoVariantError::VariantClearFailed{hr} => FromVariantError::VariantClearFailed{hr: hr},
+        }
+    }
+}
+
+/// Ways coercing a value to another vartype can fail. Mirrors the `HRESULT`s
+/// `VariantChangeType` returns.
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum ChangeTypeError {
+    /// The value does not fit in the target vartype - `DISP_E_OVERFLOW`
+    #[fail(display = "value of vartype {} overflows vartype {}", from, to)]
+    Overflow {
+        /// The vartype converted from
+        from: VarType,
+        /// The vartype converted to
+        to: VarType
+    },
+    /// There is no conversion between the vartypes - `DISP_E_TYPEMISMATCH`
+    #[fail(display = "vartype {} cannot be converted to vartype {}", from, to)]
+    TypeMismatch {
+        /// The vartype converted from
+        from: VarType,
+        /// The vartype converted to
+        to: VarType
+    },
+}
+
+impl ChangeTypeError {
+    /// The `HRESULT` `VariantChangeType` reports for this error
+    pub fn hresult(&self) -> i32 {
+        match *self {
+            ChangeTypeError::Overflow{..} => 0x8002000Au32 as i32,
+            ChangeTypeError::TypeMismatch{..} => 0x80020005u32 as i32,
+        }
+    }
+}
+
+/// Ways the VBScript operators on `VariantValue` can fail. Mirrors the `HRESULT`s
+/// `VarAdd` and friends return.
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum OperatorError {
+    /// An operand could not be converted to the vartype of the operation
+    #[fail(display = "{}", _0)]
+    ChangeTypeFailed(ChangeTypeError),
+    /// The result does not fit in its vartype - `DISP_E_OVERFLOW`
+    #[fail(display = "result overflows vartype {}", vartype)]
+    Overflow {
+        /// The vartype of the operation
+        vartype: VarType
+    },
+    /// Division by zero - `DISP_E_DIVBYZERO`
+    #[fail(display = "division by zero")]
+    DivideByZero,
+}
+
+impl From<ChangeTypeError> for OperatorError {
+    fn from(cte: ChangeTypeError) -> OperatorError {
+        OperatorError::ChangeTypeFailed(cte)
+    }
+}
+
+impl OperatorError {
+    /// The `HRESULT` the OleAut32 operator functions report for this error
+    pub fn hresult(&self) -> i32 {
+        match *self {
+            OperatorError::ChangeTypeFailed(cte) => cte.hresult(),
+            OperatorError::Overflow{..} => 0x8002000Au32 as i32,
+            OperatorError::DivideByZero => 0x80020012u32 as i32,
+        }
+    }
+}
+
+/// Ways parsing a `VarType` from a string can fail
+#[derive(Clone, Debug, Fail)]
+pub enum ParseVarTypeError {
+    /// A `|` separated part is neither a VARENUM name nor a number
+    #[fail(display = "unknown vartype name: {:?}", name)]
+    UnknownName {
+        /// The part that could not be parsed
+        name: String
+    },
 }
 
 /// Encapsulates errors that can occur during conversion into VARIANT
@@ -236,9 +429,18 @@ pub enum IntoVariantError {
     /// Encapsulates a `BStringError`
     #[fail(display = "{}", _0)]
     AllocBStrFailed(BStringError),
+    /// The destination `VARIANT` pointer was null
+    #[fail(display = "VARIANT pointer is null")]
+    VariantPtrNull,
     /// Encapsulates a `SafeArrayError`
     #[fail(display = "SafeArray conversion failed: {}", _0)]
     SafeArrConvFailed(Box<SafeArrayError>),
+    /// `VariantClear` could not release the previous contents of the destination
+    #[fail(display = "VariantClear failed with HRESULT = 0x{:x}", hr)]
+    VariantClearFailed {
+        /// HRESULT returned
+        hr: i32
+    },
 }
 
 impl From<IntoVariantError> for IntoSafeArrElemError {
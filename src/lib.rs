@@ -1,5 +1,4 @@
 #![cfg_attr(feature = "impl_tryfrom", feature(try_from))]
-#![cfg(windows)]
 //Enable lints for specific cases
 #![deny(future_incompatible)]
 #![deny(missing_copy_implementations)]
@@ -12,7 +11,6 @@
 #![deny(unused)]
 
 //Turn these warnings into errors
-#![deny(const_err)]
 #![deny(dead_code)]
 #![deny(deprecated)]
 #![deny(improper_ctypes)]
@@ -42,10 +40,8 @@
 //! 
 //! ```rust
 //! extern crate oaidl;
-//! extern crate winapi;
-//! 
-//! use winapi::um::oaidl::VARIANT;
 //! 
+//! use oaidl::sys::VARIANT;
 //! use oaidl::{VariantExt};
 //! 
 //! //simulate an FFI function
@@ -82,22 +78,57 @@ extern crate serde;
 
 extern crate widestring;
 
-
+#[cfg(windows)]
 extern crate winapi;
 
+mod alloc;
 mod array;
+mod bounded;
+#[macro_use]
 mod bstr;
+mod byref;
+mod coerce;
+mod compare;
 mod errors;
+mod ndarray;
 mod ptr;
+mod rust_alloc;
+mod safearray;
+pub mod sys;
+mod tracking;
 mod types;
+mod value;
 mod variant;
+mod vartype;
+mod view;
+#[cfg(unix)]
+mod wtf8;
 
-// Types = Ptr, Currency, Date, DecWrapper, Int, SCode, UInt, VariantBool, 
-//  Variant, VtEmpty, VtNull
-// Traits = BStringExt, SafeArrayElement, SafeArrayExt, VariantExt
-pub use self::array::{SafeArrayElement, SafeArrayExt};
-pub use self::bstr::{BStringExt, DroppableBString};
+// Types = AllocKind, AllocTag, AllocTracker, Allocation, BoundedVec, BStr, BString, ByRef, ByRefVariant, OleAut32, Ptr, RustAllocator, SafeArray, SafeArrayIter, SafeArrayView, SafeArrayViewMut, Currency, Date, DecWrapper, Int, NdSafeArray, SCode, UInt, VariantBool, 
+//  OwnedVariant, VarCmp, Variant, VariantKey, VariantKeyIgnoreCase, VariantRef, VariantValue, VarType, VtEmpty, VtNull
+// Traits = BStringExt, ByRefTarget, OleAllocator, SafeArrayElement, SafeArrayExt, SafeArrayPod, VariantExt
+// Macros = bstr
+pub use self::alloc::{allocator, set_allocator, OleAllocator};
+#[cfg(windows)]
+pub use self::alloc::OleAut32;
+pub use self::array::{SafeArrayElement, SafeArrayExt, SafeArrayPod};
+#[doc(hidden)]
+pub use self::array::PodElement;
+pub use self::bounded::BoundedVec;
+#[allow(deprecated)]
+pub use self::bstr::{BStr, BString, BStringExt, DroppableBString};
+#[doc(hidden)]
+pub use self::bstr::{static_bstr_len, StaticBStr};
+pub use self::byref::{ByRef, ByRefTarget, ByRefVariant};
+pub use self::compare::{VarCmp, VariantKey, VariantKeyIgnoreCase};
 pub use self::errors::*;
+pub use self::ndarray::NdSafeArray;
 pub use self::ptr::Ptr;
+pub use self::rust_alloc::RustAllocator;
+pub use self::safearray::{SafeArray, SafeArrayIter};
+pub use self::tracking::{AllocKind, AllocTag, AllocTracker, Allocation};
 pub use self::types::{Currency, Date, DecWrapper,Int, SCode, UInt, VariantBool};
-pub use self::variant::{Variant, VariantExt, VtEmpty, VtNull};
\ No newline at end of file
+pub use self::value::VariantValue;
+pub use self::variant::{OwnedVariant, Variant, VariantExt, VariantRef, VtEmpty, VtNull};
+pub use self::vartype::VarType;
+pub use self::view::{SafeArrayView, SafeArrayViewMut};
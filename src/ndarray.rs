@@ -0,0 +1,443 @@
+use std::ops::{Index, IndexMut};
+use std::ptr::null_mut;
+
+use super::alloc::allocator;
+use super::array::{check_bounds, SafeArrayDestructor, SafeArrayElement};
+use super::errors::{FromSafeArrayError, FromVariantError, IntoSafeArrayError, IntoVariantError};
+use super::ptr::Ptr;
+use super::sys::{LONG, SAFEARRAY, SAFEARRAYBOUND, VARIANT, VARTYPE, VT_ARRAY};
+use super::tracking;
+use super::variant::{array_accepts, heap_variant, safearray_variant, take_array, VariantExt};
+use super::vartype::VarType;
+
+/// SAFEARRAY of any number of dimensions, such as the 2-D arrays Excel's `Range.Value`
+/// and ADO's `GetRows` return.
+///
+/// Each dimension has a length and a lower bound, and elements are indexed with one
+/// SAFEARRAY index per dimension, first dimension first, either by slice, by array or by
+/// tuple. The elements are stored in SAFEARRAY order, which is column-major: the first
+/// index varies fastest.
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::NdSafeArray;
+///
+/// fn main() {
+///     // 2 rows and 3 columns, 1-based like Excel
+///     let mut arr = NdSafeArray::with_lbounds(vec![2, 3], vec![1, 1], vec![1, 2, 3, 4, 5, 6]).unwrap();
+///     assert_eq!(arr[(1, 1)], 1);
+///     assert_eq!(arr[(2, 1)], 2);
+///     assert_eq!(arr[(1, 2)], 3);
+///     arr[[2, 3]] = 60;
+///     assert_eq!(arr.get(&[2, 3]), Some(&60));
+///     assert_eq!(arr.get(&[0, 0]), None);
+///
+///     let psa = arr.clone().into_safearray().unwrap();
+///     assert_eq!(NdSafeArray::<i32>::from_safearray(psa.as_ptr()).unwrap(), arr);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NdSafeArray<T> {
+    shape: Vec<usize>,
+    lbounds: Vec<i32>,
+    items: Vec<T>,
+}
+
+impl<T> NdSafeArray<T> {
+    /// Creates an array with the lengths of the dimensions in `shape` and every lower bound
+    /// at 0, from `items` in column-major order. None if there are no dimensions or the
+    /// number of items does not match the shape.
+    pub fn from_shape_vec(shape: Vec<usize>, items: Vec<T>) -> Option<NdSafeArray<T>> {
+        let lbounds = vec![0; shape.len()];
+        NdSafeArray::with_lbounds(shape, lbounds, items)
+    }
+
+    /// Like [`from_shape_vec`], with a lower bound per dimension. None if there are no
+    /// dimensions, `lbounds` is not as long as `shape` or the number of items does not
+    /// match the shape.
+    ///
+    /// [`from_shape_vec`]: #method.from_shape_vec
+    #[allow(clippy::redundant_field_names)]
+    pub fn with_lbounds(shape: Vec<usize>, lbounds: Vec<i32>, items: Vec<T>) -> Option<NdSafeArray<T>> {
+        if shape.is_empty() || lbounds.len() != shape.len() {
+            return None;
+        }
+        let count = shape.iter().try_fold(1usize, |count, &len| count.checked_mul(len));
+        if count != Some(items.len()) {
+            return None;
+        }
+        Some(NdSafeArray { shape: shape, lbounds: lbounds, items: items })
+    }
+
+    /// Length of each dimension, first dimension first
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Lower bound of each dimension, first dimension first
+    pub fn lbounds(&self) -> &[i32] {
+        &self.lbounds
+    }
+
+    /// Number of dimensions
+    pub fn ndim(&self) -> usize {
+        self.shape.len()
+    }
+
+    /// Number of elements over all dimensions
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether any dimension has a length of 0
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The elements in column-major order
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+
+    /// The elements in column-major order
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.items
+    }
+
+    /// Unwraps the elements in column-major order
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+
+    /// The element at the SAFEARRAY indices `ixs`, None if there is not one index per
+    /// dimension or an index is out of bounds
+    pub fn get(&self, ixs: &[i32]) -> Option<&T> {
+        self.offset(ixs).map(|ix| &self.items[ix])
+    }
+
+    /// The element at the SAFEARRAY indices `ixs`, None if there is not one index per
+    /// dimension or an index is out of bounds
+    pub fn get_mut(&mut self, ixs: &[i32]) -> Option<&mut T> {
+        match self.offset(ixs) {
+            Some(ix) => Some(&mut self.items[ix]),
+            None => None
+        }
+    }
+
+    /// Position of the element at `ixs` in `items`
+    fn offset(&self, ixs: &[i32]) -> Option<usize> {
+        if ixs.len() != self.shape.len() {
+            return None;
+        }
+        let mut offset = 0;
+        let mut stride = 1;
+        for ((&ix, &lbound), &len) in ixs.iter().zip(&self.lbounds).zip(&self.shape) {
+            let pos = i64::from(ix) - i64::from(lbound);
+            if pos < 0 || pos >= len as i64 {
+                return None;
+            }
+            offset += pos as usize * stride;
+            stride *= len;
+        }
+        Some(offset)
+    }
+
+    /// The SAFEARRAY indices of every element, in column-major order
+    fn indices(&self) -> Indices {
+        Indices::new(&self.shape, &self.lbounds)
+    }
+}
+
+/// Steps through SAFEARRAY indices in column-major order
+struct Indices {
+    shape: Vec<usize>,
+    lbounds: Vec<i32>,
+    current: Vec<i32>,
+    started: bool,
+    remaining: usize,
+}
+
+impl Indices {
+    fn new(shape: &[usize], lbounds: &[i32]) -> Indices {
+        Indices {
+            shape: shape.to_vec(),
+            lbounds: lbounds.to_vec(),
+            current: lbounds.to_vec(),
+            started: false,
+            remaining: shape.iter().product(),
+        }
+    }
+
+    /// The next indices, first dimension first
+    fn next(&mut self) -> Option<&[i32]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if self.started {
+            for dim in 0..self.shape.len() {
+                if (i64::from(self.current[dim]) - i64::from(self.lbounds[dim]) + 1) < self.shape[dim] as i64 {
+                    self.current[dim] += 1;
+                    break;
+                }
+                self.current[dim] = self.lbounds[dim];
+            }
+        }
+        self.started = true;
+        self.remaining -= 1;
+        Some(&self.current)
+    }
+}
+
+impl<T: SafeArrayElement> NdSafeArray<T> {
+    /// Creates a SAFEARRAY with the shape and lower bounds of the array and puts every
+    /// element into it. Fails with `IntoSafeArrayError::BoundsOverflow` if the upper bound
+    /// of a dimension does not fit an `i32`.
+    pub fn into_safearray(self) -> Result<Ptr<SAFEARRAY>, IntoSafeArrayError> {
+        let _tag = tracking::tag::<Self>("into_safearray");
+        let mut bounds = self.shape.iter().zip(&self.lbounds)
+            .map(|(&len, &lbound)| Ok(SAFEARRAYBOUND { cElements: check_bounds(lbound, len)?, lLbound: lbound }))
+            .collect::<Result<Vec<SAFEARRAYBOUND>, IntoSafeArrayError>>()?;
+        let psa = unsafe { allocator().safe_array_create(T::SFTYPE as u16, bounds.len() as u32, bounds.as_mut_ptr()) };
+        if psa.is_null() {
+            return Err(IntoSafeArrayError::SafeArrayCreateFailed);
+        }
+        let mut sad = SafeArrayDestructor::new(psa);
+
+        let mut indices = self.indices();
+        for (ix, elem) in self.items.into_iter().enumerate() {
+            let ixs = indices.next().unwrap();
+            if let Err(e) = elem.into_safearray_at(psa, ixs) {
+                return Err(IntoSafeArrayError::from_element_err(e, ix));
+            }
+        }
+        sad.inner = null_mut();
+
+        Ok(Ptr::with_checked(psa).unwrap())
+    }
+
+    /// Reads every element of a SAFEARRAY of any number of dimensions, then destroys it
+    /// like [`SafeArrayExt::from_safearray`].
+    ///
+    /// [`SafeArrayExt::from_safearray`]: trait.SafeArrayExt.html#tymethod.from_safearray
+    #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::redundant_field_names)]
+    pub fn from_safearray(psa: *mut SAFEARRAY) -> Result<NdSafeArray<T>, FromSafeArrayError> {
+        let _tag = tracking::tag::<Self>("from_safearray");
+        let _sad = SafeArrayDestructor::new(psa);
+        let sa_dims = unsafe { allocator().safe_array_get_dim(psa) };
+        if sa_dims == 0 {
+            return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+        }
+        let mut vt: VARTYPE = 0;
+        let hr = unsafe { allocator().safe_array_get_vartype(psa, &mut vt) };
+        if hr != 0 {
+            return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr});
+        }
+        if !T::accepts_vartype(u32::from(vt)) {
+            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: VarType::from(T::SFTYPE), found: VarType::from(u32::from(vt))});
+        }
+
+        let mut shape = Vec::with_capacity(sa_dims as usize);
+        let mut lbounds = Vec::with_capacity(sa_dims as usize);
+        for dim in 1..=sa_dims {
+            let mut l_bound: LONG = 0;
+            let mut r_bound: LONG = 0;
+            let hr = unsafe { allocator().safe_array_get_lbound(psa, dim, &mut l_bound) };
+            if hr != 0 {
+                return Err(FromSafeArrayError::SafeArrayLBoundFailed{hr: hr});
+            }
+            let hr = unsafe { allocator().safe_array_get_ubound(psa, dim, &mut r_bound) };
+            if hr != 0 {
+                return Err(FromSafeArrayError::SafeArrayRBoundFailed{hr: hr});
+            }
+            shape.push((i64::from(r_bound) - i64::from(l_bound) + 1) as usize);
+            lbounds.push(l_bound);
+        }
+
+        let mut indices = Indices::new(&shape, &lbounds);
+        let mut items = Vec::with_capacity(indices.remaining);
+        while let Some(ixs) = indices.next() {
+            match T::from_safearray_at(psa, ixs) {
+                Ok(val) => items.push(val),
+                Err(e) => return Err(FromSafeArrayError::from_element_err(e, items.len()))
+            }
+        }
+        Ok(NdSafeArray { shape: shape, lbounds: lbounds, items: items })
+    }
+}
+
+#[allow(clippy::needless_lifetimes)]
+impl<'a, T> Index<&'a [i32]> for NdSafeArray<T> {
+    type Output = T;
+    /// ## Panics
+    /// If there is not one index per dimension or an index is out of bounds.
+    fn index(&self, ixs: &[i32]) -> &T {
+        match self.offset(ixs) {
+            Some(ix) => &self.items[ix],
+            None => panic!("indices {:?} out of bounds of an array with shape {:?} and lower bounds {:?}", ixs, self.shape, self.lbounds)
+        }
+    }
+}
+
+#[allow(clippy::needless_lifetimes)]
+impl<'a, T> IndexMut<&'a [i32]> for NdSafeArray<T> {
+    fn index_mut(&mut self, ixs: &[i32]) -> &mut T {
+        match self.offset(ixs) {
+            Some(ix) => &mut self.items[ix],
+            None => panic!("indices {:?} out of bounds of an array with shape {:?} and lower bounds {:?}", ixs, self.shape, self.lbounds)
+        }
+    }
+}
+
+impl<T, const N: usize> Index<[i32; N]> for NdSafeArray<T> {
+    type Output = T;
+    fn index(&self, ixs: [i32; N]) -> &T {
+        &self[&ixs[..]]
+    }
+}
+
+impl<T, const N: usize> IndexMut<[i32; N]> for NdSafeArray<T> {
+    fn index_mut(&mut self, ixs: [i32; N]) -> &mut T {
+        &mut self[&ixs[..]]
+    }
+}
+
+macro_rules! tuple_index_impl {
+    ($(($($ix:ident),+)),+) => {
+        $(
+            impl<T> Index<($(tuple_index_impl!(@i32 $ix),)+)> for NdSafeArray<T> {
+                type Output = T;
+                fn index(&self, ($($ix,)+): ($(tuple_index_impl!(@i32 $ix),)+)) -> &T {
+                    &self[&[$($ix),+][..]]
+                }
+            }
+
+            impl<T> IndexMut<($(tuple_index_impl!(@i32 $ix),)+)> for NdSafeArray<T> {
+                fn index_mut(&mut self, ($($ix,)+): ($(tuple_index_impl!(@i32 $ix),)+)) -> &mut T {
+                    &mut self[&[$($ix),+][..]]
+                }
+            }
+        )+
+    };
+    (@i32 $ix:ident) => { i32 };
+}
+tuple_index_impl!{(a, b), (a, b, c), (a, b, c, d)}
+
+impl<T: SafeArrayElement> VariantExt for NdSafeArray<T> {
+    const VARTYPE: u32 = VT_ARRAY | T::SFTYPE;
+    fn accepts_vartype(vt: u32) -> bool {
+        array_accepts::<T>(vt)
+    }
+
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let _tag = tracking::tag::<Self>("from_variant");
+        let psa = take_array::<T>(var)?;
+        Ok(NdSafeArray::from_safearray(psa)?)
+    }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        Ok(heap_variant(self.into_raw_variant()?))
+    }
+
+    fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        let psa = self.into_safearray()?;
+        Ok(safearray_variant(psa, Self::VARTYPE))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::sys::{VT_BSTR, VT_I4, VT_R8};
+    use super::super::tracking::AllocTracker;
+    use super::super::variant::OwnedVariant;
+
+    #[test]
+    fn test_column_major() {
+        let arr = NdSafeArray::from_shape_vec(vec![2, 3], (0..6).collect::<Vec<i32>>()).unwrap();
+        assert_eq!(arr.ndim(), 2);
+        assert_eq!(arr[(0, 0)], 0);
+        assert_eq!(arr[(1, 0)], 1);
+        assert_eq!(arr[(0, 1)], 2);
+        assert_eq!(arr[(1, 2)], 5);
+        assert_eq!(arr.get(&[2, 0]), None);
+        assert_eq!(arr.get(&[0]), None);
+
+        let psa = arr.clone().into_safearray().unwrap().as_ptr();
+        for &(row, col) in &[(0, 0), (1, 0), (0, 1), (1, 2)] {
+            let val = i32::from_safearray_at(psa, &[row, col]).unwrap();
+            assert_eq!(val, arr[(row, col)]);
+        }
+        assert!(i32::from_safearray(psa, 0).is_err());
+        assert_eq!(NdSafeArray::<i32>::from_safearray(psa).unwrap(), arr);
+    }
+
+    #[test]
+    fn test_lbounds() {
+        let items: Vec<String> = (0..24).map(|n| n.to_string()).collect();
+        let arr = NdSafeArray::with_lbounds(vec![2, 3, 4], vec![1, -1, 5], items).unwrap();
+        assert_eq!(arr[[2, -1, 5]], "1");
+        assert_eq!(arr[[1, 0, 5]], "2");
+        assert_eq!(arr[[1, -1, 6]], "6");
+        assert_eq!(arr[[2, 1, 8]], "23");
+
+        let var = arr.clone().into_variant().unwrap();
+        assert_eq!(NdSafeArray::<String>::from_variant(var).unwrap(), arr);
+        drop(unsafe { OwnedVariant::from_raw(var) });
+    }
+
+    #[test]
+    fn test_owned_variant() {
+        let tracker = AllocTracker::start();
+        {
+            let arr = NdSafeArray::from_shape_vec(vec![2, 2], vec![1i32, 2, 3, 4]).unwrap();
+            let var = OwnedVariant::new(arr).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_I4);
+            let arr = NdSafeArray::from_shape_vec(vec![1, 2], vec![String::from("a"), String::from("b")]).unwrap();
+            let var = OwnedVariant::new(arr).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_BSTR);
+        }
+        tracker.assert_no_leaks();
+
+        let var = NdSafeArray::from_shape_vec(vec![2, 1], vec![1i32, 2]).unwrap().into_variant().unwrap();
+        match NdSafeArray::<f64>::from_variant(var) {
+            Err(FromVariantError::VarTypeDoesNotMatch{expected, found}) => {
+                assert_eq!(expected, VT_ARRAY | VT_R8);
+                assert_eq!(found, VT_ARRAY | VT_I4);
+            },
+            r => panic!("unexpected result: {:?}", r)
+        }
+        drop(unsafe { OwnedVariant::from_raw(var) });
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_bounds_overflow() {
+        let max = i32::max_value();
+        let arr = NdSafeArray::with_lbounds(vec![1, 2], vec![max, max - 1], vec![1i32, 2]).unwrap();
+        let psa = arr.clone().into_safearray().unwrap();
+        assert_eq!(NdSafeArray::<i32>::from_safearray(psa.as_ptr()).unwrap(), arr);
+
+        let arr = NdSafeArray::with_lbounds(vec![1, 2], vec![0, max], vec![1i32, 2]).unwrap();
+        assert_eq!(arr[[0, max]], 1);
+        match arr.into_safearray() {
+            Err(IntoSafeArrayError::BoundsOverflow{lbound, len}) => assert_eq!((lbound, len), (max, 2)),
+            r => panic!("unexpected result: {:?}", r)
+        }
+    }
+
+    #[test]
+    fn test_shape_mismatch() {
+        assert!(NdSafeArray::from_shape_vec(vec![2, 2], vec![1, 2, 3]).is_none());
+        assert!(NdSafeArray::<i32>::from_shape_vec(vec![], vec![]).is_none());
+        assert!(NdSafeArray::with_lbounds(vec![1], vec![0, 0], vec![1]).is_none());
+        let empty = NdSafeArray::<i32>::from_shape_vec(vec![3, 0], vec![]).unwrap();
+        assert!(empty.is_empty());
+        let psa = empty.clone().into_safearray().unwrap();
+        assert_eq!(NdSafeArray::<i32>::from_safearray(psa.as_ptr()).unwrap(), empty);
+    }
+}
@@ -1,17 +1,48 @@
+use std::cmp::Ordering;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ptr::NonNull;
 
 /// Convenience type for holding value of `*mut T`
 /// Mostly just a projection of `NonNull<T>` functionality
-#[derive(Debug, Eq, Hash, PartialOrd, PartialEq)]
+/// 
+/// Like `NonNull<T>`, comparisons, hashing and `Debug` work on the pointer 
+/// address, so they do not require anything of `T`. This matters for COM 
+/// interface types like `IUnknown`, which implement none of these traits.
 pub struct Ptr<T> {
     inner: NonNull<T>
 }
 
-impl<T: Copy> Copy for Ptr<T> {}
-impl<T: Clone> Clone for Ptr<T> {
+impl<T> Copy for Ptr<T> {}
+#[allow(clippy::non_canonical_clone_impl)]
+impl<T> Clone for Ptr<T> {
     fn clone(&self) -> Self {
-        Ptr {inner: self.inner.clone()}
+        Ptr {inner: self.inner}
+    }
+}
+
+impl<T> fmt::Debug for Ptr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Ptr").field("inner", &self.inner).finish()
+    }
+}
+
+impl<T> PartialEq for Ptr<T> {
+    fn eq(&self, other: &Ptr<T>) -> bool {
+        self.inner == other.inner
+    }
+}
+impl<T> Eq for Ptr<T> {}
+
+impl<T> PartialOrd for Ptr<T> {
+    fn partial_cmp(&self, other: &Ptr<T>) -> Option<Ordering> {
+        self.inner.partial_cmp(&other.inner)
+    }
+}
+
+impl<T> Hash for Ptr<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state)
     }
 }
 
@@ -23,6 +54,7 @@ impl<T> Ptr<T> {
     }
 
     /// Checks a `*mut T` for null and wraps it up for easier handling.
+    #[allow(clippy::manual_map)]
     pub fn with_checked(p: *mut T) -> Option<Ptr<T>> {
         match NonNull::new(p) {
             Some(p) => Some(Ptr::new(p)),
@@ -66,6 +98,7 @@ impl<T> From<NonNull<T>> for Ptr<T> {
     }
 }
 
+#[allow(clippy::from_over_into)]
 impl<T> Into<NonNull<T>> for Ptr<T> {
     fn into(self) -> NonNull<T> {
         self.inner
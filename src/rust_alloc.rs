@@ -0,0 +1,988 @@
+//! # Rust allocator
+//! A pure Rust [`OleAllocator`] reproducing the OleAut32 memory layouts on top of the
+//! global Rust allocator.
+//!
+//! * A BSTR is allocated as a 4 byte length prefix (the length in bytes), the characters
+//!   and a terminating null character. The BSTR points just past the prefix.
+//! * A SAFEARRAY descriptor is a `SAFEARRAY` followed by the remaining `rgsabound`
+//!   entries, stored last dimension first, and preceded by 16 hidden bytes holding the
+//!   element VARTYPE (`FADF_HAVEVARTYPE`), the interface IID (`FADF_HAVEIID`) or the
+//!   `IRecordInfo` (`FADF_RECORD`). The elements live in a separate block at `pvData`,
+//!   first dimension varying fastest.
+//!
+//! Element and VARIANT copies follow the OleAut32 rules: BSTRs are duplicated,
+//! interfaces are `AddRef`ed and arrays are copied deeply.
+//!
+//! [`OleAllocator`]: trait.OleAllocator.html
+use std::alloc::{alloc, alloc_zeroed, dealloc, Layout};
+use std::cmp;
+use std::mem;
+use std::ptr;
+
+use super::alloc::OleAllocator;
+use super::sys::{
+    BSTR,
+    FADF_AUTO,
+    FADF_BSTR,
+    FADF_DISPATCH,
+    FADF_EMBEDDED,
+    FADF_FIXEDSIZE,
+    FADF_HAVEIID,
+    FADF_HAVEVARTYPE,
+    FADF_RECORD,
+    FADF_STATIC,
+    FADF_UNKNOWN,
+    FADF_VARIANT,
+    GUID,
+    HRESULT,
+    IRecordInfo,
+    IUnknown,
+    LONG,
+    OLECHAR,
+    SAFEARRAY,
+    SAFEARRAYBOUND,
+    UINT,
+    VARIANT,
+    VARTYPE,
+    VT_ARRAY,
+    VT_BOOL,
+    VT_BSTR,
+    VT_BYREF,
+    VT_CLSID,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_DISPATCH,
+    VT_EMPTY,
+    VT_ERROR,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_NULL,
+    VT_R4,
+    VT_R8,
+    VT_RECORD,
+    VT_RESERVED,
+    VT_SAFEARRAY,
+    VT_TYPEMASK,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+    VT_UNKNOWN,
+    VT_VARIANT,
+    VT_VECTOR,
+    VT_VOID,
+    c_void,
+};
+
+const S_OK: HRESULT = 0;
+const E_INVALIDARG: HRESULT = 0x8007_0057u32 as HRESULT;
+const E_OUTOFMEMORY: HRESULT = 0x8007_000Eu32 as HRESULT;
+const E_UNEXPECTED: HRESULT = 0x8000_FFFFu32 as HRESULT;
+const DISP_E_BADVARTYPE: HRESULT = 0x8002_0008u32 as HRESULT;
+const DISP_E_BADINDEX: HRESULT = 0x8002_000Bu32 as HRESULT;
+const DISP_E_ARRAYISLOCKED: HRESULT = 0x8002_000Du32 as HRESULT;
+
+/// Size of the BSTR length prefix
+const BSTR_PREFIX: usize = 4;
+/// Size of the hidden area in front of a SAFEARRAY descriptor
+const SA_HIDDEN: usize = 16;
+/// Alignment of SAFEARRAY data blocks, enough for every element type
+const SA_DATA_ALIGN: usize = 8;
+/// Descriptors and data of arrays with these features are not owned by the allocator
+const FADF_NOT_OWNED: u32 = FADF_AUTO | FADF_STATIC | FADF_EMBEDDED;
+
+const IID_IUNKNOWN: GUID = GUID {
+    Data1: 0x0000_0000, Data2: 0x0000, Data3: 0x0000,
+    Data4: [0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+const IID_IDISPATCH: GUID = GUID {
+    Data1: 0x0002_0400, Data2: 0x0000, Data3: 0x0000,
+    Data4: [0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// Pure Rust backend reproducing the OleAut32 memory layouts.
+///
+/// This is the default allocator everywhere except Windows. On Windows it can be installed
+/// with [`set_allocator`], as long as no memory it allocates is handed to code that frees
+/// it through OleAut32 (and vice versa).
+///
+/// [`set_allocator`]: fn.set_allocator.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RustAllocator;
+
+unsafe impl OleAllocator for RustAllocator {
+    unsafe fn sys_alloc_string_len(&self, psz: *const OLECHAR, len: UINT) -> BSTR {
+        match len.checked_mul(2) {
+            Some(bytes) => bstr_alloc(psz as *const u8, bytes),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn sys_free_string(&self, bstr: BSTR) {
+        if bstr.is_null() {
+            return;
+        }
+        let bytes = bstr_byte_len(bstr);
+        let base = (bstr as *mut u8).sub(BSTR_PREFIX);
+        dealloc(base, bstr_layout(bytes).unwrap());
+    }
+
+    unsafe fn sys_string_len(&self, bstr: BSTR) -> UINT {
+        if bstr.is_null() {
+            return 0;
+        }
+        bstr_byte_len(bstr) / 2
+    }
+
+    unsafe fn sys_alloc_string_byte_len(&self, psz: *const u8, len: UINT) -> BSTR {
+        bstr_alloc(psz, len)
+    }
+
+    unsafe fn sys_string_byte_len(&self, bstr: BSTR) -> UINT {
+        if bstr.is_null() {
+            return 0;
+        }
+        bstr_byte_len(bstr)
+    }
+
+    unsafe fn safe_array_create(&self, vt: VARTYPE, c_dims: UINT, rgsabound: *mut SAFEARRAYBOUND) -> *mut SAFEARRAY {
+        if rgsabound.is_null() || c_dims == 0 || c_dims > 0xffff {
+            return ptr::null_mut();
+        }
+        let cb = match element_size(vt) {
+            Some(cb) => cb,
+            None => return ptr::null_mut(),
+        };
+        let psa = descriptor_alloc(c_dims as usize);
+        if psa.is_null() {
+            return ptr::null_mut();
+        }
+        (*psa).cDims = c_dims as u16;
+        (*psa).cbElements = cb;
+        let bounds = bounds_ptr(psa);
+        for dim in 0..c_dims as usize {
+            *bounds.add(c_dims as usize - 1 - dim) = *rgsabound.add(dim);
+        }
+
+        let vt = u32::from(vt);
+        let features = match vt {
+            VT_BSTR => FADF_HAVEVARTYPE | FADF_BSTR,
+            VT_UNKNOWN => FADF_HAVEIID | FADF_UNKNOWN,
+            VT_DISPATCH => FADF_HAVEIID | FADF_DISPATCH,
+            VT_VARIANT => FADF_HAVEVARTYPE | FADF_VARIANT,
+            _ => FADF_HAVEVARTYPE,
+        };
+        (*psa).fFeatures = features as u16;
+        match vt {
+            VT_UNKNOWN => *hidden_iid(psa) = IID_IUNKNOWN,
+            VT_DISPATCH => *hidden_iid(psa) = IID_IDISPATCH,
+            _ => *hidden_vartype(psa) = vt,
+        }
+
+        if data_alloc(psa).is_err() {
+            descriptor_free(psa);
+            return ptr::null_mut();
+        }
+        psa
+    }
+
+    unsafe fn safe_array_destroy(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        if psa.is_null() {
+            return S_OK;
+        }
+        if (*psa).cLocks > 0 {
+            return DISP_E_ARRAYISLOCKED;
+        }
+        let features = u32::from((*psa).fFeatures);
+        if !(*psa).pvData.is_null() {
+            release_elements(self, psa);
+            if features & FADF_STATIC != 0 {
+                ptr::write_bytes((*psa).pvData as *mut u8, 0, data_size(psa).unwrap_or(0));
+            } else if features & FADF_NOT_OWNED == 0 {
+                data_free(psa);
+            }
+        }
+        if features & FADF_RECORD != 0 {
+            let recinfo = *hidden_recinfo(psa);
+            if !recinfo.is_null() {
+                (*recinfo).Release();
+            }
+        }
+        if features & FADF_NOT_OWNED == 0 {
+            descriptor_free(psa);
+        }
+        S_OK
+    }
+
+    unsafe fn safe_array_get_dim(&self, psa: *mut SAFEARRAY) -> UINT {
+        if psa.is_null() {
+            return 0;
+        }
+        UINT::from((*psa).cDims)
+    }
+
+    unsafe fn safe_array_get_elemsize(&self, psa: *mut SAFEARRAY) -> UINT {
+        if psa.is_null() {
+            return 0;
+        }
+        (*psa).cbElements
+    }
+
+    unsafe fn safe_array_get_lbound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_lbound: *mut LONG) -> HRESULT {
+        match bound(psa, n_dim, pl_lbound) {
+            Ok(b) => {
+                *pl_lbound = b.lLbound;
+                S_OK
+            },
+            Err(hr) => hr,
+        }
+    }
+
+    unsafe fn safe_array_get_ubound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_ubound: *mut LONG) -> HRESULT {
+        match bound(psa, n_dim, pl_ubound) {
+            Ok(b) => {
+                *pl_ubound = b.lLbound.wrapping_add(b.cElements as LONG).wrapping_sub(1);
+                S_OK
+            },
+            Err(hr) => hr,
+        }
+    }
+
+    unsafe fn safe_array_get_vartype(&self, psa: *mut SAFEARRAY, pvt: *mut VARTYPE) -> HRESULT {
+        if psa.is_null() || pvt.is_null() {
+            return E_INVALIDARG;
+        }
+        let features = u32::from((*psa).fFeatures);
+        *pvt = if features & FADF_RECORD != 0 {
+            VT_RECORD as VARTYPE
+        } else if features & (FADF_HAVEIID | FADF_DISPATCH) == FADF_HAVEIID | FADF_DISPATCH {
+            VT_DISPATCH as VARTYPE
+        } else if features & FADF_HAVEIID != 0 {
+            VT_UNKNOWN as VARTYPE
+        } else if features & FADF_HAVEVARTYPE != 0 {
+            *hidden_vartype(psa) as VARTYPE
+        } else {
+            return E_INVALIDARG;
+        };
+        S_OK
+    }
+
+    unsafe fn safe_array_lock(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        if psa.is_null() {
+            return E_INVALIDARG;
+        }
+        if (*psa).cLocks >= 0xffff {
+            return E_UNEXPECTED;
+        }
+        (*psa).cLocks += 1;
+        S_OK
+    }
+
+    unsafe fn safe_array_unlock(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        if psa.is_null() {
+            return E_INVALIDARG;
+        }
+        if (*psa).cLocks == 0 {
+            return E_UNEXPECTED;
+        }
+        (*psa).cLocks -= 1;
+        S_OK
+    }
+
+    unsafe fn safe_array_access_data(&self, psa: *mut SAFEARRAY, ppv_data: *mut *mut c_void) -> HRESULT {
+        if ppv_data.is_null() {
+            return E_INVALIDARG;
+        }
+        let hr = self.safe_array_lock(psa);
+        if hr == S_OK {
+            *ppv_data = (*psa).pvData;
+        }
+        hr
+    }
+
+    unsafe fn safe_array_unaccess_data(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        self.safe_array_unlock(psa)
+    }
+
+    unsafe fn safe_array_redim(&self, psa: *mut SAFEARRAY, psabound_new: *mut SAFEARRAYBOUND) -> HRESULT {
+        if psa.is_null() || psabound_new.is_null() || (*psa).cDims == 0 {
+            return E_INVALIDARG;
+        }
+        let features = u32::from((*psa).fFeatures);
+        if features & (FADF_FIXEDSIZE | FADF_NOT_OWNED) != 0 {
+            return E_INVALIDARG;
+        }
+        if (*psa).cLocks > 0 {
+            return DISP_E_ARRAYISLOCKED;
+        }
+        // The last dimension comes first in the descriptor and varies slowest in the data,
+        // so its cells are at the end
+        let bounds = bounds_ptr(psa);
+        let inner = (1..(*psa).cDims as usize).try_fold(1usize, |count, dim| count.checked_mul((*bounds.add(dim)).cElements as usize));
+        let old_count = cell_count(psa).unwrap_or(0);
+        let new_count = match inner.and_then(|inner| inner.checked_mul((*psabound_new).cElements as usize)) {
+            Some(count) => count,
+            None => return E_OUTOFMEMORY,
+        };
+        let cb = (*psa).cbElements as usize;
+        let layout = match new_count.checked_mul(cb).and_then(data_layout) {
+            Some(layout) => layout,
+            None => return E_OUTOFMEMORY,
+        };
+        let data = alloc_zeroed(layout);
+        if data.is_null() {
+            return E_OUTOFMEMORY;
+        }
+        if !(*psa).pvData.is_null() {
+            if new_count < old_count {
+                release_cells(self, psa, new_count, old_count);
+            }
+            ptr::copy_nonoverlapping((*psa).pvData as *const u8, data, cmp::min(old_count, new_count) * cb);
+            data_free(psa);
+        }
+        (*psa).pvData = data as *mut c_void;
+        *bounds = *psabound_new;
+        S_OK
+    }
+
+    unsafe fn safe_array_get_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT {
+        if psa.is_null() || rg_indices.is_null() || pv.is_null() {
+            return E_INVALIDARG;
+        }
+        let elem = match element_ptr(psa, rg_indices) {
+            Some(elem) => elem,
+            None => return DISP_E_BADINDEX,
+        };
+        let features = u32::from((*psa).fFeatures);
+        (*psa).cLocks += 1;
+        let hr = if features & FADF_VARIANT != 0 {
+            variant_copy(self, pv as *mut VARIANT, elem as *const VARIANT)
+        } else if features & FADF_BSTR != 0 {
+            match bstr_copy(*(elem as *const BSTR)) {
+                Ok(bstr) => {
+                    *(pv as *mut BSTR) = bstr;
+                    S_OK
+                },
+                Err(hr) => hr,
+            }
+        } else if features & (FADF_UNKNOWN | FADF_DISPATCH) != 0 {
+            let punk = *(elem as *const *mut IUnknown);
+            if !punk.is_null() {
+                (*punk).AddRef();
+            }
+            *(pv as *mut *mut IUnknown) = punk;
+            S_OK
+        } else {
+            ptr::copy_nonoverlapping(elem as *const u8, pv as *mut u8, (*psa).cbElements as usize);
+            S_OK
+        };
+        (*psa).cLocks -= 1;
+        hr
+    }
+
+    unsafe fn safe_array_put_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT {
+        if psa.is_null() || rg_indices.is_null() {
+            return E_INVALIDARG;
+        }
+        let elem = match element_ptr(psa, rg_indices) {
+            Some(elem) => elem,
+            None => return DISP_E_BADINDEX,
+        };
+        let features = u32::from((*psa).fFeatures);
+        if pv.is_null() && features & (FADF_BSTR | FADF_UNKNOWN | FADF_DISPATCH) == 0 {
+            return E_INVALIDARG;
+        }
+        (*psa).cLocks += 1;
+        let hr = if features & FADF_VARIANT != 0 {
+            variant_copy(self, elem as *mut VARIANT, pv as *const VARIANT)
+        } else if features & FADF_BSTR != 0 {
+            match bstr_copy(pv as BSTR) {
+                Ok(bstr) => {
+                    self.sys_free_string(*(elem as *const BSTR));
+                    *(elem as *mut BSTR) = bstr;
+                    S_OK
+                },
+                Err(hr) => hr,
+            }
+        } else if features & (FADF_UNKNOWN | FADF_DISPATCH) != 0 {
+            let punk = pv as *mut IUnknown;
+            if !punk.is_null() {
+                (*punk).AddRef();
+            }
+            let old = *(elem as *const *mut IUnknown);
+            if !old.is_null() {
+                (*old).Release();
+            }
+            *(elem as *mut *mut IUnknown) = punk;
+            S_OK
+        } else {
+            ptr::copy_nonoverlapping(pv as *const u8, elem, (*psa).cbElements as usize);
+            S_OK
+        };
+        (*psa).cLocks -= 1;
+        hr
+    }
+
+    unsafe fn variant_clear(&self, pvarg: *mut VARIANT) -> HRESULT {
+        if pvarg.is_null() {
+            return E_INVALIDARG;
+        }
+        let n2 = (*pvarg).n1.n2_mut();
+        let vt = u32::from(n2.vt);
+        let hr = validate_vartype(vt);
+        if hr != S_OK {
+            return hr;
+        }
+        let mut hr = S_OK;
+        if vt & VT_BYREF == 0 {
+            if vt & VT_ARRAY != 0 || vt == VT_SAFEARRAY {
+                hr = self.safe_array_destroy(*n2.n3.parray());
+            } else {
+                match vt {
+                    VT_BSTR => self.sys_free_string(*n2.n3.bstrVal()),
+                    VT_UNKNOWN | VT_DISPATCH => {
+                        let punk = *n2.n3.punkVal();
+                        if !punk.is_null() {
+                            (*punk).Release();
+                        }
+                    },
+                    VT_RECORD => {
+                        // Records are copied with RecordCreateCopy, so they are destroyed
+                        // with the matching RecordDestroy.
+                        let rec = *n2.n3.n4();
+                        if !rec.pRecInfo.is_null() {
+                            (*rec.pRecInfo).RecordDestroy(rec.pvRecord);
+                            (*rec.pRecInfo).Release();
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        if hr == S_OK {
+            n2.vt = VT_EMPTY as VARTYPE;
+        }
+        hr
+    }
+}
+
+/// Checks that `vt` can be held by a VARIANT, as OleAut32 does before clearing or copying one
+fn validate_vartype(vt: u32) -> HRESULT {
+    let extra = vt & (VT_VECTOR | VT_ARRAY | VT_BYREF | VT_RESERVED);
+    let base = vt & VT_TYPEMASK;
+    if extra & (VT_VECTOR | VT_RESERVED) == 0 && (base < VT_VOID || base == VT_RECORD || base == VT_CLSID) {
+        if extra & (VT_BYREF | VT_ARRAY) != 0 && base <= VT_NULL {
+            return DISP_E_BADVARTYPE;
+        }
+        if base != 15 {
+            return S_OK;
+        }
+    }
+    DISP_E_BADVARTYPE
+}
+
+/// `VariantCopy`: clears `dst`, then copies `src` into it, duplicating what it owns
+unsafe fn variant_copy(alloc: &RustAllocator, dst: *mut VARIANT, src: *const VARIANT) -> HRESULT {
+    if dst.is_null() || src.is_null() {
+        return E_INVALIDARG;
+    }
+    if ptr::eq(dst, src) {
+        return S_OK;
+    }
+    let vt = u32::from((*src).n1.n2().vt);
+    let hr = validate_vartype(vt);
+    if hr != S_OK {
+        return hr;
+    }
+    let hr = alloc.variant_clear(dst);
+    if hr != S_OK {
+        return hr;
+    }
+    *dst = *src;
+    if vt & VT_BYREF != 0 {
+        return S_OK;
+    }
+    let n3 = &mut (*dst).n1.n2_mut().n3;
+    let hr = if vt & VT_ARRAY != 0 || vt == VT_SAFEARRAY {
+        match array_copy(alloc, *n3.parray()) {
+            Ok(psa) => {
+                *n3.parray_mut() = psa;
+                S_OK
+            },
+            Err(hr) => hr,
+        }
+    } else {
+        match vt {
+            VT_BSTR => match bstr_copy(*n3.bstrVal()) {
+                Ok(bstr) => {
+                    *n3.bstrVal_mut() = bstr;
+                    S_OK
+                },
+                Err(hr) => hr,
+            },
+            VT_UNKNOWN | VT_DISPATCH => {
+                let punk = *n3.punkVal();
+                if !punk.is_null() {
+                    (*punk).AddRef();
+                }
+                S_OK
+            },
+            VT_RECORD => {
+                let rec = n3.n4_mut();
+                if rec.pRecInfo.is_null() {
+                    S_OK
+                } else {
+                    let mut copy = ptr::null_mut();
+                    let hr = (*rec.pRecInfo).RecordCreateCopy(rec.pvRecord, &mut copy);
+                    if hr == S_OK {
+                        (*rec.pRecInfo).AddRef();
+                        rec.pvRecord = copy;
+                    }
+                    hr
+                }
+            },
+            _ => S_OK,
+        }
+    };
+    if hr != S_OK {
+        (*dst).n1.n2_mut().vt = VT_EMPTY as VARTYPE;
+    }
+    hr
+}
+
+fn bstr_layout(bytes: u32) -> Option<Layout> {
+    let size = (bytes as usize).checked_add(BSTR_PREFIX + mem::size_of::<OLECHAR>())?;
+    Layout::from_size_align(size, BSTR_PREFIX).ok()
+}
+
+/// Allocates a BSTR of `bytes` bytes copied from `src`, or zeroed if `src` is null
+unsafe fn bstr_alloc(src: *const u8, bytes: u32) -> BSTR {
+    let layout = match bstr_layout(bytes) {
+        Some(layout) => layout,
+        None => return ptr::null_mut(),
+    };
+    let base = alloc(layout);
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+    *(base as *mut u32) = bytes;
+    let data = base.add(BSTR_PREFIX);
+    if src.is_null() {
+        ptr::write_bytes(data, 0, bytes as usize);
+    } else {
+        ptr::copy_nonoverlapping(src, data, bytes as usize);
+    }
+    ptr::write_bytes(data.add(bytes as usize), 0, mem::size_of::<OLECHAR>());
+    data as BSTR
+}
+
+/// Reads the length prefix of a non-null BSTR
+unsafe fn bstr_byte_len(bstr: BSTR) -> u32 {
+    *(bstr as *const u32).sub(1)
+}
+
+/// Duplicates a BSTR including its byte length, null stays null
+unsafe fn bstr_copy(bstr: BSTR) -> Result<BSTR, HRESULT> {
+    if bstr.is_null() {
+        return Ok(bstr);
+    }
+    let copy = bstr_alloc(bstr as *const u8, bstr_byte_len(bstr));
+    if copy.is_null() {
+        Err(E_OUTOFMEMORY)
+    } else {
+        Ok(copy)
+    }
+}
+
+/// Size of a SAFEARRAY element of type `vt`, None if SafeArrayCreate does not support it
+fn element_size(vt: VARTYPE) -> Option<u32> {
+    let size = match u32::from(vt) {
+        VT_I1 | VT_UI1 => 1,
+        VT_I2 | VT_UI2 | VT_BOOL => 2,
+        VT_I4 | VT_UI4 | VT_R4 | VT_INT | VT_UINT | VT_ERROR => 4,
+        VT_I8 | VT_UI8 | VT_R8 | VT_CY | VT_DATE => 8,
+        VT_BSTR | VT_UNKNOWN | VT_DISPATCH => mem::size_of::<*mut c_void>(),
+        VT_DECIMAL => mem::size_of::<super::sys::DECIMAL>(),
+        VT_VARIANT => mem::size_of::<VARIANT>(),
+        _ => return None,
+    };
+    Some(size as u32)
+}
+
+fn descriptor_layout(c_dims: usize) -> Layout {
+    let size = SA_HIDDEN + mem::size_of::<SAFEARRAY>() + (c_dims - 1) * mem::size_of::<SAFEARRAYBOUND>();
+    Layout::from_size_align(size, mem::align_of::<SAFEARRAY>()).unwrap()
+}
+
+/// Allocates a zeroed descriptor of `c_dims` dimensions, without data
+unsafe fn descriptor_alloc(c_dims: usize) -> *mut SAFEARRAY {
+    let base = alloc_zeroed(descriptor_layout(c_dims));
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+    base.add(SA_HIDDEN) as *mut SAFEARRAY
+}
+
+unsafe fn descriptor_free(psa: *mut SAFEARRAY) {
+    let layout = descriptor_layout((*psa).cDims as usize);
+    dealloc((psa as *mut u8).sub(SA_HIDDEN), layout);
+}
+
+unsafe fn hidden_vartype(psa: *mut SAFEARRAY) -> *mut u32 {
+    (psa as *mut u32).sub(1)
+}
+
+unsafe fn hidden_iid(psa: *mut SAFEARRAY) -> *mut GUID {
+    (psa as *mut GUID).sub(1)
+}
+
+unsafe fn hidden_recinfo(psa: *mut SAFEARRAY) -> *mut *mut IRecordInfo {
+    (psa as *mut *mut IRecordInfo).sub(1)
+}
+
+/// `rgsabound` of the descriptor, `cDims` entries long
+unsafe fn bounds_ptr(psa: *mut SAFEARRAY) -> *mut SAFEARRAYBOUND {
+    ptr::addr_of_mut!((*psa).rgsabound) as *mut SAFEARRAYBOUND
+}
+
+/// Bound of the 1-based dimension `n_dim`, validating the arguments of Get[LU]Bound
+unsafe fn bound(psa: *mut SAFEARRAY, n_dim: UINT, out: *mut LONG) -> Result<SAFEARRAYBOUND, HRESULT> {
+    if psa.is_null() || out.is_null() {
+        return Err(E_INVALIDARG);
+    }
+    let c_dims = UINT::from((*psa).cDims);
+    if n_dim == 0 || n_dim > c_dims {
+        return Err(DISP_E_BADINDEX);
+    }
+    Ok(*bounds_ptr(psa).add((c_dims - n_dim) as usize))
+}
+
+/// Number of elements over all dimensions
+unsafe fn cell_count(psa: *mut SAFEARRAY) -> Option<usize> {
+    let bounds = bounds_ptr(psa);
+    (0..(*psa).cDims as usize).try_fold(1usize, |count, dim| count.checked_mul((*bounds.add(dim)).cElements as usize))
+}
+
+unsafe fn data_size(psa: *mut SAFEARRAY) -> Option<usize> {
+    cell_count(psa)?.checked_mul((*psa).cbElements as usize)
+}
+
+fn data_layout(size: usize) -> Option<Layout> {
+    Layout::from_size_align(cmp::max(size, 1), SA_DATA_ALIGN).ok()
+}
+
+/// Allocates zeroed data for the bounds and element size of the descriptor
+unsafe fn data_alloc(psa: *mut SAFEARRAY) -> Result<(), HRESULT> {
+    let layout = data_size(psa).and_then(data_layout).ok_or(E_OUTOFMEMORY)?;
+    let data = alloc_zeroed(layout);
+    if data.is_null() {
+        return Err(E_OUTOFMEMORY);
+    }
+    (*psa).pvData = data as *mut c_void;
+    Ok(())
+}
+
+unsafe fn data_free(psa: *mut SAFEARRAY) {
+    let layout = data_size(psa).and_then(data_layout).unwrap();
+    dealloc((*psa).pvData as *mut u8, layout);
+    (*psa).pvData = ptr::null_mut();
+}
+
+/// Address of the element at `rg_indices` (first dimension first), None if out of bounds
+unsafe fn element_ptr(psa: *mut SAFEARRAY, rg_indices: *const LONG) -> Option<*mut u8> {
+    let c_dims = (*psa).cDims as usize;
+    let bounds = bounds_ptr(psa);
+    let mut cell = 0usize;
+    let mut stride = 1usize;
+    for dim in 0..c_dims {
+        let bound = *bounds.add(c_dims - 1 - dim);
+        let offset = i64::from(*rg_indices.add(dim)) - i64::from(bound.lLbound);
+        if offset < 0 || offset >= i64::from(bound.cElements) {
+            return None;
+        }
+        cell += offset as usize * stride;
+        stride *= bound.cElements as usize;
+    }
+    Some(((*psa).pvData as *mut u8).add(cell * (*psa).cbElements as usize))
+}
+
+/// Releases what every element owns, leaving the data zeroed
+unsafe fn release_elements(alloc: &RustAllocator, psa: *mut SAFEARRAY) {
+    release_cells(alloc, psa, 0, cell_count(psa).unwrap_or(0));
+}
+
+/// Releases what the elements in the cells `from..to` own, leaving them zeroed
+unsafe fn release_cells(alloc: &RustAllocator, psa: *mut SAFEARRAY, from: usize, to: usize) {
+    let features = u32::from((*psa).fFeatures);
+    let cb = (*psa).cbElements as usize;
+    let data = (*psa).pvData as *mut u8;
+    for cell in from..to {
+        let elem = data.add(cell * cb);
+        if features & (FADF_UNKNOWN | FADF_DISPATCH) != 0 {
+            let punk = *(elem as *const *mut IUnknown);
+            if !punk.is_null() {
+                (*punk).Release();
+            }
+        } else if features & FADF_RECORD != 0 {
+            let recinfo = *hidden_recinfo(psa);
+            if !recinfo.is_null() {
+                (*recinfo).RecordClear(elem as *mut c_void);
+            }
+        } else if features & FADF_BSTR != 0 {
+            alloc.sys_free_string(*(elem as *const BSTR));
+        } else if features & FADF_VARIANT != 0 {
+            alloc.variant_clear(elem as *mut VARIANT);
+        }
+    }
+    ptr::write_bytes(data.add(from * cb), 0, (to - from) * cb);
+}
+
+/// `SafeArrayCopy`: a new array with the same shape, type and copies of the elements
+unsafe fn array_copy(alloc: &RustAllocator, psa: *mut SAFEARRAY) -> Result<*mut SAFEARRAY, HRESULT> {
+    if psa.is_null() {
+        return Ok(psa);
+    }
+    let c_dims = (*psa).cDims as usize;
+    let copy = descriptor_alloc(c_dims);
+    if copy.is_null() {
+        return Err(E_OUTOFMEMORY);
+    }
+    ptr::copy_nonoverlapping((psa as *const u8).sub(SA_HIDDEN), (copy as *mut u8).sub(SA_HIDDEN), SA_HIDDEN);
+    (*copy).cDims = (*psa).cDims;
+    (*copy).fFeatures = (u32::from((*psa).fFeatures) & !FADF_NOT_OWNED) as u16;
+    (*copy).cbElements = (*psa).cbElements;
+    ptr::copy_nonoverlapping(bounds_ptr(psa), bounds_ptr(copy), c_dims);
+    if let Err(hr) = data_alloc(copy) {
+        descriptor_free(copy);
+        return Err(hr);
+    }
+    let features = u32::from((*copy).fFeatures);
+    if features & FADF_RECORD != 0 {
+        let recinfo = *hidden_recinfo(copy);
+        if !recinfo.is_null() {
+            (*recinfo).AddRef();
+        }
+    }
+
+    let cb = (*psa).cbElements as usize;
+    let count = cell_count(psa).unwrap_or(0);
+    let src = (*psa).pvData as *const u8;
+    let dst = (*copy).pvData as *mut u8;
+    if features & (FADF_VARIANT | FADF_BSTR | FADF_UNKNOWN | FADF_DISPATCH | FADF_RECORD) == 0 {
+        ptr::copy_nonoverlapping(src, dst, count * cb);
+        return Ok(copy);
+    }
+    for cell in 0..count {
+        let (s, d) = (src.add(cell * cb), dst.add(cell * cb));
+        let hr = if features & FADF_VARIANT != 0 {
+            variant_copy(alloc, d as *mut VARIANT, s as *const VARIANT)
+        } else if features & FADF_BSTR != 0 {
+            match bstr_copy(*(s as *const BSTR)) {
+                Ok(bstr) => {
+                    *(d as *mut BSTR) = bstr;
+                    S_OK
+                },
+                Err(hr) => hr,
+            }
+        } else if features & FADF_RECORD != 0 {
+            let recinfo = *hidden_recinfo(copy);
+            if recinfo.is_null() {
+                S_OK
+            } else {
+                (*recinfo).RecordCopy(s as *mut c_void, d as *mut c_void)
+            }
+        } else {
+            let punk = *(s as *const *mut IUnknown);
+            if !punk.is_null() {
+                (*punk).AddRef();
+            }
+            *(d as *mut *mut IUnknown) = punk;
+            S_OK
+        };
+        if hr != S_OK {
+            alloc.safe_array_destroy(copy);
+            return Err(hr);
+        }
+    }
+    Ok(copy)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use super::super::sys::{IUnknownVtbl, REFIID, ULONG, VT_I4};
+
+    /// Reference counted object whose count is read back by the tests
+    #[repr(C)]
+    pub(crate) struct Counted {
+        pub(crate) iface: IUnknown,
+        pub(crate) refs: ULONG,
+    }
+
+    impl Counted {
+        /// An object holding one reference
+        pub(crate) fn new() -> Counted {
+            Counted { iface: IUnknown { lpVtbl: &COUNTED_VTBL }, refs: 1 }
+        }
+    }
+
+    unsafe extern "system" fn query_interface(_this: *mut IUnknown, _riid: REFIID, _ppv: *mut *mut c_void) -> HRESULT {
+        E_INVALIDARG
+    }
+
+    unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+        let obj = this as *mut Counted;
+        (*obj).refs += 1;
+        (*obj).refs
+    }
+
+    unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+        let obj = this as *mut Counted;
+        (*obj).refs -= 1;
+        (*obj).refs
+    }
+
+    static COUNTED_VTBL: IUnknownVtbl = IUnknownVtbl {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    };
+
+    #[test]
+    fn test_bstr_layout() {
+        let alloc = RustAllocator;
+        let s: Vec<u16> = "hello".encode_utf16().collect();
+        unsafe {
+            let bstr = alloc.sys_alloc_string_len(s.as_ptr(), s.len() as u32);
+            assert_eq!(*(bstr as *const u32).sub(1), 10);
+            assert_eq!(alloc.sys_string_len(bstr), 5);
+            assert_eq!(*bstr.add(5), 0);
+            assert_eq!(::std::slice::from_raw_parts(bstr, 5), &s[..]);
+            alloc.sys_free_string(bstr);
+            alloc.sys_free_string(ptr::null_mut());
+            assert_eq!(alloc.sys_string_len(ptr::null_mut()), 0);
+
+            let bytes = b"odd\0bytes";
+            let bstr = alloc.sys_alloc_string_byte_len(bytes.as_ptr(), bytes.len() as u32);
+            assert_eq!(alloc.sys_string_byte_len(bstr), 9);
+            assert_eq!(alloc.sys_string_len(bstr), 4);
+            assert_eq!(::std::slice::from_raw_parts(bstr as *const u8, 11), b"odd\0bytes\0\0");
+            alloc.sys_free_string(bstr);
+            assert_eq!(alloc.sys_string_byte_len(ptr::null_mut()), 0);
+        }
+    }
+
+    #[test]
+    fn test_safearray_layout() {
+        let alloc = RustAllocator;
+        let mut bounds = [
+            SAFEARRAYBOUND { cElements: 2, lLbound: 1 },
+            SAFEARRAYBOUND { cElements: 3, lLbound: -1 },
+        ];
+        unsafe {
+            let psa = alloc.safe_array_create(VT_I4 as VARTYPE, 2, bounds.as_mut_ptr());
+            assert!(!psa.is_null());
+            assert_eq!((*psa).cDims, 2);
+            assert_eq!((*psa).cbElements, 4);
+            assert_eq!(u32::from((*psa).fFeatures), FADF_HAVEVARTYPE);
+            // rgsabound is stored last dimension first
+            assert_eq!((*bounds_ptr(psa)).lLbound, -1);
+            assert_eq!((*bounds_ptr(psa).add(1)).lLbound, 1);
+
+            let mut vt: VARTYPE = 0;
+            assert_eq!(alloc.safe_array_get_vartype(psa, &mut vt), S_OK);
+            assert_eq!(u32::from(vt), VT_I4);
+            let (mut lb, mut ub) = (0, 0);
+            assert_eq!(alloc.safe_array_get_lbound(psa, 2, &mut lb), S_OK);
+            assert_eq!(alloc.safe_array_get_ubound(psa, 2, &mut ub), S_OK);
+            assert_eq!((lb, ub), (-1, 1));
+            assert_eq!(alloc.safe_array_get_lbound(psa, 3, &mut lb), DISP_E_BADINDEX);
+
+            // the first dimension varies fastest
+            let mut val = 42i32;
+            let ix = [2, 0];
+            assert_eq!(alloc.safe_array_put_element(psa, ix.as_ptr(), &mut val as *mut i32 as *mut c_void), S_OK);
+            assert_eq!(*((*psa).pvData as *const i32).add(3), 42);
+            let mut out = 0i32;
+            assert_eq!(alloc.safe_array_get_element(psa, ix.as_ptr(), &mut out as *mut i32 as *mut c_void), S_OK);
+            assert_eq!(out, 42);
+            let ix = [3, 0];
+            assert_eq!(alloc.safe_array_get_element(psa, ix.as_ptr(), &mut out as *mut i32 as *mut c_void), DISP_E_BADINDEX);
+
+            assert_eq!(alloc.safe_array_lock(psa), S_OK);
+            assert_eq!(alloc.safe_array_destroy(psa), DISP_E_ARRAYISLOCKED);
+            assert_eq!(alloc.safe_array_unlock(psa), S_OK);
+            assert_eq!(alloc.safe_array_destroy(psa), S_OK);
+        }
+    }
+
+    #[test]
+    fn test_variant_array_copy() {
+        let alloc = RustAllocator;
+        let mut bound = SAFEARRAYBOUND { cElements: 1, lLbound: 0 };
+        let s: Vec<u16> = "abc".encode_utf16().collect();
+        unsafe {
+            let psa = alloc.safe_array_create(VT_VARIANT as VARTYPE, 1, &mut bound);
+            let mut var: VARIANT = mem::zeroed();
+            var.n1.n2_mut().vt = VT_BSTR as VARTYPE;
+            *var.n1.n2_mut().n3.bstrVal_mut() = alloc.sys_alloc_string_len(s.as_ptr(), 3);
+            let ix = 0;
+            assert_eq!(alloc.safe_array_put_element(psa, &ix, &mut var as *mut VARIANT as *mut c_void), S_OK);
+            assert_eq!(alloc.variant_clear(&mut var), S_OK);
+            assert_eq!(u32::from(var.n1.n2().vt), 0);
+
+            let mut out: VARIANT = mem::zeroed();
+            assert_eq!(alloc.safe_array_get_element(psa, &ix, &mut out as *mut VARIANT as *mut c_void), S_OK);
+            let bstr = *out.n1.n2().n3.bstrVal();
+            assert_eq!(::std::slice::from_raw_parts(bstr, 3), &s[..]);
+            assert_eq!(alloc.variant_clear(&mut out), S_OK);
+            assert_eq!(alloc.safe_array_destroy(psa), S_OK);
+        }
+    }
+
+    #[test]
+    fn test_redim() {
+        let alloc = RustAllocator;
+        let mut obj = Counted::new();
+        let punk = &mut obj.iface as *mut IUnknown;
+        let mut bound = SAFEARRAYBOUND { cElements: 2, lLbound: 1 };
+        unsafe {
+            let psa = alloc.safe_array_create(VT_UNKNOWN as VARTYPE, 1, &mut bound);
+            for ix in 1..3 {
+                assert_eq!(alloc.safe_array_put_element(psa, &ix, punk as *mut c_void), S_OK);
+            }
+            assert_eq!((*punk.cast::<Counted>()).refs, 3);
+
+            let mut grown = SAFEARRAYBOUND { cElements: 4, lLbound: 1 };
+            assert_eq!(alloc.safe_array_redim(psa, &mut grown), S_OK);
+            assert_eq!((*punk.cast::<Counted>()).refs, 3);
+            let mut out: *mut IUnknown = ptr::null_mut();
+            let ix = 2;
+            assert_eq!(alloc.safe_array_get_element(psa, &ix, &mut out as *mut *mut IUnknown as *mut c_void), S_OK);
+            assert_eq!(out, punk);
+            release(out);
+            let ix = 4;
+            assert_eq!(alloc.safe_array_get_element(psa, &ix, &mut out as *mut *mut IUnknown as *mut c_void), S_OK);
+            assert!(out.is_null());
+
+            let mut shrunk = SAFEARRAYBOUND { cElements: 1, lLbound: 1 };
+            assert_eq!(alloc.safe_array_redim(psa, &mut shrunk), S_OK);
+            assert_eq!((*punk.cast::<Counted>()).refs, 2);
+
+            (*psa).fFeatures |= FADF_FIXEDSIZE as u16;
+            assert_eq!(alloc.safe_array_redim(psa, &mut grown), E_INVALIDARG);
+            (*psa).fFeatures &= !(FADF_FIXEDSIZE as u16);
+            assert_eq!(alloc.safe_array_lock(psa), S_OK);
+            assert_eq!(alloc.safe_array_redim(psa, &mut grown), DISP_E_ARRAYISLOCKED);
+            assert_eq!(alloc.safe_array_unlock(psa), S_OK);
+
+            assert_eq!(alloc.safe_array_destroy(psa), S_OK);
+        }
+        assert_eq!(obj.refs, 1);
+    }
+}
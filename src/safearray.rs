@@ -0,0 +1,476 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+
+use super::alloc::allocator;
+use super::array::{check_bounds, read_vector, SafeArrayElement, SafeArrayExt};
+use super::errors::{
+    FromSafeArrayError,
+    FromSafeArrElemError,
+    FromVariantError,
+    IntoSafeArrayError,
+    IntoSafeArrElemError,
+    IntoVariantError,
+};
+use super::ptr::Ptr;
+use super::sys::{FADF_FIXEDSIZE, LONG, SAFEARRAY, SAFEARRAYBOUND, VARIANT, VARTYPE, VT_ARRAY};
+use super::tracking;
+use super::variant::{array_accepts, heap_variant, safearray_variant, take_array, VariantExt};
+use super::vartype::VarType;
+
+/// Owned one dimensional SAFEARRAY of `T`, destroyed with `SafeArrayDestroy` when dropped.
+///
+/// Unlike a `Vec<T>` the elements stay in the SAFEARRAY: [`get`] and [`set`] copy single
+/// elements out and in with `SafeArrayGetElement` and `SafeArrayPutElement`, and take
+/// SAFEARRAY indices, from [`lbound`] to [`ubound`]. Use [`into_raw`] to hand the array
+/// across FFI, and [`from_raw`] to take ownership of one again.
+///
+/// [`get`]: #method.get
+/// [`set`]: #method.set
+/// [`lbound`]: #method.lbound
+/// [`ubound`]: #method.ubound
+/// [`into_raw`]: #method.into_raw
+/// [`from_raw`]: #method.from_raw
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::SafeArray;
+///
+/// fn main() {
+///     let mut arr = SafeArray::<i32>::new(3).unwrap();
+///     arr.set(1, 42).unwrap();
+///     assert_eq!(arr.len(), 3);
+///     assert_eq!(arr.get(1).unwrap(), 42);
+///     assert_eq!(arr.to_vec().unwrap(), vec![0, 42, 0]);
+///
+///     let psa = arr.into_raw();
+///     let arr = unsafe { SafeArray::<i32>::from_raw(psa.as_ptr()) }.unwrap();
+///     assert_eq!(arr.iter().collect::<Result<Vec<_>, _>>().unwrap(), vec![0, 42, 0]);
+/// }
+/// ```
+pub struct SafeArray<T: SafeArrayElement> {
+    inner: Ptr<SAFEARRAY>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: SafeArrayElement> SafeArray<T> {
+    /// Creates an array of `len` zeroed elements starting at 0
+    pub fn new(len: usize) -> Result<SafeArray<T>, IntoSafeArrayError> {
+        SafeArray::with_lbound(0, len)
+    }
+
+    /// Creates an array of `len` zeroed elements starting at `lbound`. Fails with
+    /// `BoundsOverflow` if `len` does not fit a `u32` or the last index would overflow `i32`.
+    pub fn with_lbound(lbound: i32, len: usize) -> Result<SafeArray<T>, IntoSafeArrayError> {
+        let _tag = tracking::tag::<Self>("new");
+        let mut sab = SAFEARRAYBOUND { cElements: check_bounds(lbound, len)?, lLbound: lbound };
+        let psa = unsafe { allocator().safe_array_create(T::SFTYPE as u16, 1, &mut sab) };
+        match Ptr::with_checked(psa) {
+            Some(psa) => Ok(SafeArray { inner: psa, _marker: PhantomData }),
+            None => Err(IntoSafeArrayError::SafeArrayCreateFailed)
+        }
+    }
+
+    /// Creates an array starting at 0 holding the elements of `items`
+    pub fn from_vec(items: Vec<T>) -> Result<SafeArray<T>, IntoSafeArrayError> {
+        let psa = items.into_iter().into_safearray()?;
+        Ok(SafeArray { inner: psa, _marker: PhantomData })
+    }
+
+    /// Takes ownership of a SAFEARRAY after checking that it has one dimension and holds
+    /// elements `T` accepts, see [`SafeArrayElement::accepts_vartype`]. If the check fails the array is left to the caller.
+    ///
+    /// ## Safety
+    ///
+    /// `psa` must be a valid SAFEARRAY allocated by the current allocator and must not be
+    /// owned by anything else.
+    #[allow(clippy::redundant_field_names)]
+    pub unsafe fn from_raw(psa: *mut SAFEARRAY) -> Result<SafeArray<T>, FromSafeArrayError> {
+        let psa = match Ptr::with_checked(psa) {
+            Some(psa) => psa,
+            None => return Err(FromSafeArrayError::SafeArrayPtrNull)
+        };
+        let sa_dims = allocator().safe_array_get_dim(psa.as_ptr());
+        if sa_dims != 1 {
+            return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+        }
+        let mut vt: VARTYPE = 0;
+        let hr = allocator().safe_array_get_vartype(psa.as_ptr(), &mut vt);
+        if hr != 0 {
+            return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr});
+        }
+        if !T::accepts_vartype(u32::from(vt)) {
+            return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: VarType::from(T::SFTYPE), found: VarType::from(u32::from(vt))});
+        }
+        Ok(SafeArray { inner: psa, _marker: PhantomData })
+    }
+
+    /// Releases ownership of the SAFEARRAY without destroying it. It is your responsibility
+    /// to destroy it, eg by passing it to [`from_raw`] or to an FFI function that takes
+    /// ownership.
+    ///
+    /// [`from_raw`]: #method.from_raw
+    pub fn into_raw(self) -> Ptr<SAFEARRAY> {
+        let psa = self.inner;
+        mem::forget(self);
+        psa
+    }
+
+    /// The SAFEARRAY, for passing as an `[in] SAFEARRAY*` argument
+    pub fn as_ptr(&self) -> *mut SAFEARRAY {
+        self.inner.as_ptr()
+    }
+
+    /// Vartype of the elements, which can be any vartype `T` accepts
+    fn elem_vartype(&self) -> u32 {
+        let mut vt: VARTYPE = 0;
+        match unsafe { allocator().safe_array_get_vartype(self.as_ptr(), &mut vt) } {
+            0 => u32::from(vt),
+            _ => T::SFTYPE
+        }
+    }
+
+    /// Lower bound of the array
+    pub fn lbound(&self) -> i32 {
+        let mut l_bound: LONG = 0;
+        unsafe { allocator().safe_array_get_lbound(self.as_ptr(), 1, &mut l_bound) };
+        l_bound
+    }
+
+    /// Upper bound of the array, `lbound() - 1` when it is empty
+    pub fn ubound(&self) -> i32 {
+        let mut r_bound: LONG = 0;
+        unsafe { allocator().safe_array_get_ubound(self.as_ptr(), 1, &mut r_bound) };
+        r_bound
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        (i64::from(self.ubound()) - i64::from(self.lbound()) + 1) as usize
+    }
+
+    /// Whether there are no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies out the element at the SAFEARRAY index `ix`
+    pub fn get(&self, ix: i32) -> Result<T, FromSafeArrElemError> {
+        T::from_safearray(self.as_ptr(), ix)
+    }
+
+    /// Copies `val` into the element at the SAFEARRAY index `ix`, releasing what the
+    /// previous element owned
+    pub fn set(&mut self, ix: i32, val: T) -> Result<(), IntoSafeArrElemError> {
+        val.into_safearray(self.as_ptr(), ix)
+    }
+
+    /// Resizes the array in place with `SafeArrayRedim`, keeping the lower bound and the
+    /// elements still in bounds. New elements are zeroed, and what the elements dropped by
+    /// shrinking own (BSTRs, interfaces, VARIANTs) is released.
+    ///
+    /// Fails with `SafeArrayFixedSize` if the array has `FADF_FIXEDSIZE` set, with
+    /// `BoundsOverflow` if the new upper bound does not fit an `i32`, and with
+    /// `SafeArrayRedimFailed` if it is locked.
+    #[allow(clippy::redundant_field_names)]
+    pub fn resize(&mut self, len: usize) -> Result<(), IntoSafeArrayError> {
+        let _tag = tracking::tag::<Self>("resize");
+        if u32::from(unsafe { (*self.as_ptr()).fFeatures }) & FADF_FIXEDSIZE != 0 {
+            return Err(IntoSafeArrayError::SafeArrayFixedSize);
+        }
+        let l_bound = self.lbound();
+        let mut sab = SAFEARRAYBOUND { cElements: check_bounds(l_bound, len)?, lLbound: l_bound };
+        let hr = unsafe { allocator().safe_array_redim(self.as_ptr(), &mut sab) };
+        if hr != 0 {
+            return Err(IntoSafeArrayError::SafeArrayRedimFailed{hr: hr});
+        }
+        Ok(())
+    }
+
+    /// Appends `val` after the last element, growing the array by one. If `val` cannot be
+    /// put in the array, the array is shrunk back and left as it was.
+    pub fn push(&mut self, val: T) -> Result<(), IntoSafeArrayError> {
+        let len = self.len();
+        self.resize(len + 1)?;
+        let ix = self.ubound();
+        match self.set(ix, val) {
+            Ok(()) => Ok(()),
+            Err(iae) => {
+                // Shrinking back only drops the zeroed element added above
+                let _ = self.resize(len);
+                Err(IntoSafeArrayError::from_element_err(iae, len))
+            }
+        }
+    }
+
+    /// Shortens the array to `len` elements, releasing the others. Does nothing if it has
+    /// `len` elements or fewer.
+    pub fn truncate(&mut self, len: usize) -> Result<(), IntoSafeArrayError> {
+        if len < self.len() {
+            self.resize(len)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Iterates over copies of the elements
+    pub fn iter(&self) -> SafeArrayIter<'_, T> {
+        SafeArrayIter { arr: self, ix: i64::from(self.lbound()), end: i64::from(self.ubound()) + 1 }
+    }
+
+    /// Copies every element into a `Vec`
+    pub fn to_vec(&self) -> Result<Vec<T>, FromSafeArrayError> {
+        let _tag = tracking::tag::<Self>("to_vec");
+        let (_, items) = read_vector(self.as_ptr())?;
+        Ok(items)
+    }
+}
+
+impl<T: SafeArrayElement> Drop for SafeArray<T> {
+    fn drop(&mut self) {
+        let _tag = tracking::tag::<Self>("drop");
+        unsafe { allocator().safe_array_destroy(self.as_ptr()) };
+    }
+}
+
+impl<T: SafeArrayElement> fmt::Debug for SafeArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SafeArray")
+            .field("vartype", &VarType::from(self.elem_vartype()))
+            .field("lbound", &self.lbound())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Iterator over copies of the elements of a [`SafeArray`], made by [`SafeArray::iter`]
+///
+/// [`SafeArray`]: struct.SafeArray.html
+/// [`SafeArray::iter`]: struct.SafeArray.html#method.iter
+#[derive(Debug)]
+pub struct SafeArrayIter<'a, T: SafeArrayElement + 'a> {
+    arr: &'a SafeArray<T>,
+    ix: i64,
+    end: i64,
+}
+
+impl<'a, T: SafeArrayElement> Iterator for SafeArrayIter<'a, T> {
+    type Item = Result<T, FromSafeArrElemError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ix >= self.end {
+            return None;
+        }
+        let val = self.arr.get(self.ix as i32);
+        self.ix += 1;
+        Some(val)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.ix) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T: SafeArrayElement> ExactSizeIterator for SafeArrayIter<'a, T> {}
+
+impl<T: SafeArrayElement> VariantExt for SafeArray<T> {
+    const VARTYPE: u32 = VT_ARRAY | T::SFTYPE;
+    fn accepts_vartype(vt: u32) -> bool {
+        array_accepts::<T>(vt)
+    }
+
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let _tag = tracking::tag::<Self>("from_variant");
+        let psa = take_array::<T>(var)?;
+        match unsafe { SafeArray::from_raw(psa) } {
+            Ok(arr) => Ok(arr),
+            Err(fsae) => {
+                unsafe { allocator().safe_array_destroy(psa) };
+                Err(FromVariantError::from(fsae))
+            }
+        }
+    }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        Ok(heap_variant(self.into_raw_variant()?))
+    }
+
+    fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+        let vt = VT_ARRAY | self.elem_vartype();
+        Ok(safearray_variant(self.into_raw(), vt))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::tracking::AllocTracker;
+    use super::super::sys::{VT_BSTR, VT_I4, VT_VARIANT};
+    use super::super::variant::OwnedVariant;
+
+    #[test]
+    fn test_get_set() {
+        let tracker = AllocTracker::start();
+        {
+            let mut arr = SafeArray::<f64>::with_lbound(1, 3).unwrap();
+            assert_eq!((arr.lbound(), arr.ubound(), arr.len()), (1, 3, 3));
+            arr.set(3, 2.5).unwrap();
+            assert_eq!(arr.get(3).unwrap(), 2.5);
+            assert!(arr.get(0).is_err());
+            assert!(arr.set(4, 1.0).is_err());
+            assert_eq!(arr.iter().len(), 3);
+            assert_eq!(arr.to_vec().unwrap(), vec![0.0, 0.0, 2.5]);
+        }
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    fn test_raw() {
+        let arr = SafeArray::from_vec(vec![1u8, 2, 3]).unwrap();
+        let psa = arr.into_raw();
+        match unsafe { SafeArray::<i8>::from_raw(psa.as_ptr()) } {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+        let arr = unsafe { SafeArray::<u8>::from_raw(psa.as_ptr()) }.unwrap();
+        assert_eq!(arr.to_vec().unwrap(), vec![1, 2, 3]);
+
+        let var = arr.into_variant().unwrap();
+        let arr = SafeArray::<u8>::from_variant(var).unwrap();
+        drop(unsafe { OwnedVariant::from_raw(var) });
+        assert_eq!(format!("{:?}", arr), "SafeArray { vartype: VT_UI1, lbound: 0, len: 3 }");
+        assert!(SafeArray::<u8>::new(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_owned_variant() {
+        let tracker = AllocTracker::start();
+        {
+            let var = OwnedVariant::new(SafeArray::from_vec(vec![1i32, 2]).unwrap()).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_I4);
+            let pvar = var.into_raw();
+            match SafeArray::<u8>::from_variant(pvar) {
+                Err(FromVariantError::VarTypeDoesNotMatch{..}) => {},
+                r => panic!("unexpected result: {:?}", r)
+            }
+            drop(unsafe { OwnedVariant::from_raw(pvar) });
+
+            let mut arr = SafeArray::<String>::new(1).unwrap();
+            let mut vt: VARTYPE = 0;
+            unsafe { allocator().safe_array_get_vartype(arr.as_ptr(), &mut vt) };
+            assert_eq!(u32::from(vt), VT_BSTR);
+            arr.set(0, String::from("a")).unwrap();
+            let var = OwnedVariant::new(arr).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_BSTR);
+
+            let mut sab = SAFEARRAYBOUND { cElements: 1, lLbound: 0 };
+            let psa = unsafe { allocator().safe_array_create(VT_VARIANT as u16, 1, &mut sab) };
+            let mut arr = unsafe { SafeArray::<String>::from_raw(psa) }.unwrap();
+            arr.set(0, String::from("b")).unwrap();
+            let var = OwnedVariant::new(arr).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_VARIANT);
+            let pvar = var.into_raw();
+            let arr = SafeArray::<String>::from_variant(pvar).unwrap();
+            drop(unsafe { OwnedVariant::from_raw(pvar) });
+            assert_eq!(arr.to_vec().unwrap(), vec!["b"]);
+        }
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_bounds_overflow() {
+        match SafeArray::<i32>::with_lbound(i32::max_value(), 2) {
+            Err(IntoSafeArrayError::BoundsOverflow{lbound, len: 2}) => assert_eq!(lbound, i32::max_value()),
+            r => panic!("unexpected result: {:?}", r)
+        }
+        let arr = SafeArray::<i32>::with_lbound(i32::max_value(), 1).unwrap();
+        assert_eq!((arr.lbound(), arr.ubound()), (i32::max_value(), i32::max_value()));
+    }
+
+    #[test]
+    fn test_resize() {
+        let tracker = AllocTracker::start();
+        {
+            let mut arr = SafeArray::from_vec(vec![String::from("a"), String::from("b")]).unwrap();
+            arr.push(String::from("c")).unwrap();
+            assert_eq!(arr.to_vec().unwrap(), vec!["a", "b", "c"]);
+            arr.truncate(1).unwrap();
+            arr.truncate(4).unwrap();
+            assert_eq!(arr.to_vec().unwrap(), vec!["a"]);
+
+            let mut arr = SafeArray::<i32>::with_lbound(1, 2).unwrap();
+            arr.set(2, 7).unwrap();
+            arr.resize(4).unwrap();
+            assert_eq!((arr.lbound(), arr.ubound()), (1, 4));
+            assert_eq!(arr.to_vec().unwrap(), vec![0, 7, 0, 0]);
+            arr.resize(0).unwrap();
+            assert!(arr.is_empty());
+        }
+        tracker.assert_no_leaks();
+    }
+
+    struct NonNegative(i32);
+
+    impl SafeArrayElement for NonNegative {
+        const SFTYPE: u32 = VT_I4;
+
+        fn into_safearray(self, psa: *mut SAFEARRAY, ix: i32) -> Result<(), IntoSafeArrElemError> {
+            if self.0 < 0 {
+                return Err(IntoSafeArrElemError::PutElementFailed{hr: -1});
+            }
+            self.0.into_safearray(psa, ix)
+        }
+
+        fn from_safearray(psa: *mut SAFEARRAY, ix: i32) -> Result<Self, FromSafeArrElemError> {
+            i32::from_safearray(psa, ix).map(NonNegative)
+        }
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_push_fails() {
+        let tracker = AllocTracker::start();
+        {
+            let mut arr = SafeArray::<NonNegative>::new(0).unwrap();
+            arr.push(NonNegative(1)).unwrap();
+            match arr.push(NonNegative(-1)) {
+                Err(IntoSafeArrayError::ElementConversionFailed{index: 1, ..}) => {},
+                r => panic!("unexpected result: {:?}", r.map(|_| ()))
+            }
+            assert_eq!((arr.lbound(), arr.ubound(), arr.len()), (0, 0, 1));
+            assert_eq!(arr.get(0).unwrap().0, 1);
+        }
+        tracker.assert_no_leaks();
+
+        let mut arr = SafeArray::<i32>::with_lbound(i32::max_value(), 1).unwrap();
+        match arr.push(2) {
+            Err(IntoSafeArrayError::BoundsOverflow{len: 2, ..}) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+        assert_eq!(arr.to_vec().unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_resize_fails() {
+        let mut arr = SafeArray::from_vec(vec![1i32, 2]).unwrap();
+        unsafe { (*arr.as_ptr()).fFeatures |= FADF_FIXEDSIZE as u16 };
+        match arr.push(3) {
+            Err(IntoSafeArrayError::SafeArrayFixedSize) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+        unsafe { (*arr.as_ptr()).fFeatures &= !(FADF_FIXEDSIZE as u16) };
+
+        unsafe { allocator().safe_array_lock(arr.as_ptr()) };
+        match arr.resize(3) {
+            Err(IntoSafeArrayError::SafeArrayRedimFailed{..}) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+        unsafe { allocator().safe_array_unlock(arr.as_ptr()) };
+        assert_eq!(arr.to_vec().unwrap(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,403 @@
+//! # Sys
+//! The raw OLE Automation types this crate converts to and from.
+//!
+//! On Windows these are re-exports of the `winapi` definitions, so values can be passed
+//! straight to and from other `winapi` based code. Everywhere else the crate defines
+//! `#[repr(C)]` mirrors with the same names, fields and union accessors (`n1.n2()`,
+//! `n3.lVal_mut()`, ...), which lets the conversion logic compile and be tested on
+//! platforms without OleAut32.
+//!
+//! Only the subset of the Windows API used by this crate is provided.
+
+#[cfg(windows)]
+mod imp {
+    pub use winapi::ctypes::c_void;
+    pub use winapi::shared::guiddef::{GUID, REFIID};
+    pub use winapi::shared::minwindef::{BYTE, UINT, ULONG, USHORT, WORD};
+    pub use winapi::shared::ntdef::{HRESULT, LCID, LONG};
+    pub use winapi::shared::wtypes::*;
+    pub use winapi::shared::wtypesbase::{LPOLESTR, OLECHAR, SCODE};
+    pub use winapi::um::oaidl::{
+        DISPID,
+        FADF_AUTO,
+        FADF_BSTR,
+        FADF_DISPATCH,
+        FADF_EMBEDDED,
+        FADF_FIXEDSIZE,
+        FADF_HAVEIID,
+        FADF_HAVEVARTYPE,
+        FADF_RECORD,
+        FADF_RESERVED,
+        FADF_STATIC,
+        FADF_UNKNOWN,
+        FADF_VARIANT,
+        IDispatch,
+        IDispatchVtbl,
+        IRecordInfo,
+        IRecordInfoVtbl,
+        LPSAFEARRAY,
+        LPSAFEARRAYBOUND,
+        SAFEARRAY,
+        SAFEARRAYBOUND,
+        VARIANT,
+        VARIANT_n1,
+        VARIANT_n3,
+        __tagBRECORD,
+        __tagVARIANT,
+    };
+    pub use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+}
+
+#[cfg(not(windows))]
+#[allow(missing_copy_implementations, missing_docs, non_camel_case_types, non_snake_case, non_upper_case_globals)]
+#[allow(clippy::missing_safety_doc, clippy::too_many_arguments)]
+mod imp {
+    use std::ops::Deref;
+
+    pub use std::os::raw::c_void;
+
+    pub type BYTE = u8;
+    pub type WORD = u16;
+    pub type USHORT = u16;
+    pub type UINT = u32;
+    pub type ULONG = u32;
+    pub type LONG = i32;
+    pub type LCID = u32;
+    pub type DISPID = i32;
+    pub type HRESULT = i32;
+    pub type SCODE = i32;
+    pub type OLECHAR = u16;
+    pub type LPOLESTR = *mut OLECHAR;
+    pub type BSTR = *mut OLECHAR;
+    pub type DATE = f64;
+    pub type VARIANT_BOOL = i16;
+    pub type VARTYPE = u16;
+
+    pub const VARIANT_TRUE: VARIANT_BOOL = -1;
+    pub const VARIANT_FALSE: VARIANT_BOOL = 0;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct GUID {
+        pub Data1: u32,
+        pub Data2: u16,
+        pub Data3: u16,
+        pub Data4: [u8; 8],
+    }
+    pub type REFIID = *const GUID;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CY {
+        pub int64: i64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct DECIMAL {
+        pub wReserved: USHORT,
+        pub scale: BYTE,
+        pub sign: BYTE,
+        pub Hi32: ULONG,
+        pub Lo64: u64,
+    }
+    pub const DECIMAL_NEG: BYTE = 0x80;
+
+    pub type VARENUM = u32;
+    pub const VT_EMPTY: VARENUM = 0;
+    pub const VT_NULL: VARENUM = 1;
+    pub const VT_I2: VARENUM = 2;
+    pub const VT_I4: VARENUM = 3;
+    pub const VT_R4: VARENUM = 4;
+    pub const VT_R8: VARENUM = 5;
+    pub const VT_CY: VARENUM = 6;
+    pub const VT_DATE: VARENUM = 7;
+    pub const VT_BSTR: VARENUM = 8;
+    pub const VT_DISPATCH: VARENUM = 9;
+    pub const VT_ERROR: VARENUM = 10;
+    pub const VT_BOOL: VARENUM = 11;
+    pub const VT_VARIANT: VARENUM = 12;
+    pub const VT_UNKNOWN: VARENUM = 13;
+    pub const VT_DECIMAL: VARENUM = 14;
+    pub const VT_I1: VARENUM = 16;
+    pub const VT_UI1: VARENUM = 17;
+    pub const VT_UI2: VARENUM = 18;
+    pub const VT_UI4: VARENUM = 19;
+    pub const VT_I8: VARENUM = 20;
+    pub const VT_UI8: VARENUM = 21;
+    pub const VT_INT: VARENUM = 22;
+    pub const VT_UINT: VARENUM = 23;
+    pub const VT_VOID: VARENUM = 24;
+    pub const VT_HRESULT: VARENUM = 25;
+    pub const VT_PTR: VARENUM = 26;
+    pub const VT_SAFEARRAY: VARENUM = 27;
+    pub const VT_CARRAY: VARENUM = 28;
+    pub const VT_USERDEFINED: VARENUM = 29;
+    pub const VT_LPSTR: VARENUM = 30;
+    pub const VT_LPWSTR: VARENUM = 31;
+    pub const VT_RECORD: VARENUM = 36;
+    pub const VT_INT_PTR: VARENUM = 37;
+    pub const VT_UINT_PTR: VARENUM = 38;
+    pub const VT_FILETIME: VARENUM = 64;
+    pub const VT_BLOB: VARENUM = 65;
+    pub const VT_STREAM: VARENUM = 66;
+    pub const VT_STORAGE: VARENUM = 67;
+    pub const VT_STREAMED_OBJECT: VARENUM = 68;
+    pub const VT_STORED_OBJECT: VARENUM = 69;
+    pub const VT_BLOB_OBJECT: VARENUM = 70;
+    pub const VT_CF: VARENUM = 71;
+    pub const VT_CLSID: VARENUM = 72;
+    pub const VT_VERSIONED_STREAM: VARENUM = 73;
+    pub const VT_BSTR_BLOB: VARENUM = 0xfff;
+    pub const VT_VECTOR: VARENUM = 0x1000;
+    pub const VT_ARRAY: VARENUM = 0x2000;
+    pub const VT_BYREF: VARENUM = 0x4000;
+    pub const VT_RESERVED: VARENUM = 0x8000;
+    pub const VT_ILLEGAL: VARENUM = 0xffff;
+    pub const VT_ILLEGALMASKED: VARENUM = 0xfff;
+    pub const VT_TYPEMASK: VARENUM = 0xfff;
+
+    pub const FADF_AUTO: u32 = 0x1;
+    pub const FADF_STATIC: u32 = 0x2;
+    pub const FADF_EMBEDDED: u32 = 0x4;
+    pub const FADF_FIXEDSIZE: u32 = 0x10;
+    pub const FADF_RECORD: u32 = 0x20;
+    pub const FADF_HAVEIID: u32 = 0x40;
+    pub const FADF_HAVEVARTYPE: u32 = 0x80;
+    pub const FADF_BSTR: u32 = 0x100;
+    pub const FADF_UNKNOWN: u32 = 0x200;
+    pub const FADF_DISPATCH: u32 = 0x400;
+    pub const FADF_VARIANT: u32 = 0x800;
+    pub const FADF_RESERVED: u32 = 0xf008;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SAFEARRAYBOUND {
+        pub cElements: ULONG,
+        pub lLbound: LONG,
+    }
+    pub type LPSAFEARRAYBOUND = *mut SAFEARRAYBOUND;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SAFEARRAY {
+        pub cDims: USHORT,
+        pub fFeatures: USHORT,
+        pub cbElements: ULONG,
+        pub cLocks: ULONG,
+        pub pvData: *mut c_void,
+        pub rgsabound: [SAFEARRAYBOUND; 1],
+    }
+    pub type LPSAFEARRAY = *mut SAFEARRAY;
+
+    /// Declares a COM interface as a struct holding its vtable pointer, the vtable itself
+    /// and `unsafe` methods calling through it, the same shape `winapi`'s `RIDL!` produces.
+    macro_rules! interface {
+        (
+            interface $iface:ident($vtbl:ident) {
+                $(fn $method:ident($($p:ident: $t:ty),*) -> $r:ty,)*
+            }
+        ) => {
+            #[repr(C)]
+            pub struct $vtbl {
+                $(pub $method: unsafe extern "system" fn(This: *mut $iface $(, $p: $t)*) -> $r,)*
+            }
+
+            #[repr(C)]
+            pub struct $iface {
+                pub lpVtbl: *const $vtbl,
+            }
+
+            impl $iface {
+                $(
+                    #[inline]
+                    pub unsafe fn $method(&self $(, $p: $t)*) -> $r {
+                        ((*self.lpVtbl).$method)(self as *const _ as *mut _ $(, $p)*)
+                    }
+                )*
+            }
+        };
+        (
+            interface $iface:ident($vtbl:ident): $parent:ident($pvtbl:ident) {
+                $(fn $method:ident($($p:ident: $t:ty),*) -> $r:ty,)*
+            }
+        ) => {
+            #[repr(C)]
+            pub struct $vtbl {
+                pub parent: $pvtbl,
+                $(pub $method: unsafe extern "system" fn(This: *mut $iface $(, $p: $t)*) -> $r,)*
+            }
+
+            #[repr(C)]
+            pub struct $iface {
+                pub lpVtbl: *const $vtbl,
+            }
+
+            impl $iface {
+                $(
+                    #[inline]
+                    pub unsafe fn $method(&self $(, $p: $t)*) -> $r {
+                        ((*self.lpVtbl).$method)(self as *const _ as *mut _ $(, $p)*)
+                    }
+                )*
+            }
+
+            impl Deref for $iface {
+                type Target = $parent;
+                #[inline]
+                fn deref(&self) -> &$parent {
+                    unsafe { &*(self as *const $iface as *const $parent) }
+                }
+            }
+        };
+    }
+
+    interface!{interface IUnknown(IUnknownVtbl) {
+        fn QueryInterface(riid: REFIID, ppvObject: *mut *mut c_void) -> HRESULT,
+        fn AddRef() -> ULONG,
+        fn Release() -> ULONG,
+    }}
+
+    // ITypeInfo, DISPPARAMS and EXCEPINFO are not mirrored, so they are passed as `c_void`.
+    interface!{interface IDispatch(IDispatchVtbl): IUnknown(IUnknownVtbl) {
+        fn GetTypeInfoCount(pctinfo: *mut UINT) -> HRESULT,
+        fn GetTypeInfo(iTInfo: UINT, lcid: LCID, ppTInfo: *mut *mut c_void) -> HRESULT,
+        fn GetIDsOfNames(riid: REFIID, rgszNames: *mut LPOLESTR, cNames: UINT, lcid: LCID, rgDispId: *mut DISPID) -> HRESULT,
+        fn Invoke(dispIdMember: DISPID, riid: REFIID, lcid: LCID, wFlags: WORD, pDispParams: *mut c_void, pVarResult: *mut VARIANT, pExcepInfo: *mut c_void, puArgErr: *mut UINT) -> HRESULT,
+    }}
+
+    interface!{interface IRecordInfo(IRecordInfoVtbl): IUnknown(IUnknownVtbl) {
+        fn RecordInit(pvNew: *mut c_void) -> HRESULT,
+        fn RecordClear(pvExisting: *mut c_void) -> HRESULT,
+        fn RecordCopy(pvExisting: *mut c_void, pvNew: *mut c_void) -> HRESULT,
+        fn GetGuid(pguid: *mut GUID) -> HRESULT,
+        fn GetName(pbstrName: *mut BSTR) -> HRESULT,
+        fn GetSize(pcbSize: *mut ULONG) -> HRESULT,
+        fn GetTypeInfo(ppTypeInfo: *mut *mut c_void) -> HRESULT,
+        fn GetField(pvData: *mut c_void, szFieldName: *const OLECHAR, pvarField: *mut VARIANT) -> HRESULT,
+        fn GetFieldNoCopy(pvData: *mut c_void, szFieldName: *const OLECHAR, pvarField: *mut VARIANT, ppvDataCArray: *mut *mut c_void) -> HRESULT,
+        fn PutField(wFlags: ULONG, pvData: *mut c_void, szFieldName: *const OLECHAR, pvarField: *mut VARIANT) -> HRESULT,
+        fn PutFieldNoCopy(wFlags: ULONG, pvData: *mut c_void, szFieldName: *const OLECHAR, pvarField: *mut VARIANT) -> HRESULT,
+        fn GetFieldNames(pcNames: *mut ULONG, rgBstrNames: *mut BSTR) -> HRESULT,
+        fn IsMatchingType(pRecordInfo: *mut IRecordInfo) -> i32,
+        fn RecordCreate() -> *mut c_void,
+        fn RecordCreateCopy(pvSource: *mut c_void, ppvDest: *mut *mut c_void) -> HRESULT,
+        fn RecordDestroy(pvRecord: *mut c_void) -> HRESULT,
+    }}
+
+    /// Declares a C union along with the `winapi` style `field()` / `field_mut()` accessors.
+    macro_rules! com_union {
+        (union $name:ident { $($field:ident $field_mut:ident: $t:ty,)* }) => {
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            pub union $name {
+                $($field: $t,)*
+            }
+
+            impl $name {
+                $(
+                    #[inline]
+                    pub unsafe fn $field(&self) -> &$t {
+                        &self.$field
+                    }
+                    #[inline]
+                    pub unsafe fn $field_mut(&mut self) -> &mut $t {
+                        &mut self.$field
+                    }
+                )*
+            }
+        };
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct __tagBRECORD {
+        pub pvRecord: *mut c_void,
+        pub pRecInfo: *mut IRecordInfo,
+    }
+
+    com_union!{union VARIANT_n3 {
+        llVal llVal_mut: i64,
+        lVal lVal_mut: LONG,
+        bVal bVal_mut: BYTE,
+        iVal iVal_mut: i16,
+        fltVal fltVal_mut: f32,
+        dblVal dblVal_mut: f64,
+        boolVal boolVal_mut: VARIANT_BOOL,
+        scode scode_mut: SCODE,
+        cyVal cyVal_mut: CY,
+        date date_mut: DATE,
+        bstrVal bstrVal_mut: BSTR,
+        punkVal punkVal_mut: *mut IUnknown,
+        pdispVal pdispVal_mut: *mut IDispatch,
+        parray parray_mut: *mut SAFEARRAY,
+        pbVal pbVal_mut: *mut BYTE,
+        piVal piVal_mut: *mut i16,
+        plVal plVal_mut: *mut LONG,
+        pllVal pllVal_mut: *mut i64,
+        pfltVal pfltVal_mut: *mut f32,
+        pdblVal pdblVal_mut: *mut f64,
+        pboolVal pboolVal_mut: *mut VARIANT_BOOL,
+        pscode pscode_mut: *mut SCODE,
+        pcyVal pcyVal_mut: *mut CY,
+        pdate pdate_mut: *mut DATE,
+        pbstrVal pbstrVal_mut: *mut BSTR,
+        ppunkVal ppunkVal_mut: *mut *mut IUnknown,
+        ppdispVal ppdispVal_mut: *mut *mut IDispatch,
+        pparray pparray_mut: *mut *mut SAFEARRAY,
+        pvarVal pvarVal_mut: *mut VARIANT,
+        byref byref_mut: *mut c_void,
+        cVal cVal_mut: i8,
+        uiVal uiVal_mut: u16,
+        ulVal ulVal_mut: ULONG,
+        ullVal ullVal_mut: u64,
+        intVal intVal_mut: i32,
+        uintVal uintVal_mut: UINT,
+        pdecVal pdecVal_mut: *mut DECIMAL,
+        pcVal pcVal_mut: *mut i8,
+        puiVal puiVal_mut: *mut u16,
+        pulVal pulVal_mut: *mut ULONG,
+        pullVal pullVal_mut: *mut u64,
+        pintVal pintVal_mut: *mut i32,
+        puintVal puintVal_mut: *mut UINT,
+        n4 n4_mut: __tagBRECORD,
+    }}
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct __tagVARIANT {
+        pub vt: VARTYPE,
+        pub wReserved1: WORD,
+        pub wReserved2: WORD,
+        pub wReserved3: WORD,
+        pub n3: VARIANT_n3,
+    }
+
+    com_union!{union VARIANT_n1 {
+        n2 n2_mut: __tagVARIANT,
+        decVal decVal_mut: DECIMAL,
+    }}
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct VARIANT {
+        pub n1: VARIANT_n1,
+    }
+}
+
+pub use self::imp::*;
+
+#[cfg(test)]
+mod test {
+    use std::mem;
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let word = mem::size_of::<usize>();
+        assert_eq!(mem::size_of::<DECIMAL>(), 16);
+        assert_eq!(mem::size_of::<SAFEARRAYBOUND>(), 8);
+        assert_eq!(mem::size_of::<SAFEARRAY>(), 16 + 2 * word);
+        assert_eq!(mem::size_of::<VARIANT>(), 8 + 2 * word);
+    }
+}
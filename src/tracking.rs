@@ -0,0 +1,464 @@
+//! # Allocation tracking
+//! A test mode recording every BSTR, SAFEARRAY, VARIANT and BYREF allocation this crate
+//! makes, and every free, so test suites can check that conversions are balanced.
+//!
+//! [`AllocTracker::start`] wraps the current allocator once and then tracks the current
+//! thread until the tracker is dropped. Each allocation is tagged with the conversion
+//! that made it (`into_variant` of `String`, ...). [`AllocTracker::assert_no_leaks`]
+//! panics listing whatever is still live, and freeing an allocation twice panics
+//! immediately, before the memory is handed back to the allocator.
+//!
+//! Memory allocated before tracking started, or on another thread, is not tracked:
+//! freeing it is passed through unchecked.
+//!
+//! [`AllocTracker::start`]: struct.AllocTracker.html#method.start
+//! [`AllocTracker::assert_no_leaks`]: struct.AllocTracker.html#method.assert_no_leaks
+use std::any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Once;
+
+use super::alloc::{allocator, set_allocator, OleAllocator};
+use super::sys::{
+    BSTR,
+    HRESULT,
+    LONG,
+    OLECHAR,
+    SAFEARRAY,
+    SAFEARRAYBOUND,
+    UINT,
+    VARIANT,
+    VARTYPE,
+    VT_ARRAY,
+    VT_BSTR,
+    VT_BYREF,
+    VT_VARIANT,
+    c_void,
+};
+
+/// What a tracked allocation holds
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AllocKind {
+    /// A BSTR from `SysAllocStringLen` or `SysAllocStringByteLen`
+    Bstr,
+    /// A SAFEARRAY from `SafeArrayCreate`
+    SafeArray,
+    /// A heap allocated VARIANT, eg from `into_variant`
+    Variant,
+    /// The value a VT_BYREF VARIANT built from a `Box<T>` points at
+    ByRef,
+}
+
+impl fmt::Display for AllocKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            AllocKind::Bstr => "BSTR",
+            AllocKind::SafeArray => "SAFEARRAY",
+            AllocKind::Variant => "VARIANT",
+            AllocKind::ByRef => "BYREF",
+        })
+    }
+}
+
+/// Names the conversion that caused an allocation or a free
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AllocTag {
+    /// The conversion, eg `into_variant`
+    pub conversion: &'static str,
+    /// The type being converted, as given by `std::any::type_name`
+    pub type_name: &'static str,
+}
+
+const UNTAGGED: AllocTag = AllocTag { conversion: "untagged", type_name: "" };
+
+impl fmt::Display for AllocTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.type_name.is_empty() {
+            f.write_str(self.conversion)
+        } else {
+            write!(f, "{} of {}", self.conversion, self.type_name)
+        }
+    }
+}
+
+/// An allocation which has not been freed yet
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Allocation {
+    /// What was allocated
+    pub kind: AllocKind,
+    /// Address of the allocation, as handed out (a BSTR points past its length prefix)
+    pub address: usize,
+    /// The conversion which allocated it
+    pub tag: AllocTag,
+}
+
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {:#x}, allocated by {}", self.kind, self.address, self.tag)
+    }
+}
+
+struct Freed {
+    allocation: Allocation,
+    by: AllocTag,
+}
+
+#[derive(Default)]
+struct State {
+    live: Vec<Allocation>,
+    freed: HashMap<usize, Freed>,
+    tags: Vec<AllocTag>,
+}
+
+thread_local! {
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+fn active() -> bool {
+    ACTIVE.try_with(|a| a.get()).unwrap_or(false)
+}
+
+fn current_tag(state: &State) -> AllocTag {
+    state.tags.last().cloned().unwrap_or(UNTAGGED)
+}
+
+/// Records an allocation made on the current thread, if it is being tracked
+#[allow(clippy::redundant_field_names)]
+pub(crate) fn note_alloc(kind: AllocKind, p: *const c_void) {
+    if p.is_null() || !active() {
+        return;
+    }
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let address = p as usize;
+        let tag = current_tag(&state);
+        state.freed.remove(&address);
+        state.live.push(Allocation { kind: kind, address: address, tag: tag });
+    });
+}
+
+/// Panics if `p` has already been freed while tracking
+fn check_free(kind: AllocKind, p: *const c_void) {
+    if p.is_null() || !active() {
+        return;
+    }
+    STATE.with(|state| {
+        let state = state.borrow();
+        if let Some(freed) = state.freed.get(&(p as usize)) {
+            panic!("double free of {} at {:#x} by {}: {}, already freed by {}",
+                kind, p as usize, current_tag(&state), freed.allocation, freed.by);
+        }
+    });
+}
+
+/// Records a free made on the current thread, panicking on a double free
+#[allow(clippy::redundant_field_names)]
+pub(crate) fn note_free(kind: AllocKind, p: *const c_void) {
+    if p.is_null() || !active() {
+        return;
+    }
+    check_free(kind, p);
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let address = p as usize;
+        if let Some(ix) = state.live.iter().position(|a| a.address == address) {
+            let allocation = state.live.remove(ix);
+            let by = current_tag(&state);
+            state.freed.insert(address, Freed { allocation: allocation, by: by });
+        }
+    });
+}
+
+/// Guard returned by [`tag`], restoring the previous tag when dropped
+pub(crate) struct TagGuard {
+    pushed: bool,
+}
+
+/// Tags allocations and frees on the current thread with `conversion` of `T` until the
+/// guard is dropped
+#[allow(clippy::redundant_field_names)]
+pub(crate) fn tag<T: ?Sized>(conversion: &'static str) -> TagGuard {
+    if !active() {
+        return TagGuard { pushed: false };
+    }
+    let tag = AllocTag { conversion: conversion, type_name: any::type_name::<T>() };
+    STATE.with(|state| state.borrow_mut().tags.push(tag));
+    TagGuard { pushed: true }
+}
+
+impl Drop for TagGuard {
+    fn drop(&mut self) {
+        if self.pushed {
+            let _ = STATE.try_with(|state| state.borrow_mut().tags.pop());
+        }
+    }
+}
+
+/// The BSTR or SAFEARRAY a VARIANT owns, if any
+unsafe fn variant_contents(var: *const VARIANT) -> Option<(AllocKind, *const c_void)> {
+    let n2 = (*var).n1.n2();
+    let vt = u32::from(n2.vt);
+    if vt & VT_BYREF != 0 {
+        None
+    } else if vt & VT_ARRAY != 0 {
+        Some((AllocKind::SafeArray, *n2.n3.parray() as *const c_void))
+    } else if vt == VT_BSTR {
+        Some((AllocKind::Bstr, *n2.n3.bstrVal() as *const c_void))
+    } else {
+        None
+    }
+}
+
+/// Allocator wrapping another one, recording what goes through it
+struct TrackingAllocator {
+    inner: &'static dyn OleAllocator,
+}
+
+unsafe impl OleAllocator for TrackingAllocator {
+    unsafe fn sys_alloc_string_len(&self, psz: *const OLECHAR, len: UINT) -> BSTR {
+        let bstr = self.inner.sys_alloc_string_len(psz, len);
+        note_alloc(AllocKind::Bstr, bstr as *const c_void);
+        bstr
+    }
+    unsafe fn sys_free_string(&self, bstr: BSTR) {
+        note_free(AllocKind::Bstr, bstr as *const c_void);
+        self.inner.sys_free_string(bstr)
+    }
+    unsafe fn sys_string_len(&self, bstr: BSTR) -> UINT {
+        self.inner.sys_string_len(bstr)
+    }
+    unsafe fn sys_alloc_string_byte_len(&self, psz: *const u8, len: UINT) -> BSTR {
+        let bstr = self.inner.sys_alloc_string_byte_len(psz, len);
+        note_alloc(AllocKind::Bstr, bstr as *const c_void);
+        bstr
+    }
+    unsafe fn sys_string_byte_len(&self, bstr: BSTR) -> UINT {
+        self.inner.sys_string_byte_len(bstr)
+    }
+    unsafe fn safe_array_create(&self, vt: VARTYPE, c_dims: UINT, rgsabound: *mut SAFEARRAYBOUND) -> *mut SAFEARRAY {
+        let psa = self.inner.safe_array_create(vt, c_dims, rgsabound);
+        note_alloc(AllocKind::SafeArray, psa as *const c_void);
+        psa
+    }
+    unsafe fn safe_array_destroy(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        check_free(AllocKind::SafeArray, psa as *const c_void);
+        let hr = self.inner.safe_array_destroy(psa);
+        if hr == 0 {
+            note_free(AllocKind::SafeArray, psa as *const c_void);
+        }
+        hr
+    }
+    unsafe fn safe_array_get_dim(&self, psa: *mut SAFEARRAY) -> UINT {
+        self.inner.safe_array_get_dim(psa)
+    }
+    unsafe fn safe_array_get_elemsize(&self, psa: *mut SAFEARRAY) -> UINT {
+        self.inner.safe_array_get_elemsize(psa)
+    }
+    unsafe fn safe_array_get_lbound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_lbound: *mut LONG) -> HRESULT {
+        self.inner.safe_array_get_lbound(psa, n_dim, pl_lbound)
+    }
+    unsafe fn safe_array_get_ubound(&self, psa: *mut SAFEARRAY, n_dim: UINT, pl_ubound: *mut LONG) -> HRESULT {
+        self.inner.safe_array_get_ubound(psa, n_dim, pl_ubound)
+    }
+    unsafe fn safe_array_get_vartype(&self, psa: *mut SAFEARRAY, pvt: *mut VARTYPE) -> HRESULT {
+        self.inner.safe_array_get_vartype(psa, pvt)
+    }
+    unsafe fn safe_array_lock(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        self.inner.safe_array_lock(psa)
+    }
+    unsafe fn safe_array_unlock(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        self.inner.safe_array_unlock(psa)
+    }
+    unsafe fn safe_array_access_data(&self, psa: *mut SAFEARRAY, ppv_data: *mut *mut c_void) -> HRESULT {
+        self.inner.safe_array_access_data(psa, ppv_data)
+    }
+    unsafe fn safe_array_unaccess_data(&self, psa: *mut SAFEARRAY) -> HRESULT {
+        self.inner.safe_array_unaccess_data(psa)
+    }
+    unsafe fn safe_array_redim(&self, psa: *mut SAFEARRAY, psabound_new: *mut SAFEARRAYBOUND) -> HRESULT {
+        self.inner.safe_array_redim(psa, psabound_new)
+    }
+    unsafe fn safe_array_get_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT {
+        let hr = self.inner.safe_array_get_element(psa, rg_indices, pv);
+        if hr != 0 || !active() {
+            return hr;
+        }
+        // The element is copied out, so the caller owns the copy of a BSTR or of
+        // whatever a VARIANT holds
+        let mut vt: VARTYPE = 0;
+        if self.inner.safe_array_get_vartype(psa, &mut vt) == 0 {
+            match u32::from(vt) {
+                VT_BSTR => note_alloc(AllocKind::Bstr, *(pv as *const BSTR) as *const c_void),
+                VT_VARIANT => if let Some((kind, p)) = variant_contents(pv as *const VARIANT) {
+                    note_alloc(kind, p)
+                },
+                _ => {}
+            }
+        }
+        hr
+    }
+    unsafe fn safe_array_put_element(&self, psa: *mut SAFEARRAY, rg_indices: *const LONG, pv: *mut c_void) -> HRESULT {
+        self.inner.safe_array_put_element(psa, rg_indices, pv)
+    }
+    unsafe fn variant_clear(&self, pvarg: *mut VARIANT) -> HRESULT {
+        let contents = if pvarg.is_null() { None } else { variant_contents(pvarg) };
+        if let Some((kind, p)) = contents {
+            check_free(kind, p);
+        }
+        let hr = self.inner.variant_clear(pvarg);
+        if let (0, Some((kind, p))) = (hr, contents) {
+            note_free(kind, p);
+        }
+        hr
+    }
+}
+
+static INSTALL: Once = Once::new();
+
+/// Tracks the allocations made on the current thread until it is dropped.
+///
+/// The first tracker started wraps the allocator in use with a tracking one, for the
+/// rest of the process. Replacing the allocator afterwards with [`set_allocator`] stops
+/// the tracking.
+///
+/// Only one tracker can be active per thread, and trackers are not `Send`.
+///
+/// [`set_allocator`]: fn.set_allocator.html
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{AllocTracker, OwnedVariant};
+///
+/// fn main() {
+///     let tracker = AllocTracker::start();
+///     let var = OwnedVariant::new(String::from("Turing completeness.")).unwrap();
+///     assert_eq!(tracker.live().len(), 2);
+///     drop(var);
+///     tracker.assert_no_leaks();
+/// }
+/// ```
+pub struct AllocTracker {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl AllocTracker {
+    /// Starts tracking the current thread.
+    ///
+    /// ## Panics
+    /// If a tracker is already active on the current thread.
+    #[allow(clippy::redundant_field_names)]
+    pub fn start() -> AllocTracker {
+        INSTALL.call_once(|| {
+            let inner = allocator();
+            let tracking: &'static TrackingAllocator = Box::leak(Box::new(TrackingAllocator { inner: inner }));
+            // Wrapping the allocator in use is fine: memory allocated before is freed by it
+            let _ = unsafe { set_allocator(tracking) };
+        });
+        assert!(!active(), "an AllocTracker is already active on this thread");
+        STATE.with(|state| *state.borrow_mut() = State::default());
+        ACTIVE.with(|a| a.set(true));
+        AllocTracker { _not_send: PhantomData }
+    }
+
+    /// The allocations which have not been freed yet, oldest first
+    pub fn live(&self) -> Vec<Allocation> {
+        STATE.with(|state| state.borrow().live.clone())
+    }
+
+    /// Panics listing every allocation which has not been freed yet
+    pub fn assert_no_leaks(&self) {
+        let live = self.live();
+        if !live.is_empty() {
+            let list: Vec<String> = live.iter().map(|a| format!("    {}", a)).collect();
+            panic!("{} allocation(s) leaked:\n{}", live.len(), list.join("\n"));
+        }
+    }
+}
+
+impl fmt::Debug for AllocTracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AllocTracker").field("live", &self.live().len()).finish()
+    }
+}
+
+impl Drop for AllocTracker {
+    fn drop(&mut self) {
+        let _ = ACTIVE.try_with(|a| a.set(false));
+        let _ = STATE.try_with(|state| *state.borrow_mut() = State::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use widestring::U16String;
+    use super::super::bstr::BStringExt;
+    use super::super::types::Currency;
+    use super::super::value::VariantValue;
+    use super::super::variant::{OwnedVariant, VariantExt};
+
+    fn round_trip<T: VariantExt>(t: T) -> T {
+        let pvar = t.into_variant().unwrap();
+        let t = T::from_variant(pvar).unwrap();
+        drop(unsafe { OwnedVariant::from_raw(pvar) });
+        t
+    }
+
+    #[test]
+    fn test_balanced() {
+        let tracker = AllocTracker::start();
+        assert_eq!(round_trip(-5i32), -5);
+        assert_eq!(round_trip(Currency::from(100)), Currency::from(100));
+        assert_eq!(round_trip(String::from("Turing completeness.")), "Turing completeness.");
+        assert_eq!(round_trip(vec![1i32, 2, 3]), vec![1, 2, 3]);
+
+        let val = OwnedVariant::from_value(VariantValue::from("abc")).unwrap();
+        assert_eq!(tracker.live().len(), 2);
+        let mut out = OwnedVariant::empty();
+        unsafe {
+            String::from("first").write_into(out.as_mut_ptr()).unwrap();
+            String::from("second").write_into(out.as_mut_ptr()).unwrap();
+        }
+        drop(val);
+        drop(out);
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    #[allow(clippy::redundant_field_names)]
+    fn test_tags() {
+        let tracker = AllocTracker::start();
+        let pvar = String::from("tagged").into_variant().unwrap();
+        let live = tracker.live();
+        let tag = AllocTag { conversion: "into_variant", type_name: any::type_name::<String>() };
+        assert_eq!(live.len(), 2);
+        assert_eq!(live[0].kind, AllocKind::Bstr);
+        assert_eq!(live[0].tag, tag);
+        assert_eq!(live[1], Allocation { kind: AllocKind::Variant, address: pvar.as_ptr() as usize, tag: tag });
+        drop(unsafe { OwnedVariant::from_raw(pvar) });
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    #[should_panic(expected = "1 allocation(s) leaked")]
+    fn test_leak() {
+        let tracker = AllocTracker::start();
+        let _ = U16String::from_str("leaked").allocate_bstr().unwrap();
+        tracker.assert_no_leaks();
+    }
+
+    #[test]
+    #[should_panic(expected = "double free of BSTR")]
+    fn test_double_free() {
+        let _tracker = AllocTracker::start();
+        let bstr = U16String::from_str("freed twice").allocate_bstr().unwrap();
+        U16String::deallocate_bstr(bstr);
+        U16String::deallocate_bstr(bstr);
+    }
+}
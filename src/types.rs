@@ -4,8 +4,13 @@
 //!   * CY
 //!   * DATE
 //!   * DECIMAL
+//!
+//! It also implements the VBScript operators (`VarAdd`, `VarSub`, ...) on `VariantValue`.
 //! 
+use std::cmp::Ordering;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::panic;
 
 #[cfg(feature = "impl_tryfrom")]
 use std::convert::{TryFrom};
@@ -15,14 +20,36 @@ use std::num::{TryFromIntError};
 
 use rust_decimal::Decimal;
 
-use winapi::shared::wtypes::{CY, DECIMAL, DECIMAL_NEG, VARIANT_BOOL, VARIANT_TRUE};
+use super::coerce::{to_number, to_real, Number};
+use super::errors::{ChangeTypeError, OperatorError};
+use super::sys::{
+    CY,
+    DECIMAL,
+    DECIMAL_NEG,
+    VARIANT_BOOL,
+    VARIANT_TRUE,
+    VT_BSTR,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_R4,
+    VT_R8,
+    VT_UI1,
+};
+use super::value::VariantValue;
+use super::vartype::VarType;
 
 /// Pseudo-`From` trait because of orphan rules
+#[allow(dead_code)]
 trait Conversion<T> {
     fn convert(val: T) -> Self;
 }
 
 impl<T> Conversion<T> for T where T: From<T>{
+    #[allow(clippy::useless_conversion)]
     fn convert(val: T) -> Self {
         T::from(val)
     }
@@ -148,6 +175,7 @@ macro_rules! conversions_impl {
 /// Helper type for the OLE/COM+ type CY
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Copy, Debug, Eq,  Hash, PartialOrd, PartialEq)]
+#[repr(transparent)]
 pub struct Currency(i64);
 
 impl From<CY> for Currency {
@@ -155,11 +183,13 @@ impl From<CY> for Currency {
         Currency(cy.int64)
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'c> From<&'c CY> for Currency {
     fn from(cy: &CY) -> Currency {
         Currency(cy.int64)
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'c> From<&'c mut CY> for Currency {
     fn from(cy: &mut CY) -> Currency {
         Currency(cy.int64)
@@ -171,11 +201,13 @@ impl From<Currency> for CY {
         CY {int64: cy.0}
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'c> From<&'c Currency> for CY {
     fn from(cy: &Currency) -> CY {
         CY {int64: cy.0}
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'c> From<&'c mut Currency> for CY {
     fn from(cy: &mut Currency) -> CY {
         CY {int64: cy.0}
@@ -193,6 +225,7 @@ conversions_impl!(Currency, CY);
 /// Helper type for the OLE/COM+ type DATE
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+#[repr(transparent)]
 pub struct Date(f64); //DATE <--> F64
 
 impl AsRef<f64> for Date {
@@ -220,15 +253,18 @@ impl DecWrapper {
     }
 
     /// Get borrow of internal value
+    #[allow(clippy::should_implement_trait)]
     pub fn borrow(&self) -> &Decimal {
         &self.0
     }
 
     /// Get mutable borrow of internal value
+    #[allow(clippy::should_implement_trait)]
     pub fn borrow_mut(&mut self) -> &mut Decimal {
         &mut self.0
     }
 
+    #[allow(clippy::redundant_field_names)]
     fn build_c_decimal(dec: Decimal) -> DECIMAL {
         let scale = dec.scale() as u8;
         let sign = if dec.is_sign_positive() {0} else {DECIMAL_NEG};
@@ -254,6 +290,7 @@ impl DecWrapper {
         }
     }
 
+    #[allow(clippy::needless_bool)]
     fn build_rust_decimal(dec: DECIMAL) -> Decimal {
         let sign = if dec.sign == DECIMAL_NEG {true} else {false};
         Decimal::from_parts((dec.Lo64 & 0xFFFFFFFF) as u32, 
@@ -279,11 +316,13 @@ impl From<DECIMAL> for DecWrapper {
         DecWrapper(DecWrapper::build_rust_decimal(d))
     }
 }
+#[allow(clippy::clone_on_copy, clippy::needless_lifetimes)]
 impl<'d> From<&'d DECIMAL> for DecWrapper {
     fn from(d: &DECIMAL) -> DecWrapper {
         DecWrapper(DecWrapper::build_rust_decimal(d.clone()))
     }
 }
+#[allow(clippy::clone_on_copy, clippy::needless_lifetimes)]
 impl<'d> From<&'d mut DECIMAL> for DecWrapper {
     fn from(d: &mut DECIMAL) -> DecWrapper {
         DecWrapper(DecWrapper::build_rust_decimal(d.clone()))
@@ -296,11 +335,13 @@ impl From<DecWrapper> for DECIMAL {
         DecWrapper::build_c_decimal(d.0)
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'d> From<&'d DecWrapper> for DECIMAL {
     fn from(d: &DecWrapper) -> DECIMAL {
         DecWrapper::build_c_decimal(d.0)
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'d> From<&'d mut DecWrapper> for DECIMAL {
     fn from(d: & mut DecWrapper) -> DECIMAL {
         DecWrapper::build_c_decimal(d.0)
@@ -313,11 +354,13 @@ impl From<DecWrapper> for Decimal {
         dw.0
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'w> From<&'w DecWrapper> for Decimal {
     fn from(dw: &DecWrapper) -> Decimal {
         dw.0
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'w> From<&'w mut DecWrapper> for Decimal {
     fn from(dw: &mut DecWrapper) -> Decimal {
         dw.0
@@ -330,11 +373,13 @@ impl From<Decimal> for DecWrapper {
         DecWrapper(dec)
     }
 }
+#[allow(clippy::clone_on_copy, clippy::needless_lifetimes)]
 impl<'d> From<&'d Decimal> for DecWrapper {
     fn from(dec: &Decimal) -> DecWrapper {
         DecWrapper(dec.clone())
     }
 }
+#[allow(clippy::clone_on_copy, clippy::needless_lifetimes)]
 impl<'d> From<&'d mut Decimal> for DecWrapper {
     fn from(dec: &mut Decimal) -> DecWrapper {
         DecWrapper(dec.clone())
@@ -350,79 +395,130 @@ conversions_impl!(Decimal, DecWrapper);
 conversions_impl!(DecWrapper, DECIMAL);
 
 /// Helper type for the OLE/COM+ type VARIANT_BOOL
+///
+/// It has the layout of a `VARIANT_BOOL`, so arrays of it are read and written in bulk.
+/// Any negative value is true, and values compare by truth.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct VariantBool(bool);
+#[cfg_attr(feature = "serde", serde(from = "bool", into = "bool"))]
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct VariantBool(VARIANT_BOOL);
+
+impl VariantBool {
+    fn get(self) -> bool {
+        self.0 < 0
+    }
+}
+
+impl fmt::Debug for VariantBool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("VariantBool").field(&self.get()).finish()
+    }
+}
+
+impl PartialEq for VariantBool {
+    fn eq(&self, other: &VariantBool) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Eq for VariantBool {}
+
+impl PartialOrd for VariantBool {
+    fn partial_cmp(&self, other: &VariantBool) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for VariantBool {
+    fn cmp(&self, other: &VariantBool) -> Ordering {
+        self.get().cmp(&other.get())
+    }
+}
+
+impl Hash for VariantBool {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get().hash(state)
+    }
+}
 
 impl From<VariantBool> for VARIANT_BOOL {
     fn from(vb: VariantBool) -> VARIANT_BOOL {
-        if vb.0 {VARIANT_TRUE} else {0}
+        if vb.get() {VARIANT_TRUE} else {0}
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'v> From<&'v VariantBool> for VARIANT_BOOL {
     fn from(vb: &VariantBool) -> VARIANT_BOOL {
-        if vb.0 {VARIANT_TRUE} else {0}
+        if vb.get() {VARIANT_TRUE} else {0}
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'v> From<&'v mut VariantBool> for VARIANT_BOOL {
     fn from(vb: &mut VariantBool) -> VARIANT_BOOL {
-        if vb.0 {VARIANT_TRUE} else {0}
+        if vb.get() {VARIANT_TRUE} else {0}
     }
 }
 
 impl From<VARIANT_BOOL> for VariantBool {
     fn from(vb: VARIANT_BOOL) -> VariantBool {
-        VariantBool(vb < 0) 
+        VariantBool::from(vb < 0) 
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'v> From<&'v VARIANT_BOOL> for VariantBool {
     fn from(vb: &VARIANT_BOOL) -> VariantBool {
-        VariantBool(*vb < 0) 
+        VariantBool::from(*vb < 0) 
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'v> From<&'v mut VARIANT_BOOL> for VariantBool {
     fn from(vb: &mut VARIANT_BOOL) -> VariantBool {
-        VariantBool(*vb < 0) 
+        VariantBool::from(*vb < 0) 
     }
 }
 
 impl From<bool> for VariantBool {
     fn from(b: bool) -> Self {
-        VariantBool(b)
+        VariantBool(if b {VARIANT_TRUE} else {0})
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'b> From<&'b bool> for VariantBool {
     fn from(b: &bool) -> Self {
-        VariantBool(*b)
+        VariantBool::from(*b)
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'b> From<&'b mut bool> for VariantBool {
     fn from(b: &mut bool) -> Self {
-        VariantBool(*b)
+        VariantBool::from(*b)
     }
 }
 
 impl From<VariantBool> for bool {
     fn from(b: VariantBool) -> Self {
-        b.0
+        b.get()
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'v> From<&'v VariantBool> for bool {
     fn from(b: &VariantBool) -> Self {
-        b.0
+        b.get()
     }
 }
+#[allow(clippy::needless_lifetimes)]
 impl<'v> From<&'v mut VariantBool> for bool {
     fn from(b: &mut VariantBool) -> Self {
-        b.0
+        b.get()
     }
 }
 
 impl AsRef<bool> for VariantBool {
     fn as_ref(&self) -> &bool {
-        &self.0
+        if self.get() {&true} else {&false}
     }
 }
 
@@ -432,6 +528,7 @@ conversions_impl!(VariantBool, VARIANT_BOOL);
 /// Helper type for the OLE/COM+ type INT
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
 pub struct Int(i32);
 
 impl AsRef<i32> for Int {
@@ -500,6 +597,7 @@ impl TryFrom<i8> for Int {
 
 /// Helper type for the OLE/COM+ type UINT
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
 pub struct UInt(u32);
 
 impl AsRef<u32> for UInt {
@@ -570,6 +668,7 @@ impl TryFrom<u8> for UInt {
 /// Helper type for the OLE/COM+ type SCODE
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
 pub struct SCode(i32);
 
 impl AsRef<i32> for SCode {
@@ -604,10 +703,448 @@ impl fmt::Binary for SCode {
 
 wrapper_conv_impl!(i32, SCode);
 
+/// Operand classes of the VBScript operators, ordered as the OLE promotion tables rank them
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum OpClass {
+    UI1,
+    I2,
+    I4,
+    I8,
+    R4,
+    R8,
+    Bstr,
+    Cy,
+    Date,
+    Decimal,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum IntOp {
+    Idiv,
+    Mod,
+    And,
+    Or,
+    Xor,
+}
+
+fn op_class(val: &VariantValue, other: &VariantValue) -> Result<OpClass, OperatorError> {
+    let class = match *val {
+        VariantValue::Empty | VariantValue::Bool(_) | VariantValue::I1(_) | VariantValue::I2(_) => OpClass::I2,
+        VariantValue::UI1(_) => OpClass::UI1,
+        VariantValue::UI2(_) | VariantValue::I4(_) | VariantValue::Int(_) => OpClass::I4,
+        VariantValue::UI4(_) | VariantValue::UInt(_) | VariantValue::I8(_) | VariantValue::UI8(_) => OpClass::I8,
+        VariantValue::R4(_) => OpClass::R4,
+        VariantValue::R8(_) => OpClass::R8,
+        VariantValue::Bstr(_) => OpClass::Bstr,
+        VariantValue::Cy(_) => OpClass::Cy,
+        VariantValue::Date(_) => OpClass::Date,
+        VariantValue::Decimal(_) => OpClass::Decimal,
+        _ => return Err(OperatorError::from(ChangeTypeError::TypeMismatch{from: val.vartype(), to: other.vartype()})),
+    };
+    Ok(class)
+}
+
+fn int_vartype(class: OpClass) -> u32 {
+    match class {
+        OpClass::UI1 => VT_UI1,
+        OpClass::I2 => VT_I2,
+        OpClass::I8 => VT_I8,
+        _ => VT_I4,
+    }
+}
+
+fn as_int(val: &VariantValue, vartype: u32) -> Result<i128, OperatorError> {
+    match to_number(&val.change_type(VarType::from(vartype))?) {
+        Some(Number::Int(i)) => Ok(i),
+        _ => Err(OperatorError::from(ChangeTypeError::TypeMismatch{from: val.vartype(), to: VarType::from(vartype)})),
+    }
+}
+
+fn as_real(val: &VariantValue) -> Result<f64, OperatorError> {
+    match val.change_type(VarType::from(VT_R8))? {
+        VariantValue::R8(r) => Ok(r),
+        _ => Err(OperatorError::from(ChangeTypeError::TypeMismatch{from: val.vartype(), to: VarType::from(VT_R8)})),
+    }
+}
+
+fn as_currency(val: &VariantValue) -> Result<i128, OperatorError> {
+    match val.change_type(VarType::from(VT_CY))? {
+        VariantValue::Cy(cy) => Ok(i64::from(cy) as i128),
+        _ => Err(OperatorError::from(ChangeTypeError::TypeMismatch{from: val.vartype(), to: VarType::from(VT_CY)})),
+    }
+}
+
+fn as_decimal(val: &VariantValue) -> Result<Decimal, OperatorError> {
+    match val.change_type(VarType::from(VT_DECIMAL))? {
+        VariantValue::Decimal(dec) => Ok(dec.unwrap()),
+        _ => Err(OperatorError::from(ChangeTypeError::TypeMismatch{from: val.vartype(), to: VarType::from(VT_DECIMAL)})),
+    }
+}
+
+fn as_string(val: &VariantValue) -> Result<String, OperatorError> {
+    match val.change_type(VarType::from(VT_BSTR))? {
+        VariantValue::Bstr(s) => Ok(s),
+        _ => Err(OperatorError::from(ChangeTypeError::TypeMismatch{from: val.vartype(), to: VarType::from(VT_BSTR)})),
+    }
+}
+
+/// Integer division rounding half way cases to even, as OleAut32 rounds CY results
+fn div_round_half_even(n: i128, d: i128) -> i128 {
+    let (q, r) = (n / d, n % d);
+    let (r2, d) = (2 * r.abs(), d.abs());
+    if r2 > d || (r2 == d && q % 2 != 0) {
+        if (n < 0) == (d < 0) || n == 0 {q + 1} else {q - 1}
+    } else {
+        q
+    }
+}
+
+/// Picks the narrowest integer vartype holding `i`, starting at `class`; integer
+/// overflow promotes UI1 to I2 to I4, then to R8
+#[allow(clippy::legacy_numeric_constants)]
+fn int_result(i: i128, class: OpClass) -> VariantValue {
+    match class {
+        OpClass::UI1 if i >= 0 && i <= u8::max_value() as i128 => VariantValue::UI1(i as u8),
+        OpClass::UI1 | OpClass::I2 if i >= i16::min_value() as i128 && i <= i16::max_value() as i128 => VariantValue::I2(i as i16),
+        OpClass::UI1 | OpClass::I2 | OpClass::I4 if i >= i32::min_value() as i128 && i <= i32::max_value() as i128 => VariantValue::I4(i as i32),
+        OpClass::I8 if i >= i64::min_value() as i128 && i <= i64::max_value() as i128 => VariantValue::I8(i as i64),
+        _ => VariantValue::R8(i as f64),
+    }
+}
+
+#[allow(clippy::legacy_numeric_constants, clippy::manual_range_contains)]
+fn real_result(r: f64, vartype: u32) -> Result<VariantValue, OperatorError> {
+    if !r.is_finite() {
+        return Err(OperatorError::Overflow{vartype: VarType::from(vartype)});
+    }
+    let val = match vartype {
+        VT_R4 if r.abs() <= ::std::f32::MAX as f64 => VariantValue::R4(r as f32),
+        VT_DATE if r >= -657434.0 && r < 2958466.0 => VariantValue::Date(Date(r)),
+        VT_DATE => return Err(OperatorError::Overflow{vartype: VarType::from(vartype)}),
+        _ => VariantValue::R8(r),
+    };
+    Ok(val)
+}
+
+/// Largest magnitude a DECIMAL holds, 2^96 - 1, as an f64
+const DECIMAL_MAX: f64 = 7.922_816_251_426_434e28;
+
+#[allow(clippy::legacy_numeric_constants, clippy::neg_cmp_op_on_partial_ord)]
+fn arith(left: &VariantValue, right: &VariantValue, op: ArithOp) -> Result<VariantValue, OperatorError> {
+    if *left == VariantValue::Null || *right == VariantValue::Null {
+        return Ok(VariantValue::Null);
+    }
+    let (lc, rc) = (op_class(left, right)?, op_class(right, left)?);
+    let has = |c| lc == c || rc == c;
+    if op == ArithOp::Add && lc == OpClass::Bstr && rc == OpClass::Bstr {
+        return Ok(VariantValue::Bstr(as_string(left)? + &as_string(right)?));
+    }
+
+    let vartype = if has(OpClass::Decimal) {
+        VT_DECIMAL
+    } else if has(OpClass::Date) {
+        match op {
+            ArithOp::Add => VT_DATE,
+            ArithOp::Sub if lc != rc => VT_DATE,
+            _ => VT_R8,
+        }
+    } else if has(OpClass::Cy) {
+        if op == ArithOp::Div && lc == rc {VT_R8} else {VT_CY}
+    } else if has(OpClass::R8) || has(OpClass::Bstr) {
+        VT_R8
+    } else if has(OpClass::R4) {
+        if has(OpClass::I4) || has(OpClass::I8) {VT_R8} else {VT_R4}
+    } else if op == ArithOp::Div {
+        VT_R8
+    } else {
+        int_vartype(lc.max(rc))
+    };
+
+    match vartype {
+        VT_DECIMAL => {
+            let (l, r) = (as_decimal(left)?, as_decimal(right)?);
+            let zero = Decimal::new(0, 0);
+            if op == ArithOp::Div && r == zero {
+                return Err(if l == zero {OperatorError::Overflow{vartype: VarType::from(vartype)}} else {OperatorError::DivideByZero});
+            }
+            // rust_decimal panics on overflow. Results clearly out of range are refused
+            // from an estimate, and the panic is caught for the ones close to the limit.
+            let estimate = match op {
+                ArithOp::Add => to_real(Number::Dec(l)) + to_real(Number::Dec(r)),
+                ArithOp::Sub => to_real(Number::Dec(l)) - to_real(Number::Dec(r)),
+                ArithOp::Mul => to_real(Number::Dec(l)) * to_real(Number::Dec(r)),
+                ArithOp::Div => to_real(Number::Dec(l)) / to_real(Number::Dec(r)),
+            };
+            if !(estimate.abs() <= DECIMAL_MAX * 1.000_001) {
+                return Err(OperatorError::Overflow{vartype: VarType::from(vartype)});
+            }
+            let dec = panic::catch_unwind(|| match op {
+                ArithOp::Add => l + r,
+                ArithOp::Sub => l - r,
+                ArithOp::Mul => l * r,
+                ArithOp::Div => l / r,
+            });
+            match dec {
+                Ok(dec) => Ok(VariantValue::Decimal(DecWrapper(dec))),
+                Err(_) => Err(OperatorError::Overflow{vartype: VarType::from(vartype)}),
+            }
+        },
+        VT_CY => {
+            let (l, r) = (as_currency(left)?, as_currency(right)?);
+            let cy = match op {
+                ArithOp::Add => l + r,
+                ArithOp::Sub => l - r,
+                ArithOp::Mul => div_round_half_even(l * r, 10000),
+                ArithOp::Div if r == 0 => {
+                    return Err(if l == 0 {OperatorError::Overflow{vartype: VarType::from(vartype)}} else {OperatorError::DivideByZero});
+                },
+                ArithOp::Div => div_round_half_even(l * 10000, r),
+            };
+            if cy < i64::min_value() as i128 || cy > i64::max_value() as i128 {
+                return Err(OperatorError::Overflow{vartype: VarType::from(vartype)});
+            }
+            Ok(VariantValue::Cy(Currency(cy as i64)))
+        },
+        VT_R4 | VT_R8 | VT_DATE => {
+            let (l, r) = (as_real(left)?, as_real(right)?);
+            let res = match op {
+                ArithOp::Add => l + r,
+                ArithOp::Sub => l - r,
+                ArithOp::Mul => l * r,
+                ArithOp::Div if r == 0.0 => {
+                    return Err(if l == 0.0 {OperatorError::Overflow{vartype: VarType::from(vartype)}} else {OperatorError::DivideByZero});
+                },
+                ArithOp::Div => l / r,
+            };
+            real_result(res, vartype)
+        },
+        _ => {
+            let (l, r) = (as_int(left, vartype)?, as_int(right, vartype)?);
+            let res = match op {
+                ArithOp::Add => l + r,
+                ArithOp::Sub => l - r,
+                _ => l * r,
+            };
+            Ok(int_result(res, lc.max(rc)))
+        },
+    }
+}
+
+/// Applies a bitwise operator where one side is VT_NULL. `Null And 0` and
+/// `Null Or -1` are decided by the other operand alone, anything else is Null.
+#[allow(clippy::legacy_numeric_constants)]
+fn null_logic(other: &VariantValue, op: IntOp) -> Result<VariantValue, OperatorError> {
+    if *other == VariantValue::Null {
+        return Ok(VariantValue::Null);
+    }
+    if let VariantValue::Bool(b) = *other {
+        return Ok(match (op, b) {
+            (IntOp::And, false) | (IntOp::Or, true) => VariantValue::Bool(b),
+            _ => VariantValue::Null,
+        });
+    }
+    let class = op_class(other, &VariantValue::Null)?;
+    let vartype = int_vartype(match class {
+        OpClass::UI1 | OpClass::I2 | OpClass::I8 => class,
+        _ => OpClass::I4,
+    });
+    let i = as_int(other, vartype)?;
+    let all_bits = if vartype == VT_UI1 {u8::max_value() as i128} else {-1};
+    Ok(match op {
+        IntOp::And if i == 0 => other.change_type(VarType::from(vartype))?,
+        IntOp::Or if i == all_bits => other.change_type(VarType::from(vartype))?,
+        _ => VariantValue::Null,
+    })
+}
+
+fn int_arith(left: &VariantValue, right: &VariantValue, op: IntOp) -> Result<VariantValue, OperatorError> {
+    match (left, right, op) {
+        (&VariantValue::Null, other, IntOp::And) | (&VariantValue::Null, other, IntOp::Or) |
+        (other, &VariantValue::Null, IntOp::And) | (other, &VariantValue::Null, IntOp::Or) => {
+            return null_logic(other, op);
+        },
+        (&VariantValue::Null, _, _) | (_, &VariantValue::Null, _) => return Ok(VariantValue::Null),
+        (&VariantValue::Bool(l), &VariantValue::Bool(r), IntOp::And) => return Ok(VariantValue::Bool(l && r)),
+        (&VariantValue::Bool(l), &VariantValue::Bool(r), IntOp::Or) => return Ok(VariantValue::Bool(l || r)),
+        (&VariantValue::Bool(l), &VariantValue::Bool(r), IntOp::Xor) => return Ok(VariantValue::Bool(l != r)),
+        _ => {},
+    }
+    let (lc, rc) = (op_class(left, right)?, op_class(right, left)?);
+    let class = if lc == OpClass::I8 || rc == OpClass::I8 {
+        OpClass::I8
+    } else if lc > OpClass::I2 || rc > OpClass::I2 {
+        OpClass::I4
+    } else {
+        lc.max(rc)
+    };
+    let vartype = int_vartype(class);
+    let (l, r) = (as_int(left, vartype)?, as_int(right, vartype)?);
+    let res = match op {
+        IntOp::Idiv | IntOp::Mod if r == 0 => return Err(OperatorError::DivideByZero),
+        IntOp::Idiv => l / r,
+        IntOp::Mod => l % r,
+        IntOp::And => l & r,
+        IntOp::Or => l | r,
+        IntOp::Xor => l ^ r,
+    };
+    // Only I2::MIN \ -1 and friends can leave the range; promote like the other integers
+    match int_result(res, class) {
+        VariantValue::R8(_) => Err(OperatorError::Overflow{vartype: VarType::from(vartype)}),
+        val => Ok(val),
+    }
+}
+
+/// VBScript operators, implementing the OleAut32 functions of the same name
+/// (`VarAdd`, `VarSub`, ...) on `VariantValue`s of any vartype.
+///
+/// The result vartype follows the OLE promotion tables. VT_NULL propagates, VT_EMPTY
+/// acts as an I2 zero, integer results that overflow are promoted (UI1 to I2 to I4 to R8),
+/// currency arithmetic stays in CY and decimal arithmetic stays in DECIMAL.
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{Currency, VariantValue};
+///
+/// fn main() {
+///     let sum = VariantValue::I2(1).var_add(&VariantValue::I4(2)).unwrap();
+///     assert_eq!(sum, VariantValue::I4(3));
+///     let sum = VariantValue::I4(i32::max_value()).var_add(&VariantValue::I4(1)).unwrap();
+///     assert_eq!(sum, VariantValue::R8(2147483648.0));
+///     let product = VariantValue::Cy(Currency::from(15000)).var_mul(&VariantValue::I2(2)).unwrap();
+///     assert_eq!(product, VariantValue::Cy(Currency::from(30000)));
+///     let sum = VariantValue::Null.var_add(&VariantValue::I4(1)).unwrap();
+///     assert_eq!(sum, VariantValue::Null);
+/// }
+/// ```
+impl VariantValue {
+    /// `VarAdd` - addition, or concatenation when both values are strings
+    pub fn var_add(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        arith(self, other, ArithOp::Add)
+    }
+
+    /// `VarSub` - subtraction. The difference of two dates is an R8.
+    pub fn var_sub(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        arith(self, other, ArithOp::Sub)
+    }
+
+    /// `VarMul` - multiplication
+    pub fn var_mul(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        arith(self, other, ArithOp::Mul)
+    }
+
+    /// `VarDiv` - floating point division. Integers divide to an R8.
+    pub fn var_div(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        arith(self, other, ArithOp::Div)
+    }
+
+    /// `VarIdiv` - integer division (`\`). Both values are rounded to integers first.
+    pub fn var_idiv(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        int_arith(self, other, IntOp::Idiv)
+    }
+
+    /// `VarMod` - remainder of the integer division. The sign follows `self`.
+    pub fn var_mod(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        int_arith(self, other, IntOp::Mod)
+    }
+
+    /// `VarPow` - exponentiation, always an R8
+    pub fn var_pow(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        if *self == VariantValue::Null || *other == VariantValue::Null {
+            return Ok(VariantValue::Null);
+        }
+        op_class(self, other)?;
+        op_class(other, self)?;
+        real_result(as_real(self)?.powf(as_real(other)?), VT_R8)
+    }
+
+    /// `VarCat` - string concatenation (`&`). VT_NULL acts as the empty string,
+    /// unless both values are VT_NULL.
+    pub fn var_cat(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        let cat = match (self, other) {
+            (&VariantValue::Null, &VariantValue::Null) => return Ok(VariantValue::Null),
+            (&VariantValue::Null, val) | (val, &VariantValue::Null) => as_string(val)?,
+            (l, r) => as_string(l)? + &as_string(r)?,
+        };
+        Ok(VariantValue::Bstr(cat))
+    }
+
+    /// `VarAnd` - logical and for booleans, bitwise and otherwise. `Null And False` is `False`.
+    pub fn var_and(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        int_arith(self, other, IntOp::And)
+    }
+
+    /// `VarOr` - logical or for booleans, bitwise or otherwise. `Null Or True` is `True`.
+    pub fn var_or(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        int_arith(self, other, IntOp::Or)
+    }
+
+    /// `VarXor` - logical exclusive or for booleans, bitwise otherwise
+    pub fn var_xor(&self, other: &VariantValue) -> Result<VariantValue, OperatorError> {
+        int_arith(self, other, IntOp::Xor)
+    }
+
+    /// `VarNot` - logical not for booleans, bitwise not otherwise
+    pub fn var_not(&self) -> Result<VariantValue, OperatorError> {
+        let val = match *self {
+            VariantValue::Null => VariantValue::Null,
+            VariantValue::Bool(b) => VariantValue::Bool(!b),
+            VariantValue::UI1(v) => VariantValue::UI1(!v),
+            _ => {
+                let class = match op_class(self, self)? {
+                    OpClass::I2 => OpClass::I2,
+                    OpClass::I8 => OpClass::I8,
+                    _ => OpClass::I4,
+                };
+                int_result(!as_int(self, int_vartype(class))?, class)
+            },
+        };
+        Ok(val)
+    }
+
+    /// `VarNeg` - negation
+    pub fn var_neg(&self) -> Result<VariantValue, OperatorError> {
+        let val = match *self {
+            VariantValue::Null => VariantValue::Null,
+            VariantValue::R4(v) => VariantValue::R4(-v),
+            VariantValue::R8(v) => VariantValue::R8(-v),
+            VariantValue::Date(v) => VariantValue::Date(Date(-v.0)),
+            VariantValue::Decimal(v) => VariantValue::Decimal(DecWrapper(-v.0)),
+            VariantValue::Bstr(_) => VariantValue::R8(-as_real(self)?),
+            VariantValue::Cy(v) => match v.0.checked_neg() {
+                Some(cy) => VariantValue::Cy(Currency(cy)),
+                None => return Err(OperatorError::Overflow{vartype: VarType::from(VT_CY)}),
+            },
+            _ => {
+                let class = op_class(self, self)?.max(OpClass::I2);
+                match int_result(-as_int(self, int_vartype(class))?, class) {
+                    VariantValue::R8(_) if class == OpClass::I8 => {
+                        return Err(OperatorError::Overflow{vartype: VarType::from(VT_I8)});
+                    },
+                    val => val,
+                }
+            },
+        };
+        Ok(val)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn c_decimal() {
         let d = Decimal::new(0xFFFFFFFFFFFF, 0);
         let d = d * Decimal::new(0xFFFFFFFF, 0);
@@ -649,6 +1186,94 @@ mod tests {
         assert_ne!(VARIANT_TRUE, pvb);
     }
 
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_arithmetic() {
+        assert_eq!(VariantValue::I2(1).var_add(&VariantValue::I4(2)).unwrap(), VariantValue::I4(3));
+        assert_eq!(VariantValue::UI1(200).var_add(&VariantValue::UI1(100)).unwrap(), VariantValue::I2(300));
+        assert_eq!(VariantValue::I2(i16::max_value()).var_add(&VariantValue::I2(1)).unwrap(), VariantValue::I4(32768));
+        assert_eq!(VariantValue::I4(i32::min_value()).var_sub(&VariantValue::I4(1)).unwrap(), VariantValue::R8(-2147483649.0));
+        assert_eq!(VariantValue::Bool(true).var_add(&VariantValue::Bool(true)).unwrap(), VariantValue::I2(-2));
+        assert_eq!(VariantValue::Empty.var_add(&VariantValue::Empty).unwrap(), VariantValue::I2(0));
+        assert_eq!(VariantValue::R4(1.5).var_mul(&VariantValue::I2(2)).unwrap(), VariantValue::R4(3.0));
+        assert_eq!(VariantValue::R4(1.5).var_mul(&VariantValue::I4(2)).unwrap(), VariantValue::R8(3.0));
+        assert_eq!(VariantValue::from("1.5").var_add(&VariantValue::I2(1)).unwrap(), VariantValue::R8(2.5));
+        assert_eq!(VariantValue::from("a").var_add(&VariantValue::from("b")).unwrap(), VariantValue::from("ab"));
+        assert_eq!(VariantValue::I2(5).var_div(&VariantValue::I2(2)).unwrap(), VariantValue::R8(2.5));
+        assert_eq!(VariantValue::Null.var_mul(&VariantValue::I2(2)).unwrap(), VariantValue::Null);
+        assert_eq!(VariantValue::Date(Date(43381.0)).var_add(&VariantValue::I2(1)).unwrap(), VariantValue::Date(Date(43382.0)));
+        assert_eq!(VariantValue::Date(Date(43381.5)).var_sub(&VariantValue::Date(Date(43381.0))).unwrap(), VariantValue::R8(0.5));
+        match VariantValue::I4(1).var_div(&VariantValue::I4(0)) {
+            Err(OperatorError::DivideByZero) => {},
+            r => panic!("expected division by zero, got {:?}", r)
+        }
+        assert!(VariantValue::R8(1e308).var_mul(&VariantValue::R8(10.0)).is_err());
+        assert!(VariantValue::Error(SCode(1)).var_add(&VariantValue::I4(1)).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_currency_decimal_arithmetic() {
+        let cy = |i: i64| VariantValue::Cy(Currency(i));
+        assert_eq!(cy(15000).var_add(&VariantValue::I4(1)).unwrap(), cy(25000));
+        assert_eq!(cy(15000).var_mul(&cy(15000)).unwrap(), cy(22500));
+        assert_eq!(cy(10000).var_div(&VariantValue::I4(3)).unwrap(), cy(3333));
+        assert_eq!(cy(10000).var_div(&cy(40000)).unwrap(), VariantValue::R8(0.25));
+        assert_eq!(cy(10000).var_mul(&VariantValue::R8(0.5)).unwrap(), cy(5000));
+        assert!(cy(i64::max_value()).var_add(&cy(1)).is_err());
+        let dec = |i: i64, s: u32| VariantValue::Decimal(DecWrapper(Decimal::new(i, s)));
+        assert_eq!(dec(15, 1).var_add(&cy(10000)).unwrap(), dec(25, 1));
+        assert_eq!(dec(1, 0).var_div(&VariantValue::I4(4)).unwrap(), dec(25, 2));
+        let big = VariantValue::Decimal(DecWrapper(Decimal::from_str("50000000000000000000000000000").unwrap()));
+        assert!(big.var_add(&big).is_err());
+        let dec_str = |s: &str| VariantValue::Decimal(DecWrapper(Decimal::from_str(s).unwrap()));
+        assert_eq!(dec_str("79000000000000000000000000000").var_add(&dec_str("100000000000000000000000000")).unwrap(),
+            dec_str("79100000000000000000000000000"));
+        let max = dec_str("79228162514264337593543950335");
+        assert_eq!(max.var_sub(&dec(1, 0)).unwrap(), dec_str("79228162514264337593543950334"));
+        assert!(max.var_add(&dec(1, 0)).is_err());
+        assert!(max.var_mul(&dec(10000001, 7)).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_integer_operators() {
+        assert_eq!(VariantValue::I4(7).var_idiv(&VariantValue::I4(2)).unwrap(), VariantValue::I4(3));
+        assert_eq!(VariantValue::R8(7.5).var_idiv(&VariantValue::I2(2)).unwrap(), VariantValue::I4(4));
+        assert_eq!(VariantValue::I2(-7).var_mod(&VariantValue::I2(3)).unwrap(), VariantValue::I2(-1));
+        assert_eq!(VariantValue::UI1(7).var_mod(&VariantValue::UI1(4)).unwrap(), VariantValue::UI1(3));
+        assert_eq!(VariantValue::I4(6).var_and(&VariantValue::I4(3)).unwrap(), VariantValue::I4(2));
+        assert_eq!(VariantValue::I2(6).var_or(&VariantValue::I2(3)).unwrap(), VariantValue::I2(7));
+        assert_eq!(VariantValue::I8(6).var_xor(&VariantValue::I2(3)).unwrap(), VariantValue::I8(5));
+        assert_eq!(VariantValue::Bool(true).var_and(&VariantValue::Bool(false)).unwrap(), VariantValue::Bool(false));
+        assert_eq!(VariantValue::Null.var_and(&VariantValue::Bool(false)).unwrap(), VariantValue::Bool(false));
+        assert_eq!(VariantValue::Null.var_and(&VariantValue::Bool(true)).unwrap(), VariantValue::Null);
+        assert_eq!(VariantValue::Bool(true).var_or(&VariantValue::Null).unwrap(), VariantValue::Bool(true));
+        assert_eq!(VariantValue::I4(0).var_and(&VariantValue::Null).unwrap(), VariantValue::I4(0));
+        assert_eq!(VariantValue::Null.var_xor(&VariantValue::Bool(true)).unwrap(), VariantValue::Null);
+        assert!(VariantValue::I4(1).var_mod(&VariantValue::Empty).is_err());
+        assert_eq!(VariantValue::I2(i16::min_value()).var_idiv(&VariantValue::I2(-1)).unwrap(), VariantValue::I4(32768));
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
+    fn test_unary_and_string_operators() {
+        assert_eq!(VariantValue::Bool(true).var_not().unwrap(), VariantValue::Bool(false));
+        assert_eq!(VariantValue::I4(0).var_not().unwrap(), VariantValue::I4(-1));
+        assert_eq!(VariantValue::UI1(0).var_not().unwrap(), VariantValue::UI1(255));
+        assert_eq!(VariantValue::Empty.var_not().unwrap(), VariantValue::I2(-1));
+        assert_eq!(VariantValue::R8(1.4).var_not().unwrap(), VariantValue::I4(-2));
+        assert_eq!(VariantValue::Null.var_not().unwrap(), VariantValue::Null);
+        assert_eq!(VariantValue::I2(i16::min_value()).var_neg().unwrap(), VariantValue::I4(32768));
+        assert_eq!(VariantValue::UI1(5).var_neg().unwrap(), VariantValue::I2(-5));
+        assert_eq!(VariantValue::Cy(Currency(5)).var_neg().unwrap(), VariantValue::Cy(Currency(-5)));
+        assert!(VariantValue::I8(i64::min_value()).var_neg().is_err());
+        assert_eq!(VariantValue::I2(2).var_pow(&VariantValue::I2(10)).unwrap(), VariantValue::R8(1024.0));
+        assert_eq!(VariantValue::I2(1).var_cat(&VariantValue::Bool(true)).unwrap(), VariantValue::from("1True"));
+        assert_eq!(VariantValue::Null.var_cat(&VariantValue::from("a")).unwrap(), VariantValue::from("a"));
+        assert_eq!(VariantValue::Null.var_cat(&VariantValue::Null).unwrap(), VariantValue::Null);
+    }
+
     #[test]
     fn test_send() {
         fn assert_send<T: Send>() {}
@@ -0,0 +1,417 @@
+//! Dynamic variant values
+//!
+//! This module contains the enum [`VariantValue`], which holds the contents of a VARIANT
+//! whose type is only known at runtime - for example a VARIANT handed over by VBA or Excel.
+//!
+//! [`VariantValue`]: enum.VariantValue.html
+use std::mem;
+use std::ptr::null_mut;
+
+use rust_decimal::Decimal;
+
+use super::bstr::{BStr, BString};
+use super::errors::{FromVariantError, IntoVariantError};
+use super::ptr::Ptr;
+use super::sys::{
+    DECIMAL,
+    IDispatch,
+    IRecordInfo,
+    IUnknown,
+    SAFEARRAY,
+    VARIANT,
+    VARIANT_BOOL,
+    VARIANT_n3,
+    VT_ARRAY,
+    VT_BOOL,
+    VT_BSTR,
+    VT_BYREF,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_DISPATCH,
+    VT_EMPTY,
+    VT_ERROR,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_NULL,
+    VT_R4,
+    VT_R8,
+    VT_RECORD,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+    VT_UNKNOWN,
+    c_void,
+};
+use super::tracking;
+use super::types::{Currency, Date, DecWrapper, Int, SCode, UInt, VariantBool};
+use super::variant::heap_variant;
+use super::vartype::VarType;
+
+/// Owned value of a VARIANT whose type is decided at runtime.
+///
+/// Each variant of the enum corresponds to a VARTYPE. Values are copied out of the
+/// VARIANT, so a `VariantValue` does not borrow from it. `Bstr` holds a copy of
+/// the string, while `Dispatch`, `Unknown`, `Array`, `ByRef` and `Record` hold the
+/// pointers as they were found - no reference counts are touched.
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::VariantValue;
+///
+/// fn main() {
+///     let pvar = VariantValue::I4(1337).into_raw().unwrap();
+///     let val = unsafe { VariantValue::from_raw(pvar.as_ptr()) }.unwrap();
+///     assert_eq!(val, VariantValue::I4(1337));
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum VariantValue {
+    /// VT_EMPTY
+    Empty,
+    /// VT_NULL
+    Null,
+    /// VT_I1
+    I1(i8),
+    /// VT_I2
+    I2(i16),
+    /// VT_I4
+    I4(i32),
+    /// VT_I8
+    I8(i64),
+    /// VT_UI1
+    UI1(u8),
+    /// VT_UI2
+    UI2(u16),
+    /// VT_UI4
+    UI4(u32),
+    /// VT_UI8
+    UI8(u64),
+    /// VT_INT
+    Int(Int),
+    /// VT_UINT
+    UInt(UInt),
+    /// VT_R4
+    R4(f32),
+    /// VT_R8
+    R8(f64),
+    /// VT_CY
+    Cy(Currency),
+    /// VT_DATE
+    Date(Date),
+    /// VT_BSTR
+    Bstr(String),
+    /// VT_BOOL
+    Bool(bool),
+    /// VT_ERROR
+    Error(SCode),
+    /// VT_DECIMAL
+    Decimal(DecWrapper),
+    /// VT_DISPATCH - `None` is the null pointer, eg `Nothing` in VB
+    Dispatch(Option<Ptr<IDispatch>>),
+    /// VT_UNKNOWN - `None` is the null pointer
+    Unknown(Option<Ptr<IUnknown>>),
+    /// VT_ARRAY combined with the element vartype
+    Array {
+        /// vartype of the elements, without the VT_ARRAY flag
+        vartype: VarType,
+        /// the SAFEARRAY holding the elements
+        array: Ptr<SAFEARRAY>
+    },
+    /// VT_BYREF combined with the vartype of the pointee
+    ByRef {
+        /// vartype of the pointee, without the VT_BYREF flag
+        vartype: VarType,
+        /// pointer to the value
+        pointer: Ptr<c_void>
+    },
+    /// VT_RECORD
+    Record {
+        /// pointer to the record data
+        record: Ptr<c_void>,
+        /// `IRecordInfo` describing the record
+        record_info: Ptr<IRecordInfo>
+    },
+}
+
+impl VariantValue {
+    /// The VARTYPE a VARIANT holding this value is marked with.
+    pub fn vartype(&self) -> VarType {
+        VarType::from(match *self {
+            VariantValue::Empty => VT_EMPTY,
+            VariantValue::Null => VT_NULL,
+            VariantValue::I1(_) => VT_I1,
+            VariantValue::I2(_) => VT_I2,
+            VariantValue::I4(_) => VT_I4,
+            VariantValue::I8(_) => VT_I8,
+            VariantValue::UI1(_) => VT_UI1,
+            VariantValue::UI2(_) => VT_UI2,
+            VariantValue::UI4(_) => VT_UI4,
+            VariantValue::UI8(_) => VT_UI8,
+            VariantValue::Int(_) => VT_INT,
+            VariantValue::UInt(_) => VT_UINT,
+            VariantValue::R4(_) => VT_R4,
+            VariantValue::R8(_) => VT_R8,
+            VariantValue::Cy(_) => VT_CY,
+            VariantValue::Date(_) => VT_DATE,
+            VariantValue::Bstr(_) => VT_BSTR,
+            VariantValue::Bool(_) => VT_BOOL,
+            VariantValue::Error(_) => VT_ERROR,
+            VariantValue::Decimal(_) => VT_DECIMAL,
+            VariantValue::Dispatch(_) => VT_DISPATCH,
+            VariantValue::Unknown(_) => VT_UNKNOWN,
+            VariantValue::Array{vartype, ..} => VT_ARRAY | u32::from(vartype),
+            VariantValue::ByRef{vartype, ..} => VT_BYREF | u32::from(vartype),
+            VariantValue::Record{..} => VT_RECORD,
+        })
+    }
+
+    /// Reads the value held by the VARIANT, dispatching on its `vt` field.
+    ///
+    /// The VARIANT is left untouched - it is neither cleared nor freed. A VT_BSTR holding
+    /// an unpaired surrogate fails with `FromVariantError::InvalidUtf16`.
+    ///
+    /// ## Safety
+    ///
+    /// `var` must be null or point to a valid, initialized VARIANT.
+    #[allow(clippy::redundant_field_names)]
+    pub unsafe fn from_raw(var: *const VARIANT) -> Result<VariantValue, FromVariantError> {
+        if var.is_null() {
+            return Err(FromVariantError::VariantPtrNull);
+        }
+        let n1 = (*var).n1;
+        let vt = n1.n2().vt as u32;
+        let n3 = n1.n2().n3;
+
+        if vt & VT_ARRAY != 0 && vt & VT_BYREF == 0 {
+            return match Ptr::with_checked(*n3.parray()) {
+                Some(array) => Ok(VariantValue::Array{vartype: VarType::from(vt & !VT_ARRAY), array: array}),
+                None => Err(FromVariantError::ArrayPtrNull)
+            };
+        }
+        if vt & VT_BYREF != 0 {
+            return match Ptr::with_checked(*n3.byref()) {
+                Some(pointer) => Ok(VariantValue::ByRef{vartype: VarType::from(vt & !VT_BYREF), pointer: pointer}),
+                None => Err(FromVariantError::CVoidPtrNull)
+            };
+        }
+
+        let val = match vt {
+            VT_EMPTY => VariantValue::Empty,
+            VT_NULL => VariantValue::Null,
+            VT_I1 => VariantValue::I1(*n3.cVal()),
+            VT_I2 => VariantValue::I2(*n3.iVal()),
+            VT_I4 => VariantValue::I4(*n3.lVal()),
+            VT_I8 => VariantValue::I8(*n3.llVal()),
+            VT_UI1 => VariantValue::UI1(*n3.bVal()),
+            VT_UI2 => VariantValue::UI2(*n3.uiVal()),
+            VT_UI4 => VariantValue::UI4(*n3.ulVal()),
+            VT_UI8 => VariantValue::UI8(*n3.ullVal()),
+            VT_INT => VariantValue::Int(Int::from(*n3.intVal())),
+            VT_UINT => VariantValue::UInt(UInt::from(*n3.uintVal())),
+            VT_R4 => VariantValue::R4(*n3.fltVal()),
+            VT_R8 => VariantValue::R8(*n3.dblVal()),
+            VT_CY => VariantValue::Cy(Currency::from(*n3.cyVal())),
+            VT_DATE => VariantValue::Date(Date::from(*n3.date())),
+            VT_BSTR => VariantValue::Bstr(BStr::from_ptr(*n3.bstrVal()).to_string_strict()?),
+            VT_BOOL => VariantValue::Bool(bool::from(VariantBool::from(*n3.boolVal()))),
+            VT_ERROR => VariantValue::Error(SCode::from(*n3.scode())),
+            VT_DECIMAL => VariantValue::Decimal(DecWrapper::from(*n1.decVal())),
+            VT_DISPATCH => VariantValue::Dispatch(Ptr::with_checked(*n3.pdispVal())),
+            VT_UNKNOWN => VariantValue::Unknown(Ptr::with_checked(*n3.punkVal())),
+            VT_RECORD => {
+                let brecord = n3.n4();
+                let record = match Ptr::with_checked(brecord.pvRecord) {
+                    Some(record) => record,
+                    None => return Err(FromVariantError::CVoidPtrNull)
+                };
+                let record_info = match Ptr::with_checked(brecord.pRecInfo) {
+                    Some(record_info) => record_info,
+                    None => return Err(FromVariantError::RecordInfoPtrNull)
+                };
+                VariantValue::Record{record: record, record_info: record_info}
+            },
+            _ => return Err(FromVariantError::VarTypeNotSupported{found: VarType::from(vt)})
+        };
+        Ok(val)
+    }
+
+    /// Converts the value into a newly allocated VARIANT.
+    ///
+    /// `Bstr` values are copied into a BSTR allocated with the Sys* functions.
+    pub fn into_raw(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_raw");
+        Ok(heap_variant(self.into_raw_variant()?))
+    }
+
+    /// Converts the value into a VARIANT on the stack, owning the BSTR of a `Bstr` value
+    pub(crate) fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+        let vt = u32::from(self.vartype());
+        let mut var: VARIANT = unsafe {mem::zeroed()};
+        unsafe {
+            match self {
+                VariantValue::Empty | VariantValue::Null => {},
+                VariantValue::I1(v) => *n3_mut(&mut var).cVal_mut() = v,
+                VariantValue::I2(v) => *n3_mut(&mut var).iVal_mut() = v,
+                VariantValue::I4(v) => *n3_mut(&mut var).lVal_mut() = v,
+                VariantValue::I8(v) => *n3_mut(&mut var).llVal_mut() = v,
+                VariantValue::UI1(v) => *n3_mut(&mut var).bVal_mut() = v,
+                VariantValue::UI2(v) => *n3_mut(&mut var).uiVal_mut() = v,
+                VariantValue::UI4(v) => *n3_mut(&mut var).ulVal_mut() = v,
+                VariantValue::UI8(v) => *n3_mut(&mut var).ullVal_mut() = v,
+                VariantValue::Int(v) => *n3_mut(&mut var).intVal_mut() = i32::from(v),
+                VariantValue::UInt(v) => *n3_mut(&mut var).uintVal_mut() = u32::from(v),
+                VariantValue::R4(v) => *n3_mut(&mut var).fltVal_mut() = v,
+                VariantValue::R8(v) => *n3_mut(&mut var).dblVal_mut() = v,
+                VariantValue::Cy(v) => *n3_mut(&mut var).cyVal_mut() = v.into(),
+                VariantValue::Date(v) => *n3_mut(&mut var).date_mut() = v.into(),
+                VariantValue::Bstr(s) => {
+                    *n3_mut(&mut var).bstrVal_mut() = BString::from_str(&s)?.into_raw();
+                },
+                VariantValue::Bool(v) => *n3_mut(&mut var).boolVal_mut() = VARIANT_BOOL::from(VariantBool::from(v)),
+                VariantValue::Error(v) => *n3_mut(&mut var).scode_mut() = i32::from(v),
+                // DECIMAL overlays the whole VARIANT, so vt is written after it below
+                VariantValue::Decimal(v) => *var.n1.decVal_mut() = DECIMAL::from(v),
+                VariantValue::Dispatch(p) => {
+                    *n3_mut(&mut var).pdispVal_mut() = p.map_or(null_mut(), |p| p.as_ptr())
+                },
+                VariantValue::Unknown(p) => {
+                    *n3_mut(&mut var).punkVal_mut() = p.map_or(null_mut(), |p| p.as_ptr())
+                },
+                VariantValue::Array{array, ..} => *n3_mut(&mut var).parray_mut() = array.as_ptr(),
+                VariantValue::ByRef{pointer, ..} => *n3_mut(&mut var).byref_mut() = pointer.as_ptr(),
+                VariantValue::Record{record, record_info} => {
+                    let brecord = n3_mut(&mut var).n4_mut();
+                    brecord.pvRecord = record.as_ptr();
+                    brecord.pRecInfo = record_info.as_ptr();
+                },
+            }
+            var.n1.n2_mut().vt = vt as u16;
+        }
+        Ok(var)
+    }
+}
+
+fn n3_mut(var: &mut VARIANT) -> &mut VARIANT_n3 {
+    unsafe { &mut var.n1.n2_mut().n3 }
+}
+
+macro_rules! value_from_impl {
+    ($($t:ty => $var:ident),* $(,)*) => {
+        $(
+            impl From<$t> for VariantValue {
+                fn from(v: $t) -> VariantValue {
+                    VariantValue::$var(v)
+                }
+            }
+        )*
+    };
+}
+
+value_from_impl!{
+    i8 => I1,
+    i16 => I2,
+    i32 => I4,
+    i64 => I8,
+    u8 => UI1,
+    u16 => UI2,
+    u32 => UI4,
+    u64 => UI8,
+    Int => Int,
+    UInt => UInt,
+    f32 => R4,
+    f64 => R8,
+    Currency => Cy,
+    Date => Date,
+    String => Bstr,
+    bool => Bool,
+    SCode => Error,
+    DecWrapper => Decimal,
+}
+
+#[allow(clippy::needless_lifetimes)]
+impl<'s> From<&'s str> for VariantValue {
+    fn from(s: &str) -> VariantValue {
+        VariantValue::Bstr(String::from(s))
+    }
+}
+
+impl From<Decimal> for VariantValue {
+    fn from(dec: Decimal) -> VariantValue {
+        VariantValue::Decimal(DecWrapper::from(dec))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    macro_rules! validate_value {
+        ($val:expr, $vt:expr) => {
+            let v = $val;
+            let var = v.clone().into_raw().unwrap();
+            unsafe {
+                let pvar = var.as_ptr();
+                assert_eq!((*pvar).n1.n2().vt as u32, $vt);
+            };
+            let r = unsafe { VariantValue::from_raw(var.as_ptr()) };
+            assert_eq!(v, r.unwrap());
+        };
+    }
+    #[test]
+    fn test_scalars() {
+        validate_value!(VariantValue::Empty, VT_EMPTY);
+        validate_value!(VariantValue::Null, VT_NULL);
+        validate_value!(VariantValue::I1(-3), VT_I1);
+        validate_value!(VariantValue::I2(1337), VT_I2);
+        validate_value!(VariantValue::I4(-1337), VT_I4);
+        validate_value!(VariantValue::I8(1 << 40), VT_I8);
+        validate_value!(VariantValue::UI1(137), VT_UI1);
+        validate_value!(VariantValue::UI2(1337), VT_UI2);
+        validate_value!(VariantValue::UI4(1337), VT_UI4);
+        validate_value!(VariantValue::UI8(1 << 40), VT_UI8);
+        validate_value!(VariantValue::Int(Int::from(-7)), VT_INT);
+        validate_value!(VariantValue::UInt(UInt::from(7)), VT_UINT);
+        validate_value!(VariantValue::R4(1337.9), VT_R4);
+        validate_value!(VariantValue::R8(-1337.9), VT_R8);
+        validate_value!(VariantValue::Cy(Currency::from(137)), VT_CY);
+        validate_value!(VariantValue::Date(Date::from(137.7)), VT_DATE);
+        validate_value!(VariantValue::Bool(true), VT_BOOL);
+        validate_value!(VariantValue::Error(SCode::from(-50)), VT_ERROR);
+    }
+    #[test]
+    fn test_bstr() {
+        validate_value!(VariantValue::from("testing abc1267 ?Ťũřǐꝥꞔ"), VT_BSTR);
+    }
+    #[test]
+    fn test_decimal() {
+        validate_value!(VariantValue::from(Decimal::new(-1337, 2)), VT_DECIMAL);
+    }
+    #[test]
+    fn test_null_dispatch() {
+        validate_value!(VariantValue::Dispatch(None), VT_DISPATCH);
+        validate_value!(VariantValue::Unknown(None), VT_UNKNOWN);
+    }
+    #[test]
+    fn test_byref() {
+        let mut i = 1337i32;
+        let p = Ptr::with_checked(&mut i as *mut i32 as *mut c_void).unwrap();
+        validate_value!(VariantValue::ByRef{vartype: VarType::from(VT_I4), pointer: p}, VT_BYREF | VT_I4);
+    }
+    #[test]
+    fn test_unsupported() {
+        let mut var: VARIANT = unsafe {mem::zeroed()};
+        unsafe { var.n1.n2_mut().vt = 0xFFF };
+        match unsafe { VariantValue::from_raw(&var) } {
+            Err(FromVariantError::VarTypeNotSupported{found}) => assert_eq!(found, 0xFFF),
+            r => panic!("unexpected result: {:?}", r)
+        }
+    }
+}
@@ -1,11 +1,13 @@
 //! Variant conversions
 //! 
-//! This module contains the trait [`VariantExt`] and the types [`Variant`], [`VtEmpty`], [`VtNull`].
+//! This module contains the trait [`VariantExt`] and the types [`OwnedVariant`], [`Variant`], [`VariantRef`], [`VtEmpty`], [`VtNull`].
 //! 
 //! It implements [`VariantExt`] for many built in types to enable conversions to VARIANT.  
 //! 
 //! [`VariantExt`]: trait.VariantExt.html
+//! [`OwnedVariant`]: struct.OwnedVariant.html
 //! [`Variant`]: struct.Variant.html
+//! [`VariantRef`]: struct.VariantRef.html
 //! [`VtEmpty`]: struct.VtEmpty.html
 //! [`VtNull`]: struct.VtNull.html
 
@@ -130,55 +132,64 @@
 *  VT_BYREF            [V]           void* for local use
 *  VT_BSTR_BLOB                      Reserved for system use
 */
+use std::ffi::OsString;
+use std::fmt;
 use std::marker::PhantomData;
 use std::mem;
-use std::ptr::{NonNull, null_mut};
+use std::ptr::{self, NonNull, null_mut};
+use std::slice;
 
 use rust_decimal::Decimal;
 
-use widestring::U16String;
-
-use winapi::ctypes::c_void;
-use winapi::shared::wtypes::{
-    CY, DATE, DECIMAL,
+use super::alloc::allocator;
+use super::array::{SafeArrayElement, SafeArrayExt};
+use super::bstr::{BStr, BString};
+use super::errors::{IntoVariantError, FromVariantError};
+use super::ptr::Ptr;
+use super::sys::{
+    CY,
+    DATE,
+    DECIMAL,
+    IDispatch,
+    IUnknown,
+    SAFEARRAY,
+    SCODE,
+    VARIANT,
     VARIANT_BOOL,
-    VT_ARRAY, 
-    VT_BSTR, 
+    VARIANT_n1,
+    VARIANT_n3,
+    __tagVARIANT,
+    VT_ARRAY,
     VT_BOOL,
-    VT_BYREF, 
+    VT_BSTR,
+    VT_BYREF,
     VT_CY,
     VT_DATE,
-    VT_DECIMAL, 
+    VT_DECIMAL,
     VT_DISPATCH,
-    VT_EMPTY, 
-    VT_ERROR, 
-    VT_I1, 
-    VT_I2, 
+    VT_EMPTY,
+    VT_ERROR,
+    VT_I1,
+    VT_I2,
     VT_I4,
     VT_I8,
-    VT_INT,  
+    VT_INT,
     VT_NULL,
-    VT_R4, 
-    VT_R8, 
-    //VT_RECORD,
+    VT_R4,
+    VT_R8,
     VT_UI1,
     VT_UI2,
     VT_UI4,
-    VT_UI8,  
-    VT_UINT, 
-    VT_UNKNOWN, 
-    VT_VARIANT, 
+    VT_UI8,
+    VT_UINT,
+    VT_UNKNOWN,
+    VT_VARIANT,
+    c_void,
 };
-use winapi::shared::wtypesbase::SCODE;
-use winapi::um::oaidl::{IDispatch,  __tagVARIANT, SAFEARRAY, VARIANT, VARIANT_n3, VARIANT_n1};
-use winapi::um::oleauto::VariantClear;
-use winapi::um::unknwnbase::IUnknown;
-
-use super::array::{SafeArrayElement, SafeArrayExt};
-use super::bstr::BStringExt;
-use super::errors::{IntoVariantError, FromVariantError};
-use super::ptr::Ptr;
+use super::tracking::{self, AllocKind};
 use super::types::{Date, DecWrapper, Currency, Int, SCode, UInt, VariantBool };
+use super::value::VariantValue;
+use super::vartype::VarType;
 
 const VT_PUI1:      u32 = VT_BYREF | VT_UI1;
 const VT_PI2:       u32 = VT_BYREF | VT_I2;
@@ -210,8 +221,75 @@ pub trait VariantExt: Sized { //Would like Clone, but *mut IDispatch and *mut IU
     /// Call this associated function on a Ptr<VARIANT> to obtain a value T
     fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError>;  
 
+    /// Whether `from_variant` reads VARIANTs of vartype `vt`. By default only
+    /// `Self::VARTYPE`; arrays also take the other element vartypes their elements
+    /// accept, see [`SafeArrayElement::accepts_vartype`].
+    ///
+    /// [`SafeArrayElement::accepts_vartype`]: trait.SafeArrayElement.html#method.accepts_vartype
+    fn accepts_vartype(vt: u32) -> bool {
+        vt == Self::VARTYPE
+    }
+
     /// Convert a value of type T into a Ptr<VARIANT>
     fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError>;
+
+    /// Convert a value of type T into a VARIANT returned by value. Unlike `into_variant`
+    /// the VARIANT itself is not heap allocated.
+    fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+        let pvar = self.into_variant()?;
+        tracking::note_free(AllocKind::Variant, pvar.as_ptr() as *const c_void);
+        Ok(*unsafe { Box::from_raw(pvar.as_ptr()) })
+    }
+
+    /// Convert a value of type T into the VARIANT `dst` points at, for example an
+    /// `[out, retval] VARIANT*`. The previous contents are released with `VariantClear`
+    /// first. If the conversion or the clear fails `dst` is left untouched and the
+    /// converted value is released again.
+    ///
+    /// ## Safety
+    /// `dst` must point to an initialized VARIANT - use VT_EMPTY for fresh storage.
+    #[allow(clippy::redundant_field_names)]
+    unsafe fn write_into(self, dst: *mut VARIANT) -> Result<(), IntoVariantError> {
+        if dst.is_null() {
+            return Err(IntoVariantError::VariantPtrNull);
+        }
+        let _tag = tracking::tag::<Self>("write_into");
+        let mut var = self.into_raw_variant()?;
+        let hr = allocator().variant_clear(dst);
+        if hr != 0 {
+            allocator().variant_clear(&mut var);
+            return Err(IntoVariantError::VariantClearFailed{hr: hr});
+        }
+        ptr::write(dst, var);
+        Ok(())
+    }
+
+    /// Like `from_variant`, but a VARIANT of a vartype `Self` does not accept is first coerced to
+    /// `Self::VARTYPE` following the OLE Automation conversion rules, as
+    /// `VariantChangeType` would.
+    ///
+    /// The VARIANT is handed over exactly as to `from_variant`, whether or not it needs
+    /// coercing: it is cleared if the conversion fails, and otherwise left for the caller
+    /// to clear. The coercion works on a copy.
+    ///
+    /// The conversion is implemented in Rust and does not call into OleAut32.
+    fn from_variant_coerced(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let vt = unsafe { (*var.as_ptr()).n1.n2().vt };
+        if Self::accepts_vartype(vt as u32) {
+            return Self::from_variant(var);
+        }
+        let coerced = unsafe { VariantValue::from_raw(var.as_ptr()) }
+            .and_then(|value| Ok(value.change_type(VarType::from(Self::VARTYPE))?))
+            .and_then(|value| Ok(OwnedVariant::from_value(value)?));
+        let ret = match coerced {
+            Ok(mut coerced) => Self::from_variant(Ptr::with_checked(coerced.as_mut_ptr()).unwrap()),
+            Err(fve) => Err(fve)
+        };
+        if ret.is_err() {
+            unsafe { allocator().variant_clear(var.as_ptr()) };
+        }
+        ret
+    }
 }
 
 /// Helper struct to wrap a VARIANT compatible type into a VT_VARIANT marked VARIANT
@@ -233,18 +311,22 @@ impl<T: VariantExt> Variant<T> {
     }
 
     /// Borrow reference to inner value
+    #[allow(clippy::should_implement_trait)]
     pub fn borrow(&self) -> &T {
         &self.0
     }
 
     /// Borrow mutable reference to inner value
+    #[allow(clippy::should_implement_trait)]
     pub fn borrow_mut(&mut self) -> &mut T {
         &mut self.0
     }
 
     /// Converts the `Variant<T>` into a `Ptr<VARIANT>`
     /// Returns `Result<Ptr<VARIANT>, IntoVariantError>`
+    #[allow(clippy::redundant_field_names)]
     pub fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
         #[allow(unused_mut)]
         let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
         let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
@@ -264,13 +346,13 @@ impl<T: VariantExt> Variant<T> {
             let n_ptr = n1.n2_mut();
             *n_ptr = tv;
         };
-        let var = Box::new(VARIANT{ n1: n1 });
-        Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
+        Ok(heap_variant(VARIANT{ n1: n1 }))
     }
 
     /// Converts `Ptr<VARIANT>` into  `Variant<T>` 
     /// Returns `Result<Variant<T>>, FromVariantError>`
     pub fn from_variant(var: Ptr<VARIANT>) -> Result<Variant<T>, FromVariantError> {
+        let _tag = tracking::tag::<Self>("from_variant");
         let var = var.as_ptr();
         let mut _var_d = VariantDestructor::new(var);
 
@@ -303,6 +385,63 @@ impl<T: VariantExt> AsMut<T> for Variant<T> {
     }
 }
 
+/// Moves a VARIANT to the heap, as handed out by `into_variant`. Free it with
+/// `OwnedVariant::from_raw`.
+pub(crate) fn heap_variant(var: VARIANT) -> Ptr<VARIANT> {
+    let pvar = Box::into_raw(Box::new(var));
+    tracking::note_alloc(AllocKind::Variant, pvar as *const c_void);
+    Ptr::with_checked(pvar).unwrap()
+}
+
+/// Detaches the SAFEARRAY from an array VARIANT and clears the VARIANT, checking that
+/// it has `vartype`. The caller owns the SAFEARRAY.
+pub(crate) fn take_safearray(var: Ptr<VARIANT>, vartype: u32) -> Result<*mut SAFEARRAY, FromVariantError> {
+    let var = var.as_ptr();
+    let _var_d = VariantDestructor::new(var);
+
+    let n2 = unsafe { (*var).n1.n2_mut() };
+    let vt = n2.vt as u32;
+    if vt != vartype {
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VarType::from(vartype), found: VarType::from(vt)});
+    }
+    let psa = unsafe { *n2.n3.parray() };
+    if psa.is_null() {
+        return Err(FromVariantError::ArrayPtrNull);
+    }
+    // The SAFEARRAY is handed to the caller, so the VARIANT must not hold on to it
+    n2.vt = VT_EMPTY as u16;
+    unsafe { *n2.n3.parray_mut() = null_mut() };
+    Ok(psa)
+}
+
+/// Whether `vt` marks an array VARIANT, not VT_BYREF, of elements `T` accepts
+pub(crate) fn array_accepts<T: SafeArrayElement>(vt: u32) -> bool {
+    vt & VT_ARRAY != 0 && vt & VT_BYREF == 0 && T::accepts_vartype(vt & !VT_ARRAY)
+}
+
+/// Detaches the SAFEARRAY from an array VARIANT and clears the VARIANT, checking that
+/// it is marked VT_ARRAY with an element vartype `T` accepts. The caller owns the
+/// SAFEARRAY.
+pub(crate) fn take_array<T: SafeArrayElement>(var: Ptr<VARIANT>) -> Result<*mut SAFEARRAY, FromVariantError> {
+    let vt = unsafe { (*var.as_ptr()).n1.n2().vt } as u32;
+    if !array_accepts::<T>(vt) {
+        let _var_d = VariantDestructor::new(var.as_ptr());
+        return Err(FromVariantError::VarTypeDoesNotMatch{expected: VarType::from(VT_ARRAY | T::SFTYPE), found: VarType::from(vt)});
+    }
+    take_safearray(var, vt)
+}
+
+/// An array VARIANT of `vartype` owning `psa`
+pub(crate) fn safearray_variant(psa: Ptr<SAFEARRAY>, vartype: u32) -> VARIANT {
+    let mut var: VARIANT = unsafe { mem::zeroed() };
+    unsafe {
+        let n2 = var.n1.n2_mut();
+        n2.vt = vartype as u16;
+        *n2.n3.parray_mut() = psa.as_ptr();
+    }
+    var
+}
+
 struct VariantDestructor {
     inner: *mut VARIANT, 
     _marker: PhantomData<VARIANT>
@@ -322,12 +461,328 @@ impl Drop for VariantDestructor {
         if self.inner.is_null() {
             return;
         }
-        unsafe { VariantClear(self.inner)};
+        unsafe { allocator().variant_clear(self.inner)};
         unsafe { let _dtor = *self.inner;}
         self.inner = null_mut();
     }
 }
 
+/// Owning container for a VARIANT allocated by this crate.
+///
+/// When dropped, the contents are released with `VariantClear` - BSTRs are freed,
+/// interface pointers are released and SAFEARRAYs are destroyed - and then the
+/// VARIANT itself is freed.
+///
+/// Use [`into_raw`] to hand the VARIANT across FFI, and [`from_raw`] to take
+/// ownership of one again.
+///
+/// [`into_raw`]: #method.into_raw
+/// [`from_raw`]: #method.from_raw
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{OwnedVariant, VariantValue};
+///
+/// fn main() {
+///     let var = OwnedVariant::new(String::from("Turing completeness.")).unwrap();
+///     assert_eq!(var.value().unwrap(), VariantValue::from("Turing completeness."));
+///     // the BSTR and the VARIANT are freed here
+/// }
+/// ```
+pub struct OwnedVariant {
+    inner: Box<VARIANT>
+}
+
+impl OwnedVariant {
+    /// Converts `t` into a VARIANT owned by the returned container.
+    pub fn new<T: VariantExt>(t: T) -> Result<OwnedVariant, IntoVariantError> {
+        let pvar = t.into_variant()?;
+        Ok(unsafe { OwnedVariant::from_raw(pvar) })
+    }
+
+    /// Converts a [`VariantValue`] into a VARIANT owned by the returned container.
+    ///
+    /// [`VariantValue`]: enum.VariantValue.html
+    pub fn from_value(val: VariantValue) -> Result<OwnedVariant, IntoVariantError> {
+        let pvar = val.into_raw()?;
+        Ok(unsafe { OwnedVariant::from_raw(pvar) })
+    }
+
+    /// Creates a VT_EMPTY VARIANT.
+    pub fn empty() -> OwnedVariant {
+        let pvar = heap_variant(unsafe { mem::zeroed() });
+        unsafe { OwnedVariant::from_raw(pvar) }
+    }
+
+    /// Takes ownership of a VARIANT.
+    ///
+    /// ## Safety
+    ///
+    /// `var` must have been allocated by this crate, for example by [`VariantExt::into_variant`],
+    /// [`VariantValue::into_raw`] or [`OwnedVariant::into_raw`], and must not be owned by anything else.
+    ///
+    /// [`VariantExt::into_variant`]: trait.VariantExt.html#tymethod.into_variant
+    /// [`VariantValue::into_raw`]: enum.VariantValue.html#method.into_raw
+    /// [`OwnedVariant::into_raw`]: #method.into_raw
+    pub unsafe fn from_raw(var: Ptr<VARIANT>) -> OwnedVariant {
+        OwnedVariant { inner: Box::from_raw(var.as_ptr()) }
+    }
+
+    /// Releases ownership of the VARIANT without clearing it.
+    /// It is your responsibility to free it, eg by passing it to [`from_raw`]
+    /// or to an FFI function that takes ownership.
+    ///
+    /// [`from_raw`]: #method.from_raw
+    pub fn into_raw(self) -> Ptr<VARIANT> {
+        let mut slf = mem::ManuallyDrop::new(self);
+        let pvar: *mut VARIANT = &mut *slf.inner;
+        Ptr::with_checked(pvar).unwrap()
+    }
+
+    /// Pointer to the owned VARIANT, for passing as an `[in] VARIANT*` argument
+    pub fn as_ptr(&self) -> *const VARIANT {
+        &*self.inner
+    }
+
+    /// Mutable pointer to the owned VARIANT, for passing as an `[in, out] VARIANT*` argument
+    pub fn as_mut_ptr(&mut self) -> *mut VARIANT {
+        &mut *self.inner
+    }
+
+    /// The vartype of the owned VARIANT
+    pub fn vartype(&self) -> VarType {
+        VarType::from(unsafe { self.inner.n1.n2().vt })
+    }
+
+    /// Reads the contents as a [`VariantValue`], leaving the VARIANT untouched.
+    ///
+    /// [`VariantValue`]: enum.VariantValue.html
+    pub fn value(&self) -> Result<VariantValue, FromVariantError> {
+        unsafe { VariantValue::from_raw(self.as_ptr()) }
+    }
+}
+
+impl fmt::Debug for OwnedVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedVariant").field("vt", &self.vartype()).finish()
+    }
+}
+
+impl Default for OwnedVariant {
+    fn default() -> OwnedVariant {
+        OwnedVariant::empty()
+    }
+}
+
+impl Drop for OwnedVariant {
+    fn drop(&mut self) {
+        let _tag = tracking::tag::<Self>("drop");
+        unsafe { allocator().variant_clear(&mut *self.inner) };
+        tracking::note_free(AllocKind::Variant, &*self.inner as *const VARIANT as *const c_void);
+    }
+}
+
+/// Borrowed view of a VARIANT owned by someone else, eg an `[in] VARIANT*` argument.
+///
+/// Unlike [`VariantExt::from_variant`], nothing is ever cleared or freed. The typed
+/// getters check the vartype exactly like the `VariantExt` impls do, and return
+/// `FromVariantError::VarTypeDoesNotMatch` on a mismatch.
+///
+/// [`VariantExt::from_variant`]: trait.VariantExt.html#tymethod.from_variant
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{OwnedVariant, VariantRef};
+///
+/// fn main() {
+///     let var = OwnedVariant::new(1337i32).unwrap();
+///     let vref = unsafe { VariantRef::from_raw(var.as_ptr()) }.unwrap();
+///     assert_eq!(vref.as_i32().unwrap(), 1337);
+///     assert!(vref.as_f64().is_err());
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct VariantRef<'a> {
+    inner: &'a VARIANT
+}
+
+macro_rules! variant_ref_getters {
+    ($(
+        $(#[$attrs:meta])*
+        fn $name:ident -> $t:ty {
+            VARTYPE = $vt:expr;
+            $un_n:ident => $from:expr
+        }
+    )*) => {
+        $(
+            $(#[$attrs])*
+            pub fn $name(&self) -> Result<$t, FromVariantError> {
+                self.check_vartype($vt)?;
+                let n3 = unsafe { self.inner.n1.n2().n3 };
+                let val = unsafe { n3.$un_n() };
+                Ok($from(*val))
+            }
+        )*
+    };
+}
+
+impl<'a> VariantRef<'a> {
+    /// Wraps a reference to a VARIANT
+    pub fn new(var: &'a VARIANT) -> VariantRef<'a> {
+        VariantRef { inner: var }
+    }
+
+    /// Wraps a `*const VARIANT`, failing with `FromVariantError::VariantPtrNull` if it is null.
+    ///
+    /// ## Safety
+    ///
+    /// `var` must point to a valid, initialized VARIANT which outlives `'a`.
+    pub unsafe fn from_raw(var: *const VARIANT) -> Result<VariantRef<'a>, FromVariantError> {
+        if var.is_null() {
+            return Err(FromVariantError::VariantPtrNull);
+        }
+        Ok(VariantRef { inner: &*var })
+    }
+
+    /// Pointer to the borrowed VARIANT
+    pub fn as_ptr(&self) -> *const VARIANT {
+        self.inner
+    }
+
+    /// The vartype of the borrowed VARIANT
+    pub fn vartype(&self) -> VarType {
+        VarType::from(unsafe { self.inner.n1.n2().vt })
+    }
+
+    /// Whether the VARIANT is VT_EMPTY
+    pub fn is_empty(&self) -> bool {
+        self.vartype() == VT_EMPTY
+    }
+
+    /// Whether the VARIANT is VT_NULL
+    pub fn is_null(&self) -> bool {
+        self.vartype() == VT_NULL
+    }
+
+    /// Copies the contents out as a [`VariantValue`].
+    ///
+    /// [`VariantValue`]: enum.VariantValue.html
+    pub fn value(&self) -> Result<VariantValue, FromVariantError> {
+        unsafe { VariantValue::from_raw(self.inner) }
+    }
+
+    #[allow(clippy::redundant_field_names)]
+    fn check_vartype(&self, expected: u32) -> Result<(), FromVariantError> {
+        let found = self.vartype();
+        if found != expected {
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: VarType::from(expected), found: found});
+        }
+        Ok(())
+    }
+
+    variant_ref_getters!{
+        /// Reads a VT_I1 value
+        fn as_i8 -> i8 { VARTYPE = VT_I1; cVal => |v| v }
+        /// Reads a VT_I2 value
+        fn as_i16 -> i16 { VARTYPE = VT_I2; iVal => |v| v }
+        /// Reads a VT_I4 value
+        fn as_i32 -> i32 { VARTYPE = VT_I4; lVal => |v| v }
+        /// Reads a VT_I8 value
+        fn as_i64 -> i64 { VARTYPE = VT_I8; llVal => |v| v }
+        /// Reads a VT_UI1 value
+        fn as_u8 -> u8 { VARTYPE = VT_UI1; bVal => |v| v }
+        /// Reads a VT_UI2 value
+        fn as_u16 -> u16 { VARTYPE = VT_UI2; uiVal => |v| v }
+        /// Reads a VT_UI4 value
+        fn as_u32 -> u32 { VARTYPE = VT_UI4; ulVal => |v| v }
+        /// Reads a VT_UI8 value
+        fn as_u64 -> u64 { VARTYPE = VT_UI8; ullVal => |v| v }
+        /// Reads a VT_INT value
+        fn as_int -> Int { VARTYPE = VT_INT; intVal => Int::from }
+        /// Reads a VT_UINT value
+        fn as_uint -> UInt { VARTYPE = VT_UINT; uintVal => UInt::from }
+        /// Reads a VT_R4 value
+        fn as_f32 -> f32 { VARTYPE = VT_R4; fltVal => |v| v }
+        /// Reads a VT_R8 value
+        fn as_f64 -> f64 { VARTYPE = VT_R8; dblVal => |v| v }
+        /// Reads a VT_BOOL value
+        fn as_bool -> bool { VARTYPE = VT_BOOL; boolVal => |v: VARIANT_BOOL| bool::from(VariantBool::from(v)) }
+        /// Reads a VT_ERROR value
+        fn as_scode -> SCode { VARTYPE = VT_ERROR; scode => SCode::from }
+        /// Reads a VT_CY value
+        fn as_currency -> Currency { VARTYPE = VT_CY; cyVal => Currency::from }
+        /// Reads a VT_DATE value
+        fn as_date -> Date { VARTYPE = VT_DATE; date => Date::from }
+        /// Reads a VT_DISPATCH value, `None` if the pointer is null
+        fn as_dispatch -> Option<Ptr<IDispatch>> { VARTYPE = VT_DISPATCH; pdispVal => Ptr::with_checked }
+        /// Reads a VT_UNKNOWN value, `None` if the pointer is null
+        fn as_unknown -> Option<Ptr<IUnknown>> { VARTYPE = VT_UNKNOWN; punkVal => Ptr::with_checked }
+    }
+
+    /// Reads a VT_DECIMAL value
+    pub fn as_decimal(&self) -> Result<DecWrapper, FromVariantError> {
+        self.check_vartype(VT_DECIMAL)?;
+        Ok(DecWrapper::from(unsafe { self.inner.n1.decVal() }))
+    }
+
+    /// Borrows the UTF-16 contents of a VT_BSTR value without copying.
+    /// A null BSTR is treated as the empty string.
+    pub fn as_bstr(&self) -> Result<&'a [u16], FromVariantError> {
+        self.check_vartype(VT_BSTR)?;
+        let bstr = unsafe { *self.inner.n1.n2().n3.bstrVal() };
+        if bstr.is_null() {
+            return Ok(&[]);
+        }
+        Ok(unsafe { slice::from_raw_parts(bstr, allocator().sys_string_len(bstr) as usize) })
+    }
+
+    /// Copies a VT_BSTR value into a `String`, failing with `FromVariantError::InvalidUtf16`
+    /// if it holds an unpaired surrogate. Use [`as_os_string`] to keep such strings.
+    ///
+    /// [`as_os_string`]: #method.as_os_string
+    pub fn as_string(&self) -> Result<String, FromVariantError> {
+        self.check_vartype(VT_BSTR)?;
+        unsafe { BStr::from_ptr(*self.inner.n1.n2().n3.bstrVal()) }.to_string_strict()
+    }
+
+    /// Copies a VT_BSTR value into an `OsString`, keeping unpaired surrogates
+    pub fn as_os_string(&self) -> Result<OsString, FromVariantError> {
+        self.check_vartype(VT_BSTR)?;
+        Ok(unsafe { BStr::from_ptr(*self.inner.n1.n2().n3.bstrVal()) }.to_os_string())
+    }
+
+    /// Borrows the SAFEARRAY of a VT_ARRAY value, whatever its element type.
+    pub fn as_array(&self) -> Result<&'a SAFEARRAY, FromVariantError> {
+        let vt = self.vartype();
+        if !vt.is_array() || vt.is_byref() {
+            return Err(FromVariantError::VarTypeDoesNotMatch{expected: VarType::from(VT_ARRAY), found: vt});
+        }
+        let psa = unsafe { *self.inner.n1.n2().n3.parray() };
+        if psa.is_null() {
+            return Err(FromVariantError::ArrayPtrNull);
+        }
+        Ok(unsafe { &*psa })
+    }
+
+    /// Follows a VT_BYREF|VT_VARIANT value to the VARIANT it points to.
+    pub fn as_variant(&self) -> Result<VariantRef<'a>, FromVariantError> {
+        self.check_vartype(VT_BYREF | VT_VARIANT)?;
+        unsafe { VariantRef::from_raw(*self.inner.n1.n2().n3.pvarVal()) }
+    }
+}
+
+impl<'a> fmt::Debug for VariantRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VariantRef").field("vt", &self.vartype()).finish()
+    }
+}
+
 macro_rules! variant_impl {
     (
         impl $(<$tn:ident : $tc:ident>)* VariantExt for $t:ty {
@@ -340,6 +795,7 @@ macro_rules! variant_impl {
         impl $(<$tn: $tc>)* VariantExt for $t {
             const VARTYPE: u32 = $vt;
             fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError>{
+                let _tag = tracking::tag::<Self>("from_variant");
                 let var = var.as_ptr();
                 let mut var_d = VariantDestructor::new(var);
 
@@ -347,8 +803,9 @@ macro_rules! variant_impl {
                 let mut n1 = unsafe {(*var).n1};
                 let vt = unsafe{n1.n2()}.vt;
                 if vt as u32 != Self::VARTYPE {
-                    return Err(FromVariantError::VarTypeDoesNotMatch{expected: Self::VARTYPE, found: vt as u32})
+                    return Err(FromVariantError::VarTypeDoesNotMatch{expected: VarType::from(Self::VARTYPE), found: VarType::from(vt as u32)})
                 }
+                #[allow(clippy::clone_on_copy)]
                 let ret = variant_impl!(@read $n_name, $un_n, $from, n1);
 
                 var_d.inner = null_mut();
@@ -356,6 +813,13 @@ macro_rules! variant_impl {
             }
 
             fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+                let _tag = tracking::tag::<Self>("into_variant");
+                Ok(heap_variant(self.into_raw_variant()?))
+            }
+
+            #[allow(clippy::redundant_field_names)]
+            fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+                let _tag = tracking::tag::<Self>("into_variant");
                 #[allow(unused_mut)]
                 let mut n3: VARIANT_n3 = unsafe {mem::zeroed()};
                 let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
@@ -369,8 +833,11 @@ macro_rules! variant_impl {
                     let n_ptr = n1.n2_mut();
                     *n_ptr = tv;
                 };
-                let var = Box::new(VARIANT{ n1: n1 });
-                Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
+                // `Box<T>` impls allocate what they point at, `Ptr<c_void>` does not
+                if Self::VARTYPE & VT_BYREF != 0 && Self::VARTYPE != VT_BYREF {
+                    tracking::note_alloc(AllocKind::ByRef, unsafe { *n3.byref() });
+                }
+                Ok(VARIANT{ n1: n1 })
             }
         }
     };
@@ -495,16 +962,19 @@ variant_impl!{
     impl VariantExt for String {
         VARTYPE = VT_BSTR;
         n3, bstrVal, bstrVal_mut
-        from => {|n_ptr: &*mut u16| {
-            let bstr = U16String::from_bstr(*n_ptr);
-            Ok(bstr.to_string_lossy())
+        from => {|n_ptr: &*mut u16| BStr::from_ptr(*n_ptr).to_string_strict()}
+        into => {|slf: String| -> Result<_, IntoVariantError> {
+            Ok(BString::from_str(&slf)?.into_raw())
         }}
-        into => {|slf: String|{
-            let mut bstr = U16String::from_str(&slf);
-            match bstr.allocate_bstr(){
-                Ok(ptr) => Ok(ptr.as_ptr()), 
-                Err(bse) => Err(IntoVariantError::from(bse))
-            }
+    }
+}
+variant_impl!{
+    impl VariantExt for OsString {
+        VARTYPE = VT_BSTR;
+        n3, bstrVal, bstrVal_mut
+        from => {|n_ptr: &*mut u16| Ok(BStr::from_ptr(*n_ptr).to_os_string())}
+        into => {|slf: OsString| -> Result<_, IntoVariantError> {
+            Ok(BString::from_os_str(&slf)?.into_raw())
         }}
     }
 }
@@ -638,12 +1108,11 @@ variant_impl!{
         VARTYPE = VT_PBSTR;
         n3, pbstrVal, pbstrVal_mut
         from => {|n_ptr: &*mut *mut u16| {
-            let bstr = U16String::from_bstr(**n_ptr);
-            Ok(Box::new(bstr.to_string_lossy()))
+            let s = BStr::from_ptr(**n_ptr).to_string_strict()?;
+            Ok(Box::new(s))
         }}
         into => {|slf: Box<String>| -> Result<_, IntoVariantError> {
-            let mut bstr = U16String::from_str(&*slf);
-            let bstr = Box::new(bstr.allocate_bstr().unwrap().as_ptr());
+            let bstr = Box::new(BString::from_str(&slf)?.into_raw());
             Ok(Box::into_raw(bstr))
         }}
     }
@@ -703,31 +1172,34 @@ variant_impl!{
         }}
     }
 }
-variant_impl!{
-    impl<T: SafeArrayElement> VariantExt for Vec<T>{
-        VARTYPE = VT_ARRAY;
-        n3, parray, parray_mut
-        from => {
-            |n_ptr: &*mut SAFEARRAY| {
-                match ExactSizeIterator::<Item=T>::from_safearray(*n_ptr) {
-                    Ok(sa) => Ok(sa), 
-                    Err(fsae) => Err(FromVariantError::from(fsae))
-                }
-            }
-        }
-        into => {
-            |slf: Vec<T>| -> Result<_, IntoVariantError> {
-                match slf.into_iter().into_safearray() {
-                    Ok(psa) => {
-                        Ok(psa.as_ptr())
-                    }, 
-                    Err(isae) => {
-                        Err(IntoVariantError::from(isae))
-                    }
-                }
-            }
+impl<T: SafeArrayElement> VariantExt for Vec<T> {
+    const VARTYPE: u32 = VT_ARRAY | T::SFTYPE;
+    fn accepts_vartype(vt: u32) -> bool {
+        array_accepts::<T>(vt)
+    }
+
+    fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
+        let _tag = tracking::tag::<Self>("from_variant");
+        let psa = take_array::<T>(var)?;
+        match <dyn ExactSizeIterator<Item=T>>::from_safearray(psa) {
+            Ok(sa) => Ok(sa), 
+            Err(fsae) => Err(FromVariantError::from(fsae))
         }
     }
+
+    fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        Ok(heap_variant(self.into_raw_variant()?))
+    }
+
+    fn into_raw_variant(self) -> Result<VARIANT, IntoVariantError> {
+        let _tag = tracking::tag::<Self>("into_variant");
+        let psa = match self.into_iter().into_safearray() {
+            Ok(psa) => psa, 
+            Err(isae) => return Err(IntoVariantError::from(isae))
+        };
+        Ok(safearray_variant(psa, Self::VARTYPE))
+    }
 }
 variant_impl!{
     impl VariantExt for Ptr<c_void> {
@@ -904,6 +1376,7 @@ pub struct VtNull{}
 
 impl VariantExt for VtEmpty {
     const VARTYPE: u32 = VT_EMPTY;
+    #[allow(clippy::redundant_field_names)]
     fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
         let n3: VARIANT_n3 = unsafe {mem::zeroed()};
         let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
@@ -917,8 +1390,7 @@ impl VariantExt for VtEmpty {
             let n_ptr = n1.n2_mut();
             *n_ptr = tv;
         };
-        let var = Box::new(VARIANT{ n1: n1 });
-        Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
+        Ok(heap_variant(VARIANT{ n1: n1 }))
     }
     fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
         let _var_d = VariantDestructor::new(var.as_ptr());
@@ -928,6 +1400,7 @@ impl VariantExt for VtEmpty {
 
 impl VariantExt for VtNull {
     const VARTYPE: u32 = VT_NULL;
+    #[allow(clippy::redundant_field_names)]
     fn into_variant(self) -> Result<Ptr<VARIANT>, IntoVariantError> {
         let n3: VARIANT_n3 = unsafe {mem::zeroed()};
         let mut n1: VARIANT_n1 = unsafe {mem::zeroed()};
@@ -941,8 +1414,7 @@ impl VariantExt for VtNull {
             let n_ptr = n1.n2_mut();
             *n_ptr = tv;
         };
-        let var = Box::new(VARIANT{ n1: n1 });
-        Ok(Ptr::with_checked(Box::into_raw(var)).unwrap())
+        Ok(heap_variant(VARIANT{ n1: n1 }))
     }
     fn from_variant(var: Ptr<VARIANT>) -> Result<Self, FromVariantError> {
         let _var_d = VariantDestructor::new(var.as_ptr());
@@ -953,6 +1425,7 @@ impl VariantExt for VtNull {
 #[cfg(test)]
 mod test {
     use super::*;
+    use super::super::tracking::AllocTracker;
     macro_rules! validate_variant {
         ($t:ident, $val:expr, $vt:expr) => {
             let v = $val;
@@ -1137,6 +1610,149 @@ mod test {
         validate_variant!(Bu64, Box::new(11976u64), VT_PUI8);
     }
     #[test]
+    fn test_owned_variant() {
+        let var = OwnedVariant::new(1337i32).unwrap();
+        assert_eq!(var.vartype(), VT_I4);
+        assert_eq!(var.value().unwrap(), VariantValue::I4(1337));
+
+        let var = OwnedVariant::from_value(VariantValue::from("testing abc1267 ?Ťũřǐꝥꞔ")).unwrap();
+        assert_eq!(var.vartype(), VT_BSTR);
+        let pvar = var.into_raw();
+        let var = unsafe { OwnedVariant::from_raw(pvar) };
+        assert_eq!(var.value().unwrap(), VariantValue::from("testing abc1267 ?Ťũřǐꝥꞔ"));
+    }
+    #[test]
+    fn test_variant_ref() {
+        let var = OwnedVariant::new(String::from("testing abc1267 ?Ťũřǐꝥꞔ")).unwrap();
+        let vref = unsafe { VariantRef::from_raw(var.as_ptr()) }.unwrap();
+        assert_eq!(vref.as_string().unwrap(), "testing abc1267 ?Ťũřǐꝥꞔ");
+        // reading again must still see the same BSTR
+        assert_eq!(vref.as_bstr().unwrap().len(), "testing abc1267 ?Ťũřǐꝥꞔ".encode_utf16().count());
+        match vref.as_i32() {
+            Err(FromVariantError::VarTypeDoesNotMatch{expected, found}) => {
+                assert_eq!(expected, VT_I4);
+                assert_eq!(found, VT_BSTR);
+            },
+            r => panic!("unexpected result: {:?}", r)
+        }
+        assert_eq!(var.vartype(), VT_BSTR);
+    }
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn test_variant_ref_scalars() {
+        let var = OwnedVariant::new(Currency::from(137)).unwrap();
+        let vref = unsafe { VariantRef::from_raw(var.as_ptr()) }.unwrap();
+        assert_eq!(vref.as_currency().unwrap(), Currency::from(137));
+
+        let var = OwnedVariant::new(true).unwrap();
+        let vref = unsafe { VariantRef::from_raw(var.as_ptr()) }.unwrap();
+        assert_eq!(vref.as_bool().unwrap(), true);
+        assert!(vref.as_dispatch().is_err());
+
+        assert!(unsafe { VariantRef::from_raw(null_mut()) }.is_err());
+    }
+    #[test]
+    fn test_owned_variant_empty() {
+        let var = OwnedVariant::default();
+        assert_eq!(var.vartype(), VT_EMPTY);
+        assert_eq!(var.value().unwrap(), VariantValue::Empty);
+    }
+    #[test]
+    fn test_write_into() {
+        let mut var = OwnedVariant::new(String::from("previous")).unwrap();
+        unsafe { 1337i32.write_into(var.as_mut_ptr()) }.unwrap();
+        assert_eq!(var.value().unwrap(), VariantValue::I4(1337));
+        unsafe { String::from("next").write_into(var.as_mut_ptr()) }.unwrap();
+        assert_eq!(var.value().unwrap(), VariantValue::from("next"));
+        match unsafe { 1i32.write_into(null_mut()) } {
+            Err(IntoVariantError::VariantPtrNull) => {},
+            r => panic!("unexpected result: {:?}", r)
+        }
+
+        let tracker = AllocTracker::start();
+        {
+            let mut var = OwnedVariant::new(vec![1i32, 2]).unwrap();
+            let psa = unsafe { *(*var.as_ptr()).n1.n2().n3.parray() };
+            assert_eq!(unsafe { allocator().safe_array_lock(psa) }, 0);
+            match unsafe { String::from("leak").write_into(var.as_mut_ptr()) } {
+                Err(IntoVariantError::VariantClearFailed{hr}) => assert_eq!(hr as u32, 0x8002_000D),
+                r => panic!("unexpected result: {:?}", r)
+            }
+            assert_eq!(var.vartype(), VT_ARRAY | VT_I4);
+            assert_eq!(unsafe { allocator().safe_array_unlock(psa) }, 0);
+        }
+        tracker.assert_no_leaks();
+    }
+    #[test]
+    fn test_into_raw_variant() {
+        let mut raw = 2.5f64.into_raw_variant().unwrap();
+        assert_eq!(unsafe { raw.n1.n2().vt } as u32, VT_R8);
+        assert_eq!(unsafe { *raw.n1.n2().n3.dblVal() }, 2.5);
+        let p = Ptr::with_checked(&mut raw as *mut VARIANT).unwrap();
+        assert_eq!(f64::from_variant(p).unwrap(), 2.5);
+
+        let mut raw = String::from("raw").into_raw_variant().unwrap();
+        assert_eq!(VariantRef::new(&raw).as_string().unwrap(), "raw");
+        unsafe { allocator().variant_clear(&mut raw) };
+    }
+    #[test]
+    fn test_unpaired_surrogate() {
+        let wide = [0x61, 0xD800, 0x62];
+        let mut raw: VARIANT = unsafe { mem::zeroed() };
+        unsafe {
+            let n2 = raw.n1.n2_mut();
+            n2.vt = VT_BSTR as u16;
+            *n2.n3.bstrVal_mut() = BString::from_wide(&wide).unwrap().into_raw();
+        }
+        let pvar = heap_variant(raw);
+        let os = OsString::from_variant(pvar).unwrap();
+        match String::from_variant(pvar) {
+            Err(FromVariantError::InvalidUtf16{index}) => assert_eq!(index, 1),
+            r => panic!("unexpected result: {:?}", r)
+        }
+        drop(unsafe { OwnedVariant::from_raw(pvar) });
+
+        let var = OwnedVariant::new(os.clone()).unwrap();
+        let vref = unsafe { VariantRef::from_raw(var.as_ptr()) }.unwrap();
+        assert_eq!(vref.as_bstr().unwrap(), &wide[..]);
+        match vref.as_string() {
+            Err(FromVariantError::InvalidUtf16{index}) => assert_eq!(index, 1),
+            r => panic!("unexpected result: {:?}", r)
+        }
+        match vref.value() {
+            Err(FromVariantError::InvalidUtf16{index}) => assert_eq!(index, 1),
+            r => panic!("unexpected result: {:?}", r)
+        }
+        assert_eq!(vref.as_os_string().unwrap(), os);
+    }
+    #[test]
+    fn test_owned_array() {
+        let tracker = AllocTracker::start();
+        {
+            let var = OwnedVariant::new(vec![1i32, 2, 3]).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_I4);
+            let var = OwnedVariant::new(vec![String::from("a")]).unwrap();
+            assert_eq!(var.vartype(), VT_ARRAY | VT_BSTR);
+        }
+        tracker.assert_no_leaks();
+
+        let psa = vec![Variant::new(1i32)].into_iter().into_safearray().unwrap();
+        let pvar = heap_variant(safearray_variant(psa, VT_ARRAY | VT_VARIANT));
+        match Vec::<i32>::from_variant(pvar) {
+            Err(FromVariantError::VarTypeDoesNotMatch{expected, found}) => {
+                assert_eq!(expected, VT_ARRAY | VT_I4);
+                assert_eq!(found, VT_ARRAY | VT_VARIANT);
+            },
+            r => panic!("unexpected result: {:?}", r)
+        }
+        drop(unsafe { OwnedVariant::from_raw(pvar) });
+
+        let psa = vec![String::from("x"), String::from("y")].into_iter().into_safearray().unwrap();
+        let pvar = heap_variant(safearray_variant(psa, VT_ARRAY | VT_BSTR));
+        assert_eq!(Vec::<String>::from_variant(pvar).unwrap(), vec!["x", "y"]);
+        drop(unsafe { OwnedVariant::from_raw(pvar) });
+    }
+    #[test]
     fn test_send() {
         fn assert_send<T: Send>() {}
         assert_send::<Variant<i64>>();
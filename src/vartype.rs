@@ -0,0 +1,363 @@
+//! # VarType
+//! A typed VARTYPE, separating the base type from the VT_VECTOR, VT_ARRAY and VT_BYREF
+//! flags. It prints as `VT_ARRAY|VT_I4`, parses that form back and knows which
+//! combinations are legal in a VARIANT, a SAFEARRAY or a PROPVARIANT.
+//!
+use std::fmt;
+use std::str::FromStr;
+
+use super::errors::ParseVarTypeError;
+use super::sys::{
+    VT_ARRAY,
+    VT_BLOB,
+    VT_BLOB_OBJECT,
+    VT_BOOL,
+    VT_BSTR,
+    VT_BSTR_BLOB,
+    VT_BYREF,
+    VT_CARRAY,
+    VT_CF,
+    VT_CLSID,
+    VT_CY,
+    VT_DATE,
+    VT_DECIMAL,
+    VT_DISPATCH,
+    VT_EMPTY,
+    VT_ERROR,
+    VT_FILETIME,
+    VT_HRESULT,
+    VT_I1,
+    VT_I2,
+    VT_I4,
+    VT_I8,
+    VT_INT,
+    VT_INT_PTR,
+    VT_LPSTR,
+    VT_LPWSTR,
+    VT_NULL,
+    VT_PTR,
+    VT_R4,
+    VT_R8,
+    VT_RECORD,
+    VT_RESERVED,
+    VT_SAFEARRAY,
+    VT_STORAGE,
+    VT_STORED_OBJECT,
+    VT_STREAM,
+    VT_STREAMED_OBJECT,
+    VT_TYPEMASK,
+    VT_UI1,
+    VT_UI2,
+    VT_UI4,
+    VT_UI8,
+    VT_UINT,
+    VT_UINT_PTR,
+    VT_UNKNOWN,
+    VT_USERDEFINED,
+    VT_VARIANT,
+    VT_VECTOR,
+    VT_VERSIONED_STREAM,
+    VT_VOID,
+};
+
+/// may appear in a VARIANT
+const V: u8 = 0b0001;
+/// may appear in a TYPEDESC
+const T: u8 = 0b0010;
+/// may appear in an OLE property set
+const P: u8 = 0b0100;
+/// may appear in a Safe Array
+const S: u8 = 0b1000;
+
+/// The VARENUM usage key from `variant.rs`, as (base type, name, usage).
+///
+/// VT_I8 and VT_UI8 are marked [T][P] there, but have been legal in VARIANTs and
+/// SAFEARRAYs since Windows XP and this crate converts them, so they are [V][S] here too.
+const VARENUM: &[(u32, &str, u8)] = &[
+    (VT_EMPTY,              "VT_EMPTY",             V | P),
+    (VT_NULL,               "VT_NULL",              V | P),
+    (VT_I2,                 "VT_I2",                V | T | P | S),
+    (VT_I4,                 "VT_I4",                V | T | P | S),
+    (VT_R4,                 "VT_R4",                V | T | P | S),
+    (VT_R8,                 "VT_R8",                V | T | P | S),
+    (VT_CY,                 "VT_CY",                V | T | P | S),
+    (VT_DATE,               "VT_DATE",              V | T | P | S),
+    (VT_BSTR,               "VT_BSTR",              V | T | P | S),
+    (VT_DISPATCH,           "VT_DISPATCH",          V | T | S),
+    (VT_ERROR,              "VT_ERROR",             V | T | P | S),
+    (VT_BOOL,               "VT_BOOL",              V | T | P | S),
+    (VT_VARIANT,            "VT_VARIANT",           V | T | P | S),
+    (VT_UNKNOWN,            "VT_UNKNOWN",           V | T | S),
+    (VT_DECIMAL,            "VT_DECIMAL",           V | T | S),
+    (VT_RECORD,             "VT_RECORD",            V | P | S),
+    (VT_I1,                 "VT_I1",                V | T | P | S),
+    (VT_UI1,                "VT_UI1",               V | T | P | S),
+    (VT_UI2,                "VT_UI2",               V | T | P | S),
+    (VT_UI4,                "VT_UI4",               V | T | P | S),
+    (VT_I8,                 "VT_I8",                V | T | P | S),
+    (VT_UI8,                "VT_UI8",               V | T | P | S),
+    (VT_INT,                "VT_INT",               V | T | P | S),
+    (VT_UINT,               "VT_UINT",              V | T | S),
+    (VT_INT_PTR,            "VT_INT_PTR",           T),
+    (VT_UINT_PTR,           "VT_UINT_PTR",          T),
+    (VT_VOID,               "VT_VOID",              T),
+    (VT_HRESULT,            "VT_HRESULT",           T),
+    (VT_PTR,                "VT_PTR",               T),
+    (VT_SAFEARRAY,          "VT_SAFEARRAY",         T),
+    (VT_CARRAY,             "VT_CARRAY",            T),
+    (VT_USERDEFINED,        "VT_USERDEFINED",       T),
+    (VT_LPSTR,              "VT_LPSTR",             T | P),
+    (VT_LPWSTR,             "VT_LPWSTR",            T | P),
+    (VT_FILETIME,           "VT_FILETIME",          P),
+    (VT_BLOB,               "VT_BLOB",              P),
+    (VT_STREAM,             "VT_STREAM",            P),
+    (VT_STORAGE,            "VT_STORAGE",           P),
+    (VT_STREAMED_OBJECT,    "VT_STREAMED_OBJECT",   P),
+    (VT_STORED_OBJECT,      "VT_STORED_OBJECT",     P),
+    (VT_VERSIONED_STREAM,   "VT_VERSIONED_STREAM",  P),
+    (VT_BLOB_OBJECT,        "VT_BLOB_OBJECT",       P),
+    (VT_CF,                 "VT_CF",                P),
+    (VT_CLSID,              "VT_CLSID",             P),
+    (VT_BSTR_BLOB,          "VT_BSTR_BLOB",         0),
+];
+
+/// The flags in the order they are printed
+const FLAGS: &[(u32, &str)] = &[
+    (VT_RESERVED,   "VT_RESERVED"),
+    (VT_VECTOR,     "VT_VECTOR"),
+    (VT_ARRAY,      "VT_ARRAY"),
+    (VT_BYREF,      "VT_BYREF"),
+];
+
+/// A VARTYPE: a base type from VARENUM, optionally combined with the VT_VECTOR,
+/// VT_ARRAY, VT_BYREF and VT_RESERVED flags.
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::sys::{VT_ARRAY, VT_I4};
+/// use oaidl::VarType;
+///
+/// fn main() {
+///     let vt = VarType::from(VT_ARRAY | VT_I4);
+///     assert_eq!(vt.base(), VT_I4);
+///     assert!(vt.is_array());
+///     assert_eq!(vt.to_string(), "VT_ARRAY|VT_I4");
+///     assert_eq!("VT_ARRAY|VT_I4".parse::<VarType>().unwrap(), vt);
+///     assert!(vt.is_legal_in_variant());
+///     assert!(!vt.is_legal_in_safearray());
+/// }
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct VarType(u32);
+
+impl VarType {
+    /// Builds a `VarType` from a base type and flags
+    pub fn new(base: u32, flags: u32) -> VarType {
+        VarType(base | flags)
+    }
+
+    /// The base type, without any flags
+    pub fn base(self) -> u32 {
+        self.0 & VT_TYPEMASK
+    }
+
+    /// The flags, without the base type
+    pub fn flags(self) -> u32 {
+        self.0 & !VT_TYPEMASK
+    }
+
+    /// VT_ARRAY is set
+    pub fn is_array(self) -> bool {
+        self.0 & VT_ARRAY != 0
+    }
+
+    /// VT_BYREF is set
+    pub fn is_byref(self) -> bool {
+        self.0 & VT_BYREF != 0
+    }
+
+    /// VT_VECTOR is set
+    pub fn is_vector(self) -> bool {
+        self.0 & VT_VECTOR != 0
+    }
+
+    /// The name of the base type, `None` if it is not in VARENUM
+    pub fn base_name(self) -> Option<&'static str> {
+        VARENUM.iter().find(|e| e.0 == self.base()).map(|e| e.1)
+    }
+
+    fn usage(self) -> u8 {
+        VARENUM.iter().find(|e| e.0 == self.base()).map_or(0, |e| e.2)
+    }
+
+    /// Whether the combination may appear in a VARIANT: a [V] type, a VT_ARRAY of a
+    /// [S] type, or either of those behind VT_BYREF.
+    pub fn is_legal_in_variant(self) -> bool {
+        let base = self.base();
+        match self.flags() {
+            0 => self.usage() & V != 0,
+            VT_BYREF => self.usage() & V != 0 && base != VT_EMPTY && base != VT_NULL,
+            flags if flags == VT_ARRAY || flags == VT_ARRAY | VT_BYREF => self.usage() & S != 0,
+            _ => false,
+        }
+    }
+
+    /// Whether the type may be the element type of a SAFEARRAY. No flags are allowed.
+    pub fn is_legal_in_safearray(self) -> bool {
+        self.flags() == 0 && self.usage() & S != 0
+    }
+
+    /// Whether the combination may appear in a PROPVARIANT: a [P] type, a VT_VECTOR of
+    /// a [P] type, a VT_ARRAY of a [S] type or a VT_BYREF of a [V] type.
+    pub fn is_legal_in_propvariant(self) -> bool {
+        let base = self.base();
+        let not_empty = base != VT_EMPTY && base != VT_NULL;
+        match self.flags() {
+            0 => self.usage() & P != 0,
+            VT_VECTOR => self.usage() & P != 0 && not_empty,
+            VT_ARRAY => self.usage() & S != 0,
+            VT_BYREF => self.usage() & V != 0 && not_empty,
+            _ => false,
+        }
+    }
+}
+
+impl From<u32> for VarType {
+    fn from(vt: u32) -> VarType {
+        VarType(vt)
+    }
+}
+
+impl From<u16> for VarType {
+    fn from(vt: u16) -> VarType {
+        VarType(u32::from(vt))
+    }
+}
+
+impl From<VarType> for u32 {
+    fn from(vt: VarType) -> u32 {
+        vt.0
+    }
+}
+
+impl PartialEq<u32> for VarType {
+    fn eq(&self, other: &u32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(flag, name) in FLAGS {
+            if self.0 & flag != 0 {
+                write!(f, "{}|", name)?;
+            }
+        }
+        match self.base_name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "0x{:x}", self.base()),
+        }
+    }
+}
+
+impl fmt::Debug for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl FromStr for VarType {
+    type Err = ParseVarTypeError;
+
+    /// Parses names separated by `|`, like `VT_ARRAY|VT_I4`. Numbers (`12`, `0x2000`)
+    /// are accepted in place of names.
+    fn from_str(s: &str) -> Result<VarType, ParseVarTypeError> {
+        let mut vt = 0;
+        for token in s.split('|').map(str::trim) {
+            let flag = FLAGS.iter().map(|e| (e.0, e.1))
+                .chain(VARENUM.iter().map(|e| (e.0, e.1)))
+                .find(|e| e.1.eq_ignore_ascii_case(token))
+                .map(|e| e.0);
+            let value = match flag {
+                Some(value) => Some(value),
+                None if token.starts_with("0x") || token.starts_with("0X") => u32::from_str_radix(&token[2..], 16).ok(),
+                None => u32::from_str(token).ok(),
+            };
+            match value {
+                Some(value) if value <= 0xffff => vt |= value,
+                _ => return Err(ParseVarTypeError::UnknownName{name: token.to_string()}),
+            }
+        }
+        Ok(VarType(vt))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(VarType::from(VT_I4).to_string(), "VT_I4");
+        assert_eq!(VarType::from(VT_ARRAY | VT_I4).to_string(), "VT_ARRAY|VT_I4");
+        assert_eq!(VarType::from(VT_BYREF | VT_ARRAY | VT_VARIANT).to_string(), "VT_ARRAY|VT_BYREF|VT_VARIANT");
+        assert_eq!(VarType::from(VT_VECTOR | VT_LPWSTR).to_string(), "VT_VECTOR|VT_LPWSTR");
+        assert_eq!(VarType::from(0x7ffu32).to_string(), "0x7ff");
+        assert_eq!(VarType::from(8195u32).to_string(), "VT_ARRAY|VT_I4");
+        assert_eq!(format!("{:?}", VarType::from(VT_ARRAY | VT_I4)), "VT_ARRAY|VT_I4");
+    }
+
+    #[test]
+    fn test_parse() {
+        for &vt in &[VT_EMPTY, VT_ARRAY | VT_BSTR, VT_BYREF | VT_ARRAY | VT_VARIANT, VT_VECTOR | VT_CF, 0x7ff, VT_RESERVED | VT_I2] {
+            let vt = VarType::from(vt);
+            assert_eq!(vt.to_string().parse::<VarType>().unwrap(), vt);
+        }
+        assert_eq!(" vt_byref | VT_I2 ".parse::<VarType>().unwrap(), VT_BYREF | VT_I2);
+        assert_eq!("0x2000|3".parse::<VarType>().unwrap(), VT_ARRAY | VT_I4);
+        assert!("VT_I4|VT_BOGUS".parse::<VarType>().is_err());
+        assert!("".parse::<VarType>().is_err());
+        assert!("0x10000".parse::<VarType>().is_err());
+    }
+
+    #[test]
+    fn test_flags() {
+        let vt = VarType::new(VT_UI1, VT_BYREF | VT_ARRAY);
+        assert_eq!(vt.base(), VT_UI1);
+        assert_eq!(vt.flags(), VT_BYREF | VT_ARRAY);
+        assert!(vt.is_array() && vt.is_byref() && !vt.is_vector());
+        assert_eq!(vt.base_name(), Some("VT_UI1"));
+    }
+
+    #[test]
+    fn test_legality() {
+        let vt = |vt: u32| VarType::from(vt);
+        assert!(vt(VT_EMPTY).is_legal_in_variant());
+        assert!(vt(VT_I8).is_legal_in_variant());
+        assert!(vt(VT_ARRAY | VT_BSTR).is_legal_in_variant());
+        assert!(vt(VT_BYREF | VT_ARRAY | VT_I4).is_legal_in_variant());
+        assert!(vt(VT_BYREF | VT_VARIANT).is_legal_in_variant());
+        assert!(!vt(VT_BYREF | VT_NULL).is_legal_in_variant());
+        assert!(!vt(VT_ARRAY | VT_EMPTY).is_legal_in_variant());
+        assert!(!vt(VT_VECTOR | VT_I4).is_legal_in_variant());
+        assert!(!vt(VT_LPWSTR).is_legal_in_variant());
+        assert!(!vt(VT_PTR).is_legal_in_variant());
+
+        assert!(vt(VT_DISPATCH).is_legal_in_safearray());
+        assert!(vt(VT_RECORD).is_legal_in_safearray());
+        assert!(!vt(VT_NULL).is_legal_in_safearray());
+        assert!(!vt(VT_ARRAY | VT_I4).is_legal_in_safearray());
+
+        assert!(vt(VT_LPWSTR).is_legal_in_propvariant());
+        assert!(vt(VT_VECTOR | VT_LPSTR).is_legal_in_propvariant());
+        assert!(vt(VT_ARRAY | VT_DECIMAL).is_legal_in_propvariant());
+        assert!(vt(VT_BYREF | VT_DECIMAL).is_legal_in_propvariant());
+        assert!(!vt(VT_DISPATCH).is_legal_in_propvariant());
+        assert!(!vt(VT_VECTOR | VT_EMPTY).is_legal_in_propvariant());
+        assert!(!vt(VT_VECTOR | VT_ARRAY | VT_I4).is_legal_in_propvariant());
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+use super::alloc::allocator;
+use super::array::SafeArrayPod;
+use super::errors::FromSafeArrayError;
+use super::sys::{c_void, LONG, SAFEARRAY, VARTYPE};
+use super::vartype::VarType;
+
+/// Locks `psa` with `SafeArrayAccessData` once it is known to hold elements of type `T`,
+/// returning its data and the number of elements over all dimensions
+#[allow(clippy::redundant_field_names)]
+unsafe fn access<T: SafeArrayPod>(psa: *mut SAFEARRAY) -> Result<(*mut T, usize), FromSafeArrayError> {
+    if psa.is_null() {
+        return Err(FromSafeArrayError::SafeArrayPtrNull);
+    }
+    let mut vt: VARTYPE = 0;
+    let hr = allocator().safe_array_get_vartype(psa, &mut vt);
+    if hr != 0 {
+        return Err(FromSafeArrayError::SafeArrayGetVartypeFailed{hr: hr});
+    }
+    if u32::from(vt) != T::SFTYPE {
+        return Err(FromSafeArrayError::VarTypeDoesNotMatch{expected: VarType::from(T::SFTYPE), found: VarType::from(u32::from(vt))});
+    }
+    let cb = allocator().safe_array_get_elemsize(psa);
+    if cb as usize != mem::size_of::<T>() {
+        return Err(FromSafeArrayError::ElementSizeDoesNotMatch{expected: mem::size_of::<T>(), found: cb});
+    }
+
+    let sa_dims = allocator().safe_array_get_dim(psa);
+    if sa_dims == 0 {
+        return Err(FromSafeArrayError::SafeArrayDimsInvalid{sa_dims: sa_dims});
+    }
+    let mut len = 1usize;
+    for dim in 1..=sa_dims {
+        let mut l_bound: LONG = 0;
+        let mut r_bound: LONG = 0;
+        let hr = allocator().safe_array_get_lbound(psa, dim, &mut l_bound);
+        if hr != 0 {
+            return Err(FromSafeArrayError::SafeArrayLBoundFailed{hr: hr});
+        }
+        let hr = allocator().safe_array_get_ubound(psa, dim, &mut r_bound);
+        if hr != 0 {
+            return Err(FromSafeArrayError::SafeArrayRBoundFailed{hr: hr});
+        }
+        len *= (i64::from(r_bound) - i64::from(l_bound) + 1) as usize;
+    }
+
+    let mut data: *mut c_void = ::std::ptr::null_mut();
+    let hr = allocator().safe_array_access_data(psa, &mut data);
+    if hr != 0 {
+        return Err(FromSafeArrayError::SafeArrayAccessDataFailed{hr: hr});
+    }
+    Ok((data as *mut T, len))
+}
+
+/// The slice of `len` elements at `data`, which may be null for an empty array
+unsafe fn data_slice<'a, T>(data: *mut T, len: usize) -> &'a mut [T] {
+    if len == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(data, len)
+    }
+}
+
+/// Borrowed view of the data of a SAFEARRAY, without copying it.
+///
+/// The array is locked with `SafeArrayAccessData` while the view exists, so it cannot be
+/// destroyed or resized, and is unlocked when the view is dropped. The elements of every
+/// dimension are in one slice, in column-major order. Only [`SafeArrayPod`] types can be
+/// viewed, since their Rust and SAFEARRAY layouts are the same.
+///
+/// [`SafeArrayPod`]: trait.SafeArrayPod.html
+///
+/// ## Example usage
+///
+/// ```
+/// extern crate oaidl;
+///
+/// use oaidl::{SafeArrayExt, SafeArrayView};
+///
+/// fn main() {
+///     let psa = vec![1.0f64, 2.0, 3.0].into_iter().into_safearray().unwrap();
+///     let view = unsafe { SafeArrayView::<f64>::new(psa.as_ptr()) }.unwrap();
+///     assert_eq!(view.iter().sum::<f64>(), 6.0);
+///     drop(view);
+///     let _ = <dyn ExactSizeIterator<Item=f64>>::from_safearray(psa.as_ptr());
+/// }
+/// ```
+pub struct SafeArrayView<'a, T: SafeArrayPod + 'a> {
+    psa: *mut SAFEARRAY,
+    data: &'a [T],
+}
+
+impl<'a, T: SafeArrayPod> SafeArrayView<'a, T> {
+    /// Locks `psa` and views its data, after checking that its vartype is `T::SFTYPE` and
+    /// its element size is the size of `T`.
+    ///
+    /// ## Safety
+    ///
+    /// `psa` must be null or a valid SAFEARRAY which outlives `'a` and whose data is not
+    /// modified while viewed.
+    #[allow(clippy::redundant_field_names)]
+    pub unsafe fn new(psa: *mut SAFEARRAY) -> Result<SafeArrayView<'a, T>, FromSafeArrayError> {
+        let (data, len) = access::<T>(psa)?;
+        Ok(SafeArrayView { psa: psa, data: data_slice(data, len) })
+    }
+
+    /// The elements of every dimension, in column-major order
+    pub fn as_slice(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<'a, T: SafeArrayPod> Deref for SafeArrayView<'a, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<'a, T: SafeArrayPod> Drop for SafeArrayView<'a, T> {
+    fn drop(&mut self) {
+        unsafe { allocator().safe_array_unaccess_data(self.psa) };
+    }
+}
+
+impl<'a, T: SafeArrayPod + fmt::Debug> fmt::Debug for SafeArrayView<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.data, f)
+    }
+}
+
+/// Mutably borrowed view of the data of a SAFEARRAY, like [`SafeArrayView`] but writing
+/// straight into the array.
+///
+/// [`SafeArrayView`]: struct.SafeArrayView.html
+pub struct SafeArrayViewMut<'a, T: SafeArrayPod + 'a> {
+    psa: *mut SAFEARRAY,
+    data: *mut T,
+    len: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T: SafeArrayPod> SafeArrayViewMut<'a, T> {
+    /// Locks `psa` and views its data, after checking that its vartype is `T::SFTYPE` and
+    /// its element size is the size of `T`.
+    ///
+    /// ## Safety
+    ///
+    /// `psa` must be null or a valid SAFEARRAY which outlives `'a` and whose data is not
+    /// accessed by anything else while viewed.
+    #[allow(clippy::redundant_field_names)]
+    pub unsafe fn new(psa: *mut SAFEARRAY) -> Result<SafeArrayViewMut<'a, T>, FromSafeArrayError> {
+        let (data, len) = access::<T>(psa)?;
+        Ok(SafeArrayViewMut { psa: psa, data: data, len: len, _marker: PhantomData })
+    }
+
+    /// The elements of every dimension, in column-major order
+    pub fn as_slice(&self) -> &[T] {
+        if self.len == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.data, self.len) }
+        }
+    }
+
+    /// The elements of every dimension, in column-major order
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { data_slice(self.data, self.len) }
+    }
+}
+
+impl<'a, T: SafeArrayPod> Deref for SafeArrayViewMut<'a, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<'a, T: SafeArrayPod> DerefMut for SafeArrayViewMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<'a, T: SafeArrayPod> Drop for SafeArrayViewMut<'a, T> {
+    fn drop(&mut self) {
+        unsafe { allocator().safe_array_unaccess_data(self.psa) };
+    }
+}
+
+impl<'a, T: SafeArrayPod + fmt::Debug> fmt::Debug for SafeArrayViewMut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::array::SafeArrayExt;
+    use super::super::ndarray::NdSafeArray;
+
+    #[test]
+    fn test_view() {
+        let psa = vec![1i32, 2, 3].into_iter().into_safearray().unwrap().as_ptr();
+        {
+            let mut view = unsafe { SafeArrayViewMut::<i32>::new(psa) }.unwrap();
+            assert_eq!(unsafe { (*psa).cLocks }, 1);
+            assert!(unsafe { allocator().safe_array_destroy(psa) } != 0);
+            view[1] = 20;
+        }
+        assert_eq!(unsafe { (*psa).cLocks }, 0);
+        {
+            let view = unsafe { SafeArrayView::<i32>::new(psa) }.unwrap();
+            let view2 = unsafe { SafeArrayView::<i32>::new(psa) }.unwrap();
+            assert_eq!(&*view, &[1, 20, 3]);
+            assert_eq!(format!("{:?}", view2), "[1, 20, 3]");
+        }
+        assert_eq!(<dyn ExactSizeIterator<Item=i32>>::from_safearray(psa).unwrap(), vec![1, 20, 3]);
+    }
+
+    #[test]
+    fn test_checks() {
+        let psa = vec![1i32, 2, 3].into_iter().into_safearray().unwrap().as_ptr();
+        match unsafe { SafeArrayView::<f32>::new(psa) } {
+            Err(FromSafeArrayError::VarTypeDoesNotMatch{..}) => {},
+            r => panic!("unexpected result: {:?}", r.map(|v| v.to_vec()))
+        }
+        assert_eq!(unsafe { (*psa).cLocks }, 0);
+        assert!(unsafe { SafeArrayView::<i32>::new(::std::ptr::null_mut()) }.is_err());
+        unsafe { allocator().safe_array_destroy(psa) };
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let arr = NdSafeArray::with_lbounds(vec![2, 3], vec![1, 1], (0..6).map(f64::from).collect()).unwrap();
+        let psa = arr.clone().into_safearray().unwrap().as_ptr();
+        {
+            let view = unsafe { SafeArrayView::<f64>::new(psa) }.unwrap();
+            assert_eq!(view.as_slice(), arr.as_slice());
+        }
+        unsafe { allocator().safe_array_destroy(psa) };
+
+        let psa = NdSafeArray::<u8>::from_shape_vec(vec![4, 0], vec![]).unwrap().into_safearray().unwrap().as_ptr();
+        assert!(unsafe { SafeArrayView::<u8>::new(psa) }.unwrap().is_empty());
+        unsafe { allocator().safe_array_destroy(psa) };
+    }
+}
@@ -0,0 +1,92 @@
+//! # WTF-8
+//! Outside Windows an `OsString` is a byte string, so UTF-16 which is not valid Unicode is
+//! stored as [WTF-8](https://simonsapin.github.io/wtf-8/): UTF-8 where an unpaired
+//! surrogate is encoded like any other code point below U+10000. Valid UTF-16 gives
+//! plain UTF-8, and decoding gives the original code units back.
+use std::char;
+
+/// Encodes UTF-16 code units, unpaired surrogates included, as WTF-8
+pub(crate) fn from_wide(wide: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(wide.len());
+    for c in char::decode_utf16(wide.iter().cloned()) {
+        match c {
+            Ok(c) => {
+                let mut buf = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            },
+            Err(e) => {
+                let s = e.unpaired_surrogate();
+                bytes.extend_from_slice(&[
+                    0xE0 | (s >> 12) as u8,
+                    0x80 | ((s >> 6) & 0x3F) as u8,
+                    0x80 | (s & 0x3F) as u8,
+                ]);
+            },
+        }
+    }
+    bytes
+}
+
+/// Decodes WTF-8 into UTF-16 code units, failing with the index of the first byte which
+/// does not start a valid sequence
+pub(crate) fn to_wide(bytes: &[u8]) -> Result<Vec<u16>, usize> {
+    let mut wide = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let b0 = bytes[ix];
+        let (mut cp, n, min) = match b0 {
+            0x00..=0x7F => (u32::from(b0), 1, 0),
+            0xC2..=0xDF => (u32::from(b0 & 0x1F), 2, 0x80),
+            0xE0..=0xEF => (u32::from(b0 & 0x0F), 3, 0x800),
+            0xF0..=0xF4 => (u32::from(b0 & 0x07), 4, 0x10000),
+            _ => return Err(ix),
+        };
+        if ix + n > bytes.len() {
+            return Err(ix);
+        }
+        for &b in &bytes[ix + 1..ix + n] {
+            if b & 0xC0 != 0x80 {
+                return Err(ix);
+            }
+            cp = (cp << 6) | u32::from(b & 0x3F);
+        }
+        if cp < min || cp > 0x10FFFF {
+            return Err(ix);
+        }
+        if cp >= 0x10000 {
+            let c = cp - 0x10000;
+            wide.push(0xD800 | (c >> 10) as u16);
+            wide.push(0xDC00 | (c & 0x3FF) as u16);
+        } else {
+            wide.push(cp as u16);
+        }
+        ix += n;
+    }
+    Ok(wide)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let wide: Vec<u16> = "a\u{e9}\u{20ac}\u{1F600}".encode_utf16().collect();
+        let bytes = from_wide(&wide);
+        assert_eq!(bytes, "a\u{e9}\u{20ac}\u{1F600}".as_bytes());
+        assert_eq!(to_wide(&bytes).unwrap(), wide);
+
+        let lone = [0x61, 0xD800, 0x62, 0xDFFF];
+        let bytes = from_wide(&lone);
+        assert_eq!(bytes, b"a\xED\xA0\x80b\xED\xBF\xBF");
+        assert_eq!(to_wide(&bytes).unwrap(), lone);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(to_wide(b"ok\xFF"), Err(2));
+        assert_eq!(to_wide(b"\xC0\x80"), Err(0));
+        assert_eq!(to_wide(b"a\xE2\x82"), Err(1));
+        assert_eq!(to_wide(b"\xF4\x90\x80\x80"), Err(0));
+    }
+}